@@ -1,3 +1,4 @@
+use crate::error::{self, SciImgError};
 use memmap::Mmap;
 use std::fs::File;
 use std::io;
@@ -53,15 +54,39 @@ impl BinFileReader {
         P: AsRef<Path> + Copy,
         PathBuf: From<P>,
     {
-        let file_ptr = File::open(file_path).expect("Error opening file");
-        let map: Mmap = unsafe { Mmap::map(&file_ptr).expect("Error creating memory map") };
+        BinFileReader::open_as_endiness(file_path, endiness).expect("Error opening file")
+    }
+
+    /// Opens a file for reading, returning an error rather than panicking if it cannot be opened
+    /// or memory mapped. Empty files cannot be mapped and are reported as invalid.
+    pub fn open<P>(file_path: P) -> error::Result<BinFileReader>
+    where
+        P: AsRef<Path> + Copy,
+        PathBuf: From<P>,
+    {
+        BinFileReader::open_as_endiness(file_path, Endian::LittleEndian)
+    }
 
-        BinFileReader {
+    pub fn open_as_endiness<P>(file_path: P, endiness: Endian) -> error::Result<BinFileReader>
+    where
+        P: AsRef<Path> + Copy,
+        PathBuf: From<P>,
+    {
+        let file_ptr = File::open(file_path)?;
+        if file_ptr.metadata()?.len() == 0 {
+            return Err(SciImgError::InvalidFormat(format!(
+                "File is empty: {}",
+                file_path.as_ref().display()
+            )));
+        }
+        let map: Mmap = unsafe { Mmap::map(&file_ptr)? };
+
+        Ok(BinFileReader {
             file_ptr,
             map,
             file_path: file_path.as_ref().into(),
             endiness,
-        }
+        })
     }
 
     pub fn set_endiness(&mut self, endiness: Endian) {
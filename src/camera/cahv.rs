@@ -1,4 +1,9 @@
-use crate::{camera::model::*, util::vec_to_str, vector::Vector};
+use crate::{
+    camera::cahvore::{PupilType, LINEARITY_PERSPECTIVE},
+    camera::model::*,
+    util::vec_to_str,
+    vector::Vector,
+};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
@@ -54,6 +59,14 @@ impl CameraModelTrait for Cahv {
         Vector::default()
     }
 
+    fn pupil_type(&self) -> PupilType {
+        PupilType::Perspective
+    }
+
+    fn linearity(&self) -> f64 {
+        LINEARITY_PERSPECTIVE
+    }
+
     fn box_clone(&self) -> CameraModelType {
         Box::new((*self).clone())
     }
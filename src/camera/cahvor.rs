@@ -1,5 +1,11 @@
 use crate::{
-    camera::cahv::*, camera::model::*, matrix::Matrix, max, min, util::vec_to_str, vector::Vector,
+    camera::cahv::*,
+    camera::cahvore::{PupilType, LINEARITY_PERSPECTIVE},
+    camera::model::*,
+    matrix::Matrix,
+    max, min,
+    util::vec_to_str,
+    vector::Vector,
 };
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
@@ -167,6 +173,14 @@ impl CameraModelTrait for Cahvor {
         Vector::default()
     }
 
+    fn pupil_type(&self) -> PupilType {
+        PupilType::Perspective
+    }
+
+    fn linearity(&self) -> f64 {
+        LINEARITY_PERSPECTIVE
+    }
+
     fn box_clone(&self) -> CameraModelType {
         Box::new((*self).clone())
     }
@@ -107,6 +107,14 @@ impl CameraModelTrait for Cahvore {
         self.e
     }
 
+    fn pupil_type(&self) -> PupilType {
+        self.pupil_type.clone()
+    }
+
+    fn linearity(&self) -> f64 {
+        self.linearity
+    }
+
     fn box_clone(&self) -> CameraModelType {
         Box::new((*self).clone())
     }
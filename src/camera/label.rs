@@ -0,0 +1,152 @@
+/*
+    Extraction of CAHV, CAHVOR and CAHVORE camera models from product labels.
+
+    Models are described by the GEOMETRIC_CAMERA_MODEL group of PDS3/ODL labels (named
+    GEOMETRIC_CAMERA_MODEL_PARMS in some missions) or the property of the same name in VICAR
+    labels. MODEL_TYPE names the model and MODEL_COMPONENT_1..9 carry its components in the
+    order given by MODEL_COMPONENT_ID, which defaults to C, A, H, V, O, R, E, T, P. For CAHVORE,
+    T is the pupil type (1 = perspective, 2 = fisheye, 3 = general) and P the linearity parameter.
+*/
+
+use crate::{
+    camera::cahv::Cahv,
+    camera::cahvor::Cahvor,
+    camera::cahvore::{Cahvore, PupilType, LINEARITY_FISHEYE, LINEARITY_PERSPECTIVE},
+    camera::model::CameraModel,
+    error,
+    pds3::{self, Value},
+    vector::Vector,
+    vicar,
+};
+
+use anyhow::{anyhow, Result};
+
+const MODEL_GROUP_NAMES: [&str; 2] = ["GEOMETRIC_CAMERA_MODEL", "GEOMETRIC_CAMERA_MODEL_PARMS"];
+
+const DEFAULT_COMPONENT_IDS: [&str; 9] = ["C", "A", "H", "V", "O", "R", "E", "T", "P"];
+
+/// Builds a camera model from the keywords of a GEOMETRIC_CAMERA_MODEL group, looked up by
+/// the supplied function.
+pub fn from_keywords<'a, F>(get: F) -> Result<CameraModel>
+where
+    F: Fn(&str) -> Option<&'a Value>,
+{
+    let model_type = get("MODEL_TYPE")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow!("Camera model is missing MODEL_TYPE"))?
+        .to_uppercase();
+
+    let component_ids: Vec<String> = match get("MODEL_COMPONENT_ID").and_then(|v| v.as_vec()) {
+        Some(ids) => ids
+            .iter()
+            .map(|v| v.as_str().unwrap_or_default().to_uppercase())
+            .collect(),
+        None => DEFAULT_COMPONENT_IDS
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+    };
+
+    let component = |id: &str| -> Option<&'a Value> {
+        let index = component_ids.iter().position(|c| c == id)?;
+        get(&format!("MODEL_COMPONENT_{}", index + 1))
+    };
+
+    let vector = |id: &str| -> Result<Vector> {
+        let v = component(id)
+            .and_then(|v| v.as_f64_vec())
+            .ok_or_else(|| anyhow!("Camera model is missing component {}", id))?;
+        if v.len() != 3 {
+            return Err(anyhow!("Camera model component {} is not a 3-vector", id));
+        }
+        Ok(Vector::new(v[0], v[1], v[2]))
+    };
+
+    match model_type.as_str() {
+        "CAHV" => Ok(CameraModel::new(Box::new(Cahv {
+            c: vector("C")?,
+            a: vector("A")?,
+            h: vector("H")?,
+            v: vector("V")?,
+        }))),
+        "CAHVOR" => Ok(CameraModel::new(Box::new(Cahvor {
+            c: vector("C")?,
+            a: vector("A")?,
+            h: vector("H")?,
+            v: vector("V")?,
+            o: vector("O")?,
+            r: vector("R")?,
+        }))),
+        "CAHVORE" => {
+            let pupil_type = match component("T").and_then(|v| v.as_f64()) {
+                Some(t) => pupil_type_from_label_value(t)?,
+                None => PupilType::General,
+            };
+            let linearity = match pupil_type {
+                PupilType::Perspective => LINEARITY_PERSPECTIVE,
+                PupilType::Fisheye => LINEARITY_FISHEYE,
+                PupilType::General => component("P")
+                    .and_then(|v| v.as_f64())
+                    .unwrap_or(LINEARITY_FISHEYE),
+            };
+            Ok(CameraModel::new(Box::new(Cahvore {
+                c: vector("C")?,
+                a: vector("A")?,
+                h: vector("H")?,
+                v: vector("V")?,
+                o: vector("O")?,
+                r: vector("R")?,
+                e: vector("E")?,
+                pupil_type,
+                linearity,
+            })))
+        }
+        _ => Err(anyhow!("Unsupported camera model type: {}", model_type)),
+    }
+}
+
+fn pupil_type_from_label_value(value: f64) -> Result<PupilType> {
+    match value.round() as i64 {
+        1 => Ok(PupilType::Perspective),
+        2 => Ok(PupilType::Fisheye),
+        3 => Ok(PupilType::General),
+        _ => Err(anyhow!("Invalid CAHVORE pupil type: {}", value)),
+    }
+}
+
+/// Extracts the camera model from a parsed PDS3/ODL label
+pub fn from_pds3_label(label: &pds3::Label) -> Result<CameraModel> {
+    let group = MODEL_GROUP_NAMES
+        .iter()
+        .find_map(|n| label.find_block(n))
+        .ok_or_else(|| anyhow!("Label does not contain a camera model"))?;
+    from_keywords(|n| group.get(n))
+}
+
+/// Extracts the camera model from a parsed VICAR label
+pub fn from_vicar_label(label: &vicar::Label) -> Result<CameraModel> {
+    let property = MODEL_GROUP_NAMES
+        .iter()
+        .find_map(|n| label.property(n))
+        .ok_or_else(|| anyhow!("Label does not contain a camera model"))?;
+    from_keywords(|n| property.get(n))
+}
+
+/// Extracts the camera model from VICAR or PDS3/ODL label text
+pub fn from_label_str(text: &str) -> Result<CameraModel> {
+    if text.starts_with("LBLSIZE=") {
+        from_vicar_label(&vicar::Label::parse_str(text)?)
+    } else {
+        from_pds3_label(&pds3::Label::parse_str(text)?)
+    }
+}
+
+/// Extracts the camera model from the label of a VICAR or PDS3 file, or from a detached ODL label
+pub fn from_file(file_path: &str) -> Result<CameraModel> {
+    error::check_file_exists(file_path)?;
+    if vicar::is_vicar_file(file_path) {
+        from_vicar_label(&vicar::read_label(file_path)?)
+    } else {
+        from_pds3_label(&pds3::Label::from_file(file_path)?)
+    }
+}
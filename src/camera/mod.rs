@@ -1,4 +1,5 @@
 pub mod cahv;
 pub mod cahvor;
 pub mod cahvore;
+pub mod label;
 pub mod model;
@@ -1,5 +1,15 @@
-use crate::{camera::cahv, camera::cahvor, camera::cahvore, vector::Vector};
-use anyhow::Result;
+use crate::{
+    camera::cahv,
+    camera::cahvor,
+    camera::cahvore::{self, PupilType},
+    dem::Dem,
+    matrix::Matrix,
+    quaternion::Quaternion,
+    vector::{str_to_vec, Vector},
+};
+use anyhow::{anyhow, Result};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
 
 pub static EPSILON: f64 = 1.0e-15;
 pub static CONV: f64 = 1.0e-6;
@@ -44,16 +54,99 @@ impl LookVector {
         }
     }
 
+    /// Point `radius` along the ray, i.e. on a sphere centered on the ray's origin. To
+    /// intersect the ray with a sphere centered elsewhere, use `intersect_sphere`.
     pub fn intersect_to_sphere(&self, radius: f64) -> Vector {
         self.look_direction
             .normalized()
             .scale(radius)
             .add(&self.origin)
     }
+
+    /// Intersects the ray with the plane through `point` with the given normal. Returns None
+    /// if the ray is parallel to the plane or the plane lies behind the origin.
+    pub fn intersect_plane(&self, point: &Vector, normal: &Vector) -> Option<Vector> {
+        let direction = self.look_direction.normalized();
+        let dot = direction.dot_product(normal);
+        if dot.abs() < EPSILON {
+            return None;
+        }
+
+        let t = point.subtract(&self.origin).dot_product(normal) / dot;
+        if t < 0.0 {
+            None
+        } else {
+            Some(self.origin.add(&direction.scale(t)))
+        }
+    }
+
+    /// Intersects the ray with a sphere, returning the nearest intersection in front of the
+    /// origin. From inside the sphere, this is where the ray exits it.
+    pub fn intersect_sphere(&self, center: &Vector, radius: f64) -> Option<Vector> {
+        self.intersect_ellipsoid(center, &Vector::new(radius, radius, radius))
+    }
+
+    /// Intersects the ray with a triaxial ellipsoid aligned with the coordinate axes, with
+    /// semi-axes `radii` along X, Y and Z. Returns the nearest intersection in front of the
+    /// origin.
+    pub fn intersect_ellipsoid(&self, center: &Vector, radii: &Vector) -> Option<Vector> {
+        if radii.x <= 0.0 || radii.y <= 0.0 || radii.z <= 0.0 {
+            return None;
+        }
+
+        let direction = self.look_direction.normalized();
+        if direction.len() < EPSILON {
+            return None;
+        }
+
+        // Scaling the ellipsoid to a unit sphere leaves the distance along the ray unchanged
+        let o = self.origin.subtract(center).divide(radii);
+        let d = direction.divide(radii);
+
+        let a = d.dot_product(&d);
+        let b = 2.0 * o.dot_product(&d);
+        let c = o.dot_product(&o) - 1.0;
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        let sqrt_disc = discriminant.sqrt();
+        let near = (-b - sqrt_disc) / (2.0 * a);
+        let far = (-b + sqrt_disc) / (2.0 * a);
+        let t = if near >= 0.0 { near } else { far };
+        if t < 0.0 {
+            None
+        } else {
+            Some(self.origin.add(&direction.scale(t)))
+        }
+    }
+
+    /// Intersects the ray with a DEM by marching along it up to `max_range`
+    pub fn intersect_dem(&self, dem: &Dem, max_range: f64) -> Option<Vector> {
+        dem.intersect(self, max_range)
+    }
 }
 
 pub type CameraModelType = Box<dyn CameraModelTrait + 'static + Send + Sync>;
 
+/// A rotation which can be applied to the vectors of a camera model
+pub trait Rotation {
+    fn rotate_vector(&self, v: &Vector) -> Vector;
+}
+
+impl Rotation for Quaternion {
+    fn rotate_vector(&self, v: &Vector) -> Vector {
+        Quaternion::rotate_vector(self, v)
+    }
+}
+
+impl Rotation for Matrix {
+    fn rotate_vector(&self, v: &Vector) -> Vector {
+        self.multiply_vector(v)
+    }
+}
+
 impl Clone for CameraModelType {
     fn clone(&self) -> CameraModelType {
         self.box_clone()
@@ -75,6 +168,8 @@ pub trait CameraModelTrait {
     fn o(&self) -> Vector;
     fn r(&self) -> Vector;
     fn e(&self) -> Vector;
+    fn pupil_type(&self) -> PupilType;
+    fn linearity(&self) -> f64;
     fn serialize(&self) -> String;
 }
 
@@ -88,6 +183,78 @@ impl CameraModel {
         CameraModel { model: Some(model) }
     }
 
+    /// Rebuilds a camera model from the output of `serialize`. The model type is detected from
+    /// the number of components: four for CAHV, six for CAHVOR and nine for CAHVORE. The
+    /// CAHVORE pupil type is not serialized and is inferred from the linearity.
+    pub fn from_serialized(s: &str) -> Result<CameraModel> {
+        let parts: Vec<&str> = s.trim().split(';').map(|p| p.trim()).collect();
+
+        let vector = |i: usize| -> Result<Vector> {
+            let v = str_to_vec(parts[i])?;
+            if v.len() != 3 {
+                return Err(anyhow!(
+                    "Camera model component {} has {} values, expected 3",
+                    i + 1,
+                    v.len()
+                ));
+            }
+            Ok(Vector::new(v[0], v[1], v[2]))
+        };
+
+        let scalar = |i: usize| -> Result<f64> {
+            parts[i].parse::<f64>().map_err(|_| {
+                anyhow!(
+                    "Camera model component {} is not a valid number: {}",
+                    i + 1,
+                    parts[i]
+                )
+            })
+        };
+
+        match parts.len() {
+            4 => Ok(CameraModel::new(Box::new(cahv::Cahv {
+                c: vector(0)?,
+                a: vector(1)?,
+                h: vector(2)?,
+                v: vector(3)?,
+            }))),
+            6 => Ok(CameraModel::new(Box::new(cahvor::Cahvor {
+                c: vector(0)?,
+                a: vector(1)?,
+                h: vector(2)?,
+                v: vector(3)?,
+                o: vector(4)?,
+                r: vector(5)?,
+            }))),
+            9 => {
+                let linearity = scalar(7)?;
+                scalar(8)?;
+                let pupil_type = if linearity == cahvore::LINEARITY_PERSPECTIVE {
+                    PupilType::Perspective
+                } else if linearity == cahvore::LINEARITY_FISHEYE {
+                    PupilType::Fisheye
+                } else {
+                    PupilType::General
+                };
+                Ok(CameraModel::new(Box::new(cahvore::Cahvore {
+                    c: vector(0)?,
+                    a: vector(1)?,
+                    h: vector(2)?,
+                    v: vector(3)?,
+                    o: vector(4)?,
+                    r: vector(5)?,
+                    e: vector(6)?,
+                    pupil_type,
+                    linearity,
+                })))
+            }
+            n => Err(anyhow!(
+                "Unrecognized camera model: {} components, expected 4, 6 or 9",
+                n
+            )),
+        }
+    }
+
     pub fn is_valid(&self) -> bool {
         self.model.is_some()
     }
@@ -148,6 +315,20 @@ impl CameraModel {
         }
     }
 
+    pub fn pupil_type(&self) -> PupilType {
+        match &self.model {
+            Some(m) => m.pupil_type(),
+            None => PupilType::Perspective,
+        }
+    }
+
+    pub fn linearity(&self) -> f64 {
+        match &self.model {
+            Some(m) => m.linearity(),
+            None => cahvore::LINEARITY_PERSPECTIVE,
+        }
+    }
+
     pub fn f(&self) -> f64 {
         match &self.model {
             Some(m) => m.f(),
@@ -221,8 +402,8 @@ impl CameraModel {
                         o: m.o(),
                         r: m.r(),
                         e: m.e(),
-                        pupil_type: cahvore::PupilType::General,
-                        linearity: cahvore::LINEARITY_FISHEYE,
+                        pupil_type: m.pupil_type(),
+                        linearity: m.linearity(),
                     })))
                 } else {
                     Err("Wut?")
@@ -231,6 +412,110 @@ impl CameraModel {
         }
     }
 
+    /// Returns a model for an image whose pixel coordinates are an affine transform of those
+    /// of the original image, such that a point imaged at (sample, line) is imaged at
+    /// sample' = m[0][0] * sample + m[0][1] * line + m[0][2] and
+    /// line' = m[1][0] * sample + m[1][1] * line + m[1][2].
+    /// Only H and V change since the distortion terms of CAHVOR and CAHVORE are defined in
+    /// object space.
+    pub fn transform_image_coordinates(&self, m: &[[f64; 3]; 2]) -> Result<CameraModel> {
+        let model = match &self.model {
+            Some(model) => model,
+            None => return Err(anyhow!("Camera model is not valid")),
+        };
+        let (a, h, v) = (model.a(), model.h(), model.v());
+        let transform = |row: &[f64; 3]| -> Vector {
+            h.scale(row[0]).add(&v.scale(row[1])).add(&a.scale(row[2]))
+        };
+        Ok(rebuild_model(
+            model,
+            model.c(),
+            a,
+            transform(&m[0]),
+            transform(&m[1]),
+            model.o(),
+        ))
+    }
+
+    /// Returns the model moved into another coordinate frame, where a point P in the
+    /// current frame is located at rotation * P + translation. C is rotated and translated,
+    /// while the A, H, V and O vectors are rotated. The R and E terms are coefficients rather
+    /// than directions and are unchanged.
+    pub fn transform<R: Rotation>(
+        &self,
+        rotation: &R,
+        translation: &Vector,
+    ) -> Result<CameraModel> {
+        let model = match &self.model {
+            Some(model) => model,
+            None => return Err(anyhow!("Camera model is not valid")),
+        };
+        Ok(rebuild_model(
+            model,
+            rotation.rotate_vector(&model.c()).add(translation),
+            rotation.rotate_vector(&model.a()),
+            rotation.rotate_vector(&model.h()),
+            rotation.rotate_vector(&model.v()),
+            rotation.rotate_vector(&model.o()),
+        ))
+    }
+
+    /// Model for a subframe of the image with its top left corner at (x, y)
+    pub fn subframe(&self, x: f64, y: f64) -> Result<CameraModel> {
+        self.transform_image_coordinates(&[[1.0, 0.0, -x], [0.0, 1.0, -y]])
+    }
+
+    /// Model for the image with pixel coordinates scaled by the horizontal and vertical
+    /// factors (e.g. 0.5 for 2x2 binning). Scaling is about the image origin, with pixel
+    /// coordinates measured at pixel centers.
+    pub fn scaled(&self, scale_x: f64, scale_y: f64) -> Result<CameraModel> {
+        self.transform_image_coordinates(&[[scale_x, 0.0, 0.0], [0.0, scale_y, 0.0]])
+    }
+
+    /// Model for the image resampled to a new size such that the image extents, rather
+    /// than pixel centers, are aligned (as done by `Image::resize_to`)
+    pub fn resized(
+        &self,
+        from_width: usize,
+        from_height: usize,
+        to_width: usize,
+        to_height: usize,
+    ) -> Result<CameraModel> {
+        let sx = to_width as f64 / from_width as f64;
+        let sy = to_height as f64 / from_height as f64;
+        self.transform_image_coordinates(&[
+            [sx, 0.0, (sx - 1.0) * 0.5],
+            [0.0, sy, (sy - 1.0) * 0.5],
+        ])
+    }
+
+    /// Model for the image mirrored left to right
+    pub fn flipped_horizontal(&self, width: usize) -> Result<CameraModel> {
+        self.transform_image_coordinates(&[[-1.0, 0.0, width as f64 - 1.0], [0.0, 1.0, 0.0]])
+    }
+
+    /// Model for the image mirrored top to bottom
+    pub fn flipped_vertical(&self, height: usize) -> Result<CameraModel> {
+        self.transform_image_coordinates(&[[1.0, 0.0, 0.0], [0.0, -1.0, height as f64 - 1.0]])
+    }
+
+    /// Model for the image rotated clockwise by `theta` radians about its center (as done
+    /// by `Image::rotate`, which rotates about (width / 2, height / 2))
+    pub fn rotated(&self, theta: f64, width: usize, height: usize) -> Result<CameraModel> {
+        let (sin, cos) = theta.sin_cos();
+        let cx = width as f64 / 2.0;
+        let cy = height as f64 / 2.0;
+        self.transform_image_coordinates(&[
+            [cos, -sin, cx - cos * cx + sin * cy],
+            [sin, cos, cy - sin * cx - cos * cy],
+        ])
+    }
+
+    /// Model for the image with its rows and columns exchanged
+    pub fn transposed(&self) -> Result<CameraModel> {
+        self.transform_image_coordinates(&[[0.0, 1.0, 0.0], [1.0, 0.0, 0.0]])
+    }
+
     pub fn linearize_force_type(
         &self,
         cahvor_width: usize,
@@ -284,8 +569,8 @@ impl CameraModel {
                         o: m.o(),
                         r: m.r(),
                         e: m.e(),
-                        pupil_type: cahvore::PupilType::General,
-                        linearity: cahvore::LINEARITY_FISHEYE,
+                        pupil_type: m.pupil_type(),
+                        linearity: m.linearity(),
                     };
                     Ok(CameraModel::new(Box::new(cahvore::linearize(
                         &c,
@@ -353,8 +638,8 @@ impl CameraModel {
                         o: m.o(),
                         r: m.r(),
                         e: m.e(),
-                        pupil_type: cahvore::PupilType::General,
-                        linearity: cahvore::LINEARITY_FISHEYE,
+                        pupil_type: m.pupil_type(),
+                        linearity: m.linearity(),
                     };
                     Ok(CameraModel::new(Box::new(cahvore::linearize(
                         &c,
@@ -378,6 +663,129 @@ impl CameraModel {
     }
 }
 
+// Builds a model of the same type as `model` with new geometric vectors, keeping its
+// distortion terms.
+fn rebuild_model(
+    model: &CameraModelType,
+    c: Vector,
+    a: Vector,
+    h: Vector,
+    v: Vector,
+    o: Vector,
+) -> CameraModel {
+    match model.model_type() {
+        ModelType::CAHV => CameraModel::new(Box::new(cahv::Cahv { c, a, h, v })),
+        ModelType::CAHVOR => CameraModel::new(Box::new(cahvor::Cahvor {
+            c,
+            a,
+            h,
+            v,
+            o,
+            r: model.r(),
+        })),
+        ModelType::CAHVORE => CameraModel::new(Box::new(cahvore::Cahvore {
+            c,
+            a,
+            h,
+            v,
+            o,
+            r: model.r(),
+            e: model.e(),
+            pupil_type: model.pupil_type(),
+            linearity: model.linearity(),
+        })),
+    }
+}
+
+impl fmt::Debug for CameraModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.model {
+            Some(m) => write!(f, "CameraModel({})", m.serialize()),
+            None => write!(f, "CameraModel(None)"),
+        }
+    }
+}
+
+// Camera models are serialized using the derived formats of the concrete model types,
+// tagged with the model type. The string form produced by `serialize` is also accepted
+// when deserializing.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "model_type")]
+enum TaggedModel {
+    #[serde(rename = "CAHV")]
+    Cahv(cahv::Cahv),
+    #[serde(rename = "CAHVOR")]
+    Cahvor(cahvor::Cahvor),
+    #[serde(rename = "CAHVORE")]
+    Cahvore(cahvore::Cahvore),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SerializedModel {
+    Tagged(TaggedModel),
+    Text(String),
+
+    // Written for an invalid model
+    Null,
+}
+
+impl Serialize for CameraModel {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // An invalid model is written as null rather than failing the enclosing serialization
+        let m = match &self.model {
+            Some(m) => m,
+            None => return serializer.serialize_none(),
+        };
+        let tagged = match m.model_type() {
+            ModelType::CAHV => TaggedModel::Cahv(cahv::Cahv {
+                c: m.c(),
+                a: m.a(),
+                h: m.h(),
+                v: m.v(),
+            }),
+            ModelType::CAHVOR => TaggedModel::Cahvor(cahvor::Cahvor {
+                c: m.c(),
+                a: m.a(),
+                h: m.h(),
+                v: m.v(),
+                o: m.o(),
+                r: m.r(),
+            }),
+            ModelType::CAHVORE => TaggedModel::Cahvore(cahvore::Cahvore {
+                c: m.c(),
+                a: m.a(),
+                h: m.h(),
+                v: m.v(),
+                o: m.o(),
+                r: m.r(),
+                e: m.e(),
+                pupil_type: m.pupil_type(),
+                linearity: m.linearity(),
+            }),
+        };
+        tagged.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CameraModel {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<CameraModel, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match SerializedModel::deserialize(deserializer)? {
+            SerializedModel::Tagged(TaggedModel::Cahv(m)) => Ok(CameraModel::new(Box::new(m))),
+            SerializedModel::Tagged(TaggedModel::Cahvor(m)) => Ok(CameraModel::new(Box::new(m))),
+            SerializedModel::Tagged(TaggedModel::Cahvore(m)) => Ok(CameraModel::new(Box::new(m))),
+            SerializedModel::Text(s) => CameraModel::from_serialized(&s).map_err(de::Error::custom),
+            SerializedModel::Null => Ok(CameraModel::default()),
+        }
+    }
+}
+
 impl Clone for Box<dyn CameraModelTrait + 'static> {
     fn clone(&self) -> Box<dyn CameraModelTrait + 'static> {
         self.box_clone()
@@ -35,6 +35,17 @@ pub const NSYT_ILT: [u32; 256] = [
     3584, 3617, 3650, 3683, 3716, 3750, 3784, 3818, 3852, 3886, 3920, 3955, 3990, 4025, 4060, 4095,
 ];
 
+/// Name of a known lookup table, or "custom" for any other table
+pub fn ilt_name(ilt: &[u32; 256]) -> &'static str {
+    if *ilt == ILT {
+        "ILT"
+    } else if *ilt == NSYT_ILT {
+        "NSYT_ILT"
+    } else {
+        "custom"
+    }
+}
+
 pub fn decompand_buffer(buffer: &mut ImageBuffer, ilt: &[u32; 256]) {
     buffer.apply_lut_mut(ilt);
 }
@@ -0,0 +1,224 @@
+/*
+    Digital elevation models with simple georeferencing, for intersecting camera rays with
+    terrain.
+
+    Elevations are held in an ImageBuffer, with the center of each pixel mapped to horizontal
+    X/Y coordinates by an origin and pixel size, and elevation along +Z. A negative pixel size
+    flips the direction of an axis, such as for north-up rasters where rows run southwards.
+    Masked out pixels are treated as holes in the DEM.
+*/
+
+use crate::{
+    camera::model::{LookVector, EPSILON},
+    imagebuffer::ImageBuffer,
+    vector::Vector,
+};
+
+use anyhow::{anyhow, Result};
+
+// Number of bisection steps used to refine an intersection found between two ray samples
+static REFINE_ITERATIONS: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct Dem {
+    pub elevation: ImageBuffer,
+
+    // X and Y coordinates of the center of pixel (0, 0)
+    pub origin_x: f64,
+    pub origin_y: f64,
+
+    // Size of a pixel along X (per sample) and Y (per line)
+    pub pixel_size_x: f64,
+    pub pixel_size_y: f64,
+}
+
+impl Dem {
+    pub fn new(
+        elevation: ImageBuffer,
+        origin_x: f64,
+        origin_y: f64,
+        pixel_size_x: f64,
+        pixel_size_y: f64,
+    ) -> Result<Dem> {
+        if pixel_size_x == 0.0 || pixel_size_y == 0.0 {
+            return Err(anyhow!("DEM pixel size cannot be zero"));
+        }
+        if elevation.width < 2 || elevation.height < 2 {
+            return Err(anyhow!("DEM must be at least 2x2 pixels"));
+        }
+        Ok(Dem {
+            elevation,
+            origin_x,
+            origin_y,
+            pixel_size_x,
+            pixel_size_y,
+        })
+    }
+
+    /// Fractional pixel coordinates of a horizontal location
+    pub fn to_pixel(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            (x - self.origin_x) / self.pixel_size_x,
+            (y - self.origin_y) / self.pixel_size_y,
+        )
+    }
+
+    /// Horizontal location of fractional pixel coordinates
+    pub fn to_world(&self, sample: f64, line: f64) -> (f64, f64) {
+        (
+            self.origin_x + sample * self.pixel_size_x,
+            self.origin_y + line * self.pixel_size_y,
+        )
+    }
+
+    /// Bilinearly interpolated elevation at a horizontal location. Returns None outside the
+    /// DEM or next to a hole.
+    pub fn elevation_at(&self, x: f64, y: f64) -> Option<f64> {
+        let (sample, line) = self.to_pixel(x, y);
+        let max_sample = (self.elevation.width - 1) as f64;
+        let max_line = (self.elevation.height - 1) as f64;
+        if !(0.0..=max_sample).contains(&sample) || !(0.0..=max_line).contains(&line) {
+            return None;
+        }
+
+        let x0 = (sample.floor() as usize).min(self.elevation.width - 2);
+        let y0 = (line.floor() as usize).min(self.elevation.height - 2);
+        let xd = sample - x0 as f64;
+        let yd = line - y0 as f64;
+
+        let mut corners = [0.0; 4];
+        for (i, (cx, cy)) in [(x0, y0), (x0 + 1, y0), (x0, y0 + 1), (x0 + 1, y0 + 1)]
+            .iter()
+            .enumerate()
+        {
+            if !self.elevation.get_mask_at_point(*cx, *cy) {
+                return None;
+            }
+            corners[i] = self.elevation.get(*cx, *cy) as f64;
+        }
+
+        let top = corners[0] * (1.0 - xd) + corners[1] * xd;
+        let bottom = corners[2] * (1.0 - xd) + corners[3] * xd;
+        Some(top * (1.0 - yd) + bottom * yd)
+    }
+
+    // Height of a point above the DEM surface, if over the DEM
+    fn height_above(&self, point: &Vector) -> Option<f64> {
+        self.elevation_at(point.x, point.y).map(|e| point.z - e)
+    }
+
+    /// Lowest and highest elevations of the valid pixels, or None if every pixel is masked out
+    pub fn elevation_range(&self) -> Option<(f64, f64)> {
+        let mut range: Option<(f64, f64)> = None;
+        for line in 0..self.elevation.height {
+            for sample in 0..self.elevation.width {
+                if !self.elevation.get_mask_at_point(sample, line) {
+                    continue;
+                }
+                let e = self.elevation.get(sample, line) as f64;
+                range = Some(match range {
+                    Some((min, max)) => (min.min(e), max.max(e)),
+                    None => (e, e),
+                });
+            }
+        }
+        range
+    }
+
+    // Distances along a ray between which it lies within the DEM's horizontal extent and
+    // elevation range, limited to between the origin and max_range. The elevation range is
+    // padded by `margin` so that the ray is sampled above and below a flat surface.
+    fn clip_ray(
+        &self,
+        origin: &Vector,
+        direction: &Vector,
+        max_range: f64,
+        margin: f64,
+    ) -> Option<(f64, f64)> {
+        let (min_z, max_z) = self.elevation_range()?;
+        let (x0, y0) = self.to_world(0.0, 0.0);
+        let (x1, y1) = self.to_world(
+            (self.elevation.width - 1) as f64,
+            (self.elevation.height - 1) as f64,
+        );
+
+        let mut near = 0.0_f64;
+        let mut far = max_range;
+        for (o, d, low, high) in [
+            (origin.x, direction.x, x0.min(x1), x0.max(x1)),
+            (origin.y, direction.y, y0.min(y1), y0.max(y1)),
+            (origin.z, direction.z, min_z - margin, max_z + margin),
+        ] {
+            if d.abs() < EPSILON {
+                if o < low || o > high {
+                    return None;
+                }
+            } else {
+                let t_low = (low - o) / d;
+                let t_high = (high - o) / d;
+                near = near.max(t_low.min(t_high));
+                far = far.min(t_low.max(t_high));
+            }
+        }
+
+        if near > far {
+            None
+        } else {
+            Some((near, far))
+        }
+    }
+
+    /// Finds the first point where a ray passes from above the DEM surface to below it, marching
+    /// in steps of half a pixel along the part of the ray within `max_range` that crosses the
+    /// DEM's bounds, and refining the crossing by bisection. Returns None if the ray doesn't hit
+    /// the DEM within range, or if `max_range` isn't a finite positive distance.
+    pub fn intersect(&self, ray: &LookVector, max_range: f64) -> Option<Vector> {
+        if !max_range.is_finite() || max_range <= 0.0 {
+            return None;
+        }
+
+        let direction = ray.look_direction.normalized();
+        let point_at = |t: f64| ray.origin.add(&direction.scale(t));
+        let step = self.pixel_size_x.abs().min(self.pixel_size_y.abs()) * 0.5;
+        let (near, far) = self.clip_ray(&ray.origin, &direction, max_range, step)?;
+        let num_steps = ((far - near) / step).ceil() as usize;
+
+        let mut previous: Option<(f64, f64)> = None;
+        for i in 0..=num_steps {
+            let t = (near + i as f64 * step).min(far);
+            let height = match self.height_above(&point_at(t)) {
+                Some(h) => h,
+                None => {
+                    previous = None;
+                    continue;
+                }
+            };
+
+            if height <= 0.0 {
+                if let Some((t_prev, _)) = previous {
+                    return Some(point_at(self.refine(ray, t_prev, t)));
+                } else if i == 0 && height == 0.0 {
+                    return Some(point_at(t));
+                }
+                previous = None;
+            } else {
+                previous = Some((t, height));
+            }
+        }
+        None
+    }
+
+    // Bisects between a distance along the ray above the surface and one at or below it
+    fn refine(&self, ray: &LookVector, mut above: f64, mut below: f64) -> f64 {
+        let direction = ray.look_direction.normalized();
+        for _ in 0..REFINE_ITERATIONS {
+            let mid = (above + below) / 2.0;
+            match self.height_above(&ray.origin.add(&direction.scale(mid))) {
+                Some(h) if h > 0.0 => above = mid,
+                Some(_) => below = mid,
+                None => break,
+            }
+        }
+        below
+    }
+}
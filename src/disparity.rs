@@ -0,0 +1,193 @@
+/*
+    Dense block-matching stereo correlation of an epipolar-aligned image pair.
+
+    For each pixel of the left image, a window around it is compared against windows along the
+    same row of the right image by normalized cross-correlation, over a range of disparities.
+    A pixel at `x` in the left image matching one at `x - d` in the right image has disparity
+    `d`. The best match may be refined to sub-pixel precision by fitting a parabola through the
+    correlation scores of the neighboring disparities, and matches can be validated by matching
+    the right image back against the left.
+*/
+
+use crate::{imagebuffer::ImageBuffer, DnVec, VecMath};
+
+use anyhow::{anyhow, Result};
+
+#[derive(Debug, Clone)]
+pub struct DisparityOptions {
+    // Width and height of the correlation window, in pixels. Must be odd.
+    pub window_size: usize,
+
+    // Range of disparities searched, inclusive
+    pub min_disparity: i32,
+    pub max_disparity: i32,
+
+    // Refine matches to sub-pixel precision
+    pub subpixel: bool,
+
+    // Maximum difference between the left-to-right and right-to-left disparities of a match.
+    // Disables the consistency check if None.
+    pub max_lr_difference: Option<f32>,
+
+    // Minimum normalized cross-correlation score of a valid match
+    pub min_correlation: f32,
+}
+
+impl Default for DisparityOptions {
+    fn default() -> Self {
+        DisparityOptions {
+            window_size: 7,
+            min_disparity: 0,
+            max_disparity: 64,
+            subpixel: true,
+            max_lr_difference: Some(1.0),
+            min_correlation: 0.5,
+        }
+    }
+}
+
+// Correlation windows of a single image row, None where a window is incomplete, masked, or
+// has no variance to correlate against
+fn row_windows(buffer: &ImageBuffer, y: usize, window_size: usize) -> Vec<Option<DnVec>> {
+    let half = window_size / 2;
+    (0..buffer.width)
+        .map(|x| {
+            if x < half || x + half >= buffer.width || !buffer.get_mask_at_point(x, y) {
+                return None;
+            }
+            let window = buffer.isolate_window(window_size, x, y);
+            if window.variance() > 0.0 {
+                Some(window)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+// Best disparity among the scores of one pixel, refined by a parabola fit through the
+// neighboring scores. Scores are indexed from `min_disparity`.
+fn best_disparity(scores: &[f32], min_disparity: i32, subpixel: bool) -> Option<(f32, f32)> {
+    let (best, score) = scores
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.is_finite())
+        .max_by(|a, b| a.1.total_cmp(b.1))?;
+
+    let mut offset = 0.0;
+    if subpixel && best > 0 && best + 1 < scores.len() {
+        let (s0, s1, s2) = (scores[best - 1], *score, scores[best + 1]);
+        let denom = s0 - 2.0 * s1 + s2;
+        if s0.is_finite() && s2.is_finite() && denom < 0.0 {
+            offset = (0.5 * (s0 - s2) / denom).clamp(-0.5, 0.5);
+        }
+    }
+
+    Some((best as f32 + min_disparity as f32 + offset, *score))
+}
+
+/// Computes the disparity map of an epipolar-aligned image pair, relative to the left image.
+/// Pixels without a valid match are masked out of the returned buffer and set to zero.
+pub fn compute_disparity(
+    left: &ImageBuffer,
+    right: &ImageBuffer,
+    options: &DisparityOptions,
+) -> Result<ImageBuffer> {
+    if left.width != right.width || left.height != right.height {
+        return Err(anyhow!("Stereo images differ in size"));
+    }
+    if options.window_size < 3 || options.window_size % 2 != 1 {
+        return Err(anyhow!(
+            "Correlation window size must be odd and at least 3: {}",
+            options.window_size
+        ));
+    }
+    if options.max_disparity < options.min_disparity {
+        return Err(anyhow!(
+            "Invalid disparity range: {} to {}",
+            options.min_disparity,
+            options.max_disparity
+        ));
+    }
+
+    let width = left.width;
+    let half = options.window_size / 2;
+    let num_disparities = (options.max_disparity - options.min_disparity + 1) as usize;
+
+    let mut disparity = ImageBuffer::new_with_mask_as(width, left.height, false)?;
+
+    for y in half..left.height.saturating_sub(half) {
+        let left_windows = row_windows(left, y, options.window_size);
+        let right_windows = row_windows(right, y, options.window_size);
+
+        // Correlation scores indexed by left pixel and disparity. These serve both the
+        // left-to-right matching and, read diagonally, the right-to-left matching.
+        let mut scores = vec![f32::NAN; width * num_disparities];
+        for (x, lw) in left_windows.iter().enumerate() {
+            let lw = match lw {
+                Some(lw) => lw,
+                None => continue,
+            };
+            for i in 0..num_disparities {
+                let xr = x as i32 - (options.min_disparity + i as i32);
+                if xr < 0 || xr >= width as i32 {
+                    continue;
+                }
+                if let Some(rw) = &right_windows[xr as usize] {
+                    scores[x * num_disparities + i] = lw.xcorr(rw);
+                }
+            }
+        }
+
+        let right_disparities: Vec<Option<f32>> = match options.max_lr_difference {
+            Some(_) => (0..width)
+                .map(|xr| {
+                    let right_scores: Vec<f32> = (0..num_disparities)
+                        .map(|i| {
+                            let x = xr as i32 + options.min_disparity + i as i32;
+                            if x < 0 || x >= width as i32 {
+                                f32::NAN
+                            } else {
+                                scores[x as usize * num_disparities + i]
+                            }
+                        })
+                        .collect();
+                    best_disparity(&right_scores, options.min_disparity, options.subpixel)
+                        .map(|(d, _)| d)
+                })
+                .collect(),
+            None => vec![],
+        };
+
+        for x in 0..width {
+            let (d, score) = match best_disparity(
+                &scores[x * num_disparities..(x + 1) * num_disparities],
+                options.min_disparity,
+                options.subpixel,
+            ) {
+                Some(best) => best,
+                None => continue,
+            };
+            if score < options.min_correlation {
+                continue;
+            }
+
+            if let Some(max_difference) = options.max_lr_difference {
+                let xr = (x as f32 - d).round();
+                if xr < 0.0 || xr >= width as f32 {
+                    continue;
+                }
+                match right_disparities[xr as usize] {
+                    Some(dr) if (dr - d).abs() <= max_difference => {}
+                    _ => continue,
+                }
+            }
+
+            // Masked values can't be written, so the mask is set first
+            disparity.put_mask(x, y, true);
+            disparity.put(x, y, d);
+        }
+    }
+
+    Ok(disparity)
+}
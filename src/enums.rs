@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum CalFileType {
     FlatField,
@@ -7,7 +9,7 @@ pub enum CalFileType {
 
 // Image data value range. Doesn't enforce actual
 // value data types in the structs
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ImageMode {
     U8BIT,
     U12BIT,
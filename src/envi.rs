@@ -0,0 +1,452 @@
+/*
+    Reader and writer for ENVI raw raster cubes.
+
+    An ENVI image is a headerless binary raster accompanied by a text header. The header
+    begins with the line "ENVI" followed by `key = value` fields, where values enclosed in
+    braces may span multiple lines and hold comma separated lists. The header is named either
+    by replacing the extension of the data file with .hdr (cube.img -> cube.hdr) or by
+    appending .hdr to it (cube.img -> cube.img.hdr).
+
+    Reference: ENVI Header Files,
+    https://www.nv5geospatialsoftware.com/docs/ENVIHeaderFiles.html
+*/
+
+use crate::{
+    binfilereader::{BinFileReader, Endian},
+    enums::ImageMode,
+    error,
+    image::Image,
+    raster::{self, Interleave, RasterLayout, SampleType},
+};
+
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// Extensions tried, in order, when locating the data file for a header
+const DATA_EXTENSIONS: [&str; 7] = ["", "img", "dat", "raw", "bsq", "bil", "bip"];
+
+/// Parsed ENVI header. Fields without a typed member are kept, in order, in `fields` with
+/// lower case keys and their raw (brace delimited, where applicable) values.
+#[derive(Debug, Clone)]
+pub struct Header {
+    pub samples: usize,
+    pub lines: usize,
+    pub bands: usize,
+    pub header_offset: usize,
+    pub data_type: SampleType,
+    pub interleave: Interleave,
+    pub byte_order: Endian,
+    pub description: Option<String>,
+    pub band_names: Vec<String>,
+
+    // Center wavelength of each band, in `wavelength_units`
+    pub wavelength: Vec<f64>,
+    pub wavelength_units: Option<String>,
+
+    pub fields: Vec<(String, String)>,
+}
+
+impl Header {
+    /// Creates a band sequential, little endian header describing an image
+    pub fn for_image(image: &Image, data_type: SampleType) -> Header {
+        Header {
+            samples: image.width,
+            lines: image.height,
+            bands: image.num_bands(),
+            header_offset: 0,
+            data_type,
+            interleave: Interleave::BandSequential,
+            byte_order: Endian::LittleEndian,
+            description: None,
+            band_names: vec![],
+            wavelength: vec![],
+            wavelength_units: None,
+            fields: vec![],
+        }
+    }
+
+    pub fn parse_str(text: &str) -> Result<Header> {
+        let mut lines = text.lines();
+        if lines.next().map(|l| l.trim()) != Some("ENVI") {
+            return Err(anyhow!("Header does not begin with ENVI"));
+        }
+
+        let mut fields: Vec<(String, String)> = vec![];
+        while let Some(line) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid header line: {}", line))?;
+
+            let mut value = value.trim().to_string();
+            if value.starts_with('{') {
+                while !value.ends_with('}') {
+                    match lines.next() {
+                        Some(l) => {
+                            value.push('\n');
+                            value.push_str(l.trim());
+                        }
+                        None => return Err(anyhow!("Unterminated value for field {}", key)),
+                    }
+                }
+            }
+            fields.push((key.trim().to_lowercase(), value));
+        }
+
+        let mut take = |key: &str| -> Option<String> {
+            fields
+                .iter()
+                .position(|(k, _)| k == key)
+                .map(|i| fields.remove(i).1)
+        };
+
+        let required = |v: Option<String>, key: &str| -> Result<usize> {
+            v.ok_or_else(|| anyhow!("Missing required field: {}", key))?
+                .parse::<usize>()
+                .map_err(|_| anyhow!("Invalid value for field: {}", key))
+        };
+
+        let samples = required(take("samples"), "samples")?;
+        let lines = required(take("lines"), "lines")?;
+        let bands = required(take("bands"), "bands")?;
+        let data_type = sample_type_from_data_type(required(take("data type"), "data type")?)?;
+        // The header offset is optional and defaults to the start of the data file
+        let header_offset = match take("header offset") {
+            Some(v) => required(Some(v), "header offset")?,
+            None => 0,
+        };
+
+        let interleave = match take("interleave").map(|v| v.to_lowercase()).as_deref() {
+            Some("bsq") | None => Interleave::BandSequential,
+            Some("bil") => Interleave::LineInterleaved,
+            Some("bip") => Interleave::SampleInterleaved,
+            Some(v) => return Err(anyhow!("Unsupported interleave: {}", v)),
+        };
+
+        let byte_order = match take("byte order").as_deref() {
+            Some("0") | None => Endian::LittleEndian,
+            Some("1") => Endian::BigEndian,
+            Some(v) => return Err(anyhow!("Invalid byte order: {}", v)),
+        };
+
+        let description = take("description").map(|v| unbrace(&v).to_string());
+        let band_names = take("band names")
+            .map(|v| split_list(&v))
+            .unwrap_or_default();
+        let wavelength = match take("wavelength") {
+            Some(v) => split_list(&v)
+                .iter()
+                .map(|w| {
+                    w.parse::<f64>()
+                        .map_err(|_| anyhow!("Invalid wavelength: {}", w))
+                })
+                .collect::<Result<Vec<f64>>>()?,
+            None => vec![],
+        };
+        let wavelength_units = take("wavelength units");
+
+        if !wavelength.is_empty() && wavelength.len() != bands {
+            return Err(anyhow!(
+                "Header lists {} wavelengths for {} bands",
+                wavelength.len(),
+                bands
+            ));
+        }
+
+        Ok(Header {
+            samples,
+            lines,
+            bands,
+            header_offset,
+            data_type,
+            interleave,
+            byte_order,
+            description,
+            band_names,
+            wavelength,
+            wavelength_units,
+            fields,
+        })
+    }
+
+    pub fn from_file(file_path: &str) -> Result<Header> {
+        error::check_file_exists(file_path)?;
+        Header::parse_str(&fs::read_to_string(file_path)?)
+    }
+
+    /// Returns the raw value of a field not covered by a typed member
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let key = key.to_lowercase();
+        self.fields
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Adds or replaces a field not covered by a typed member
+    pub fn set(&mut self, key: &str, value: &str) {
+        let key = key.to_lowercase();
+        match self.fields.iter_mut().find(|(k, _)| *k == key) {
+            Some(f) => f.1 = value.to_string(),
+            None => self.fields.push((key, value.to_string())),
+        }
+    }
+
+    /// Center wavelength of a band, if listed in the header
+    pub fn band_wavelength(&self, band: usize) -> Option<f64> {
+        self.wavelength.get(band).copied()
+    }
+
+    pub fn raster_layout(&self) -> RasterLayout {
+        let mut layout = RasterLayout::new(
+            self.samples,
+            self.lines,
+            self.bands,
+            self.data_type,
+            self.byte_order,
+            self.interleave,
+        );
+        layout.offset = self.header_offset;
+        layout
+    }
+
+    /// Formats the header as ENVI header text
+    pub fn format(&self) -> Result<String> {
+        let mut out = String::from("ENVI\n");
+        if let Some(d) = &self.description {
+            out.push_str(&format!("description = {{{}}}\n", d));
+        }
+        out.push_str(&format!("samples = {}\n", self.samples));
+        out.push_str(&format!("lines = {}\n", self.lines));
+        out.push_str(&format!("bands = {}\n", self.bands));
+        out.push_str(&format!("header offset = {}\n", self.header_offset));
+        out.push_str(&format!(
+            "file type = {}\n",
+            self.get("file type").unwrap_or("ENVI Standard")
+        ));
+        out.push_str(&format!(
+            "data type = {}\n",
+            data_type_from_sample_type(self.data_type)?
+        ));
+        out.push_str(&format!(
+            "interleave = {}\n",
+            match self.interleave {
+                Interleave::BandSequential => "bsq",
+                Interleave::LineInterleaved => "bil",
+                Interleave::SampleInterleaved => "bip",
+            }
+        ));
+        out.push_str(&format!(
+            "byte order = {}\n",
+            match self.byte_order {
+                Endian::BigEndian => 1,
+                Endian::LittleEndian => 0,
+                Endian::NativeEndian => i32::from(cfg!(target_endian = "big")),
+            }
+        ));
+        if !self.band_names.is_empty() {
+            out.push_str(&format!(
+                "band names = {{{}}}\n",
+                self.band_names.join(", ")
+            ));
+        }
+        if !self.wavelength.is_empty() {
+            let wavelengths: Vec<String> = self.wavelength.iter().map(|w| w.to_string()).collect();
+            out.push_str(&format!("wavelength = {{{}}}\n", wavelengths.join(", ")));
+        }
+        if let Some(u) = &self.wavelength_units {
+            out.push_str(&format!("wavelength units = {}\n", u));
+        }
+        for (k, v) in self.fields.iter().filter(|(k, _)| k != "file type") {
+            out.push_str(&format!("{} = {}\n", k, v));
+        }
+        Ok(out)
+    }
+}
+
+fn unbrace(value: &str) -> &str {
+    value
+        .trim()
+        .trim_start_matches('{')
+        .trim_end_matches('}')
+        .trim()
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    unbrace(value)
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Sample type of an ENVI data type code. Complex and 64 bit integer types are not supported.
+pub fn sample_type_from_data_type(data_type: usize) -> Result<SampleType> {
+    match data_type {
+        1 => Ok(SampleType::UnsignedInt8),
+        2 => Ok(SampleType::SignedInt16),
+        3 => Ok(SampleType::SignedInt32),
+        4 => Ok(SampleType::Float32),
+        5 => Ok(SampleType::Float64),
+        12 => Ok(SampleType::UnsignedInt16),
+        13 => Ok(SampleType::UnsignedInt32),
+        t => Err(anyhow!("Unsupported ENVI data type: {}", t)),
+    }
+}
+
+pub fn data_type_from_sample_type(sample_type: SampleType) -> Result<usize> {
+    match sample_type {
+        SampleType::UnsignedInt8 => Ok(1),
+        SampleType::SignedInt16 => Ok(2),
+        SampleType::SignedInt32 => Ok(3),
+        SampleType::Float32 => Ok(4),
+        SampleType::Float64 => Ok(5),
+        SampleType::UnsignedInt16 => Ok(12),
+        SampleType::UnsignedInt32 => Ok(13),
+        t => Err(anyhow!("Sample type not representable in ENVI: {:?}", t)),
+    }
+}
+
+fn is_header_path(file_path: &str) -> bool {
+    matches!(
+        Path::new(file_path).extension().and_then(|e| e.to_str()),
+        Some(e) if e.eq_ignore_ascii_case("hdr")
+    )
+}
+
+/// Locates the header accompanying a data file
+pub fn header_path_for(data_path: &str) -> Option<PathBuf> {
+    let path = Path::new(data_path);
+    [
+        path.with_extension("hdr"),
+        PathBuf::from(format!("{}.hdr", data_path)),
+    ]
+    .into_iter()
+    .find(|p| p.exists() && p != path)
+}
+
+/// Locates the data file described by a header
+pub fn data_path_for(header_path: &str) -> Option<PathBuf> {
+    let path = Path::new(header_path);
+    DATA_EXTENSIONS
+        .iter()
+        .map(|e| path.with_extension(e))
+        .find(|p| p.is_file())
+}
+
+fn starts_with_envi(file_path: &Path) -> bool {
+    match fs::File::open(file_path) {
+        Ok(f) => {
+            let mut buf = [0_u8; 4];
+            f.take(4).read(&mut buf).unwrap_or(0) == 4 && &buf == b"ENVI"
+        }
+        Err(_) => false,
+    }
+}
+
+// ENVI also writes headers beside files in other formats (e.g. TIFF), recorded by the
+// file type field. Only headers describing raw data are considered.
+fn describes_raw_data(header_path: &Path) -> bool {
+    starts_with_envi(header_path)
+        && match Header::from_file(header_path.to_str().unwrap_or_default()) {
+            Ok(h) => match h.get("file type") {
+                Some(t) => t.starts_with("ENVI"),
+                None => true,
+            },
+            Err(_) => false,
+        }
+}
+
+/// Checks whether a file is an ENVI header, or a raw data file with an ENVI header beside it
+pub fn is_envi_file(file_path: &str) -> bool {
+    if is_header_path(file_path) {
+        starts_with_envi(Path::new(file_path))
+    } else {
+        matches!(header_path_for(file_path), Some(h) if describes_raw_data(&h))
+    }
+}
+
+fn resolve_paths(file_path: &str) -> Result<(PathBuf, PathBuf)> {
+    error::check_file_exists(file_path)?;
+    if is_header_path(file_path) {
+        let data_path = data_path_for(file_path)
+            .ok_or_else(|| anyhow!("Data file not found for header {}", file_path))?;
+        Ok((PathBuf::from(file_path), data_path))
+    } else {
+        let header_path = header_path_for(file_path)
+            .ok_or_else(|| anyhow!("Header not found for data file {}", file_path))?;
+        Ok((header_path, PathBuf::from(file_path)))
+    }
+}
+
+/// Reads an ENVI cube, given either the header or the data file, along with its header
+pub fn read_image_with_header(file_path: &str) -> Result<(Image, Header)> {
+    let (header_path, data_path) = resolve_paths(file_path)?;
+    let header = Header::from_file(header_path.to_str().unwrap_or_default())?;
+    let reader = BinFileReader::open(&data_path)?;
+    let mut image = raster::read_image(
+        &reader,
+        &header.raster_layout(),
+        header.data_type.image_mode(),
+    )?;
+    let metadata = image.get_metadata_mut();
+    metadata.wavelengths = header.wavelength.clone();
+    metadata.wavelength_units = header.wavelength_units.clone();
+    Ok((image, header))
+}
+
+/// Reads an ENVI cube given either the header or the data file
+pub fn read_image(file_path: &str) -> Result<Image> {
+    Ok(read_image_with_header(file_path)?.0)
+}
+
+/// Default ENVI data type used to store an image of the specified mode
+pub fn default_sample_type(mode: ImageMode) -> SampleType {
+    match mode {
+        ImageMode::U8BIT => SampleType::UnsignedInt8,
+        ImageMode::U12BIT => SampleType::UnsignedInt16,
+        ImageMode::U16BIT => SampleType::Float32,
+    }
+}
+
+/// Writes an image as an ENVI data file and header, using the data type, interleave, byte
+/// order and band descriptions of the supplied header. The dimensions are taken from the
+/// image. The header is written beside the data file with its extension replaced by .hdr.
+pub fn write_image_with_header(image: &Image, to_file: &str, header: &Header) -> Result<()> {
+    error::check_parent_writable(to_file)?;
+    if is_header_path(to_file) {
+        return Err(anyhow!("Expected a data file path, got header {}", to_file));
+    }
+
+    let mut header = header.clone();
+    header.samples = image.width;
+    header.lines = image.height;
+    header.bands = image.num_bands();
+    header.header_offset = 0;
+    if !header.wavelength.is_empty() && header.wavelength.len() != header.bands {
+        return Err(anyhow!(
+            "Header lists {} wavelengths for {} bands",
+            header.wavelength.len(),
+            header.bands
+        ));
+    }
+
+    let data = raster::encode_image(image, &header.raster_layout())?;
+    fs::write(to_file, data)?;
+    fs::write(Path::new(to_file).with_extension("hdr"), header.format()?)?;
+    Ok(())
+}
+
+/// Writes an image as a band sequential ENVI cube using the default data type for its mode.
+/// Band wavelengths are taken from the image metadata.
+pub fn write_image(image: &Image, to_file: &str) -> Result<()> {
+    let mut header = Header::for_image(image, default_sample_type(image.get_mode()));
+    header.wavelength = image.get_metadata().wavelengths.clone();
+    header.wavelength_units = image.get_metadata().wavelength_units.clone();
+    write_image_with_header(image, to_file, &header)
+}
@@ -1,7 +1,100 @@
+use std::fmt;
+use std::io;
 use std::result;
 
-#[deprecated]
-pub type Result<T> = result::Result<T, &'static str>;
+/// Errors returned by the image load and save operations
+#[derive(Debug)]
+pub enum SciImgError {
+    /// The input file does not exist
+    FileNotFound(String),
+
+    /// The parent directory of an output file does not exist or cannot be written to
+    UnwritablePath(String),
+
+    /// The image cannot be saved with its number of bands in the requested format
+    UnsupportedBandCount(usize),
+
+    /// The file format, pixel type or image layout is not supported
+    UnsupportedFormat(String),
+
+    /// The file or its label could not be parsed or decoded
+    InvalidFormat(String),
+
+    /// The requested band does not exist in the image
+    BandOutOfBounds(usize),
+
+    /// Reading or writing the file failed
+    Io(io::Error),
+
+    /// The image could not be encoded or decoded by the `image` crate
+    Image(image::ImageError),
+}
+
+pub type Result<T> = result::Result<T, SciImgError>;
+
+impl fmt::Display for SciImgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SciImgError::FileNotFound(p) => write!(f, "File not found: {}", p),
+            SciImgError::UnwritablePath(p) => {
+                write!(f, "Parent path does not exist or is unwritable: {}", p)
+            }
+            SciImgError::UnsupportedBandCount(n) => {
+                write!(f, "Unsupported number of bands: {}", n)
+            }
+            SciImgError::UnsupportedFormat(s) => write!(f, "Unsupported format: {}", s),
+            SciImgError::InvalidFormat(s) => write!(f, "Invalid format: {}", s),
+            SciImgError::BandOutOfBounds(b) => write!(f, "Band index out of bounds: {}", b),
+            SciImgError::Io(e) => write!(f, "I/O error: {}", e),
+            SciImgError::Image(e) => write!(f, "Image error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SciImgError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SciImgError::Io(e) => Some(e),
+            SciImgError::Image(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SciImgError {
+    fn from(e: io::Error) -> Self {
+        SciImgError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for SciImgError {
+    fn from(e: image::ImageError) -> Self {
+        match e {
+            image::ImageError::IoError(e) => SciImgError::Io(e),
+            image::ImageError::Unsupported(e) => SciImgError::UnsupportedFormat(e.to_string()),
+            e => SciImgError::Image(e),
+        }
+    }
+}
+
+// Errors from the format readers and writers are recovered as their original type when
+// possible, otherwise they are reported as invalid input.
+impl From<anyhow::Error> for SciImgError {
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<SciImgError>() {
+            Ok(e) => return e,
+            Err(e) => e,
+        };
+        let e = match e.downcast::<io::Error>() {
+            Ok(e) => return SciImgError::Io(e),
+            Err(e) => e,
+        };
+        match e.downcast::<image::ImageError>() {
+            Ok(e) => SciImgError::from(e),
+            Err(e) => SciImgError::InvalidFormat(format!("{:#}", e)),
+        }
+    }
+}
 
 #[macro_export]
 macro_rules! ok {
@@ -9,3 +102,21 @@ macro_rules! ok {
         Ok("ok")
     };
 }
+
+pub(crate) fn check_file_exists(file_path: &str) -> Result<()> {
+    if crate::path::file_exists(file_path) {
+        Ok(())
+    } else {
+        Err(SciImgError::FileNotFound(file_path.to_string()))
+    }
+}
+
+pub(crate) fn check_parent_writable(to_file: &str) -> Result<()> {
+    if crate::path::parent_exists_and_writable(to_file) {
+        Ok(())
+    } else {
+        Err(SciImgError::UnwritablePath(crate::path::get_parent(
+            to_file,
+        )))
+    }
+}
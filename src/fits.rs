@@ -0,0 +1,525 @@
+/*
+    Reader and writer for FITS images.
+
+    A FITS file is a sequence of header/data units (HDUs). Each header is made up of 80
+    character ASCII cards terminated by an END card and padded to a multiple of 2880 bytes,
+    followed by big-endian data likewise padded to 2880 bytes. Images are read from the
+    primary HDU and from IMAGE extensions, with NAXIS1, NAXIS2 and NAXIS3 giving the width,
+    height and number of bands. Physical values are computed as BZERO + BSCALE * raw.
+
+    Reference: Definition of the Flexible Image Transport System (FITS), version 4.0
+    https://fits.gsfc.nasa.gov/fits_standard.html
+*/
+
+use crate::{
+    binfilereader::{BinFileReader, Endian},
+    error,
+    image::Image,
+    pds3::Value,
+    raster::{self, Interleave, RasterLayout, SampleType},
+};
+
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::io::Read;
+
+pub const BLOCK_SIZE: usize = 2880;
+pub const CARD_SIZE: usize = 80;
+
+/// Length rounded up to a whole number of blocks
+pub fn padded_len(len: usize) -> usize {
+    len + (BLOCK_SIZE - len % BLOCK_SIZE) % BLOCK_SIZE
+}
+
+/// A single header card. Commentary cards (COMMENT, HISTORY, blank) carry no value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Card {
+    pub keyword: String,
+    pub value: Option<Value>,
+    pub comment: Option<String>,
+}
+
+impl Card {
+    pub fn new(keyword: &str, value: Value, comment: Option<&str>) -> Card {
+        Card {
+            keyword: keyword.to_uppercase(),
+            value: Some(value),
+            comment: comment.map(String::from),
+        }
+    }
+
+    pub fn commentary(keyword: &str, text: &str) -> Card {
+        Card {
+            keyword: keyword.to_uppercase(),
+            value: None,
+            comment: Some(text.to_string()),
+        }
+    }
+
+    fn parse(card: &str) -> Result<Card> {
+        let keyword = card.get(..8).unwrap_or(card).trim().to_string();
+
+        if card.get(8..10) != Some("= ") {
+            let text = card.get(8..).unwrap_or_default().trim_end();
+            return Ok(Card {
+                keyword,
+                value: None,
+                comment: (!text.is_empty()).then(|| text.to_string()),
+            });
+        }
+
+        let trimmed = card.get(10..).unwrap_or_default().trim_start();
+        let (value, rest) = if let Some(s) = trimmed.strip_prefix('\'') {
+            // Quoted string with '' as an escaped quote. Trailing spaces are not significant.
+            let mut text = String::new();
+            let mut chars = s.char_indices().peekable();
+            let mut end = None;
+            while let Some((i, c)) = chars.next() {
+                if c == '\'' {
+                    if matches!(chars.peek(), Some((_, '\''))) {
+                        chars.next();
+                        text.push('\'');
+                    } else {
+                        end = Some(i + 1);
+                        break;
+                    }
+                } else {
+                    text.push(c);
+                }
+            }
+            let end = end.ok_or_else(|| anyhow!("Unterminated string in card: {}", keyword))?;
+            (Some(Value::Text(text.trim_end().to_string())), &s[end..])
+        } else {
+            let (v, rest) = match trimmed.find('/') {
+                Some(i) => (&trimmed[..i], &trimmed[i..]),
+                None => (trimmed, ""),
+            };
+            let v = v.trim();
+            let value = if v.is_empty() {
+                None
+            } else if let Ok(i) = v.parse::<i64>() {
+                Some(Value::Integer(i))
+            } else if let Ok(f) = v.replace(['D', 'd'], "E").parse::<f64>() {
+                Some(Value::Real(f))
+            } else {
+                Some(Value::Symbol(v.to_string()))
+            };
+            (value, rest)
+        };
+
+        let comment = rest
+            .trim()
+            .strip_prefix('/')
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty());
+
+        Ok(Card {
+            keyword,
+            value,
+            comment,
+        })
+    }
+
+    // Formats the card as 80 ASCII characters. Keywords, values and comments containing
+    // characters outside printable ASCII, and values which don't fit on the card, are rejected
+    // rather than written as a corrupt card. Comments are truncated to fit.
+    fn format(&self) -> Result<String> {
+        if self.keyword.len() > 8 || !is_printable_ascii(&self.keyword) {
+            return Err(anyhow!("Invalid FITS keyword: {}", self.keyword));
+        }
+        let mut s = format!("{:<8}", self.keyword);
+        match &self.value {
+            Some(v) => {
+                s.push_str("= ");
+                let value = match v {
+                    Value::Text(t) => format!("'{:<8}'", t.replace('\'', "''")),
+                    _ => format!("{:>20}", format_value(v)),
+                };
+                if !is_printable_ascii(&value) {
+                    return Err(anyhow!(
+                        "Value of card {} contains non-ASCII characters",
+                        self.keyword
+                    ));
+                }
+                if s.len() + value.len() > CARD_SIZE {
+                    return Err(anyhow!(
+                        "Value of card {} is too long: {} characters",
+                        self.keyword,
+                        value.len()
+                    ));
+                }
+                s.push_str(&value);
+                if let Some(c) = &self.comment {
+                    self.check_comment(c)?;
+                    s.push_str(" / ");
+                    s.push_str(c);
+                }
+            }
+            None => {
+                if let Some(c) = &self.comment {
+                    self.check_comment(c)?;
+                    s.push_str(c);
+                }
+            }
+        }
+        Ok(format!("{:<80}", truncate_chars(&s, CARD_SIZE)))
+    }
+
+    fn check_comment(&self, comment: &str) -> Result<()> {
+        if !is_printable_ascii(comment) {
+            return Err(anyhow!(
+                "Comment of card {} contains non-ASCII characters",
+                self.keyword
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn is_printable_ascii(s: &str) -> bool {
+    s.chars().all(|c| c.is_ascii() && !c.is_ascii_control())
+}
+
+// Truncates to at most `max_len` bytes without splitting a character
+fn truncate_chars(s: &str, max_len: usize) -> &str {
+    let mut end = max_len.min(s.len());
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::Integer(i) => format!("{}", i),
+        Value::Real(f) => {
+            let s = format!("{:?}", f);
+            s.to_uppercase()
+        }
+        Value::Text(s) | Value::Symbol(s) => s.to_string(),
+        Value::WithUnits(v, _) => format_value(v),
+        Value::Sequence(v) | Value::Set(v) => format!(
+            "({})",
+            v.iter()
+                .map(format_value)
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// A parsed FITS header
+#[derive(Debug, Clone, Default)]
+pub struct Header {
+    pub cards: Vec<Card>,
+}
+
+impl Header {
+    pub fn get(&self, keyword: &str) -> Option<&Value> {
+        self.cards
+            .iter()
+            .find(|c| c.keyword.eq_ignore_ascii_case(keyword) && c.value.is_some())
+            .and_then(|c| c.value.as_ref())
+    }
+
+    pub fn get_i64(&self, keyword: &str) -> Option<i64> {
+        self.get(keyword).and_then(|v| v.as_i64())
+    }
+
+    pub fn get_f64(&self, keyword: &str) -> Option<f64> {
+        self.get(keyword).and_then(|v| v.as_f64())
+    }
+
+    pub fn get_str(&self, keyword: &str) -> Option<&str> {
+        self.get(keyword).and_then(|v| v.as_str())
+    }
+
+    /// Adds or replaces a keyword, preserving the comment of an existing card
+    pub fn set(&mut self, keyword: &str, value: Value) {
+        match self
+            .cards
+            .iter_mut()
+            .find(|c| c.keyword.eq_ignore_ascii_case(keyword) && c.value.is_some())
+        {
+            Some(c) => c.value = Some(value),
+            None => self.cards.push(Card::new(keyword, value, None)),
+        }
+    }
+
+    fn naxis(&self, n: usize) -> usize {
+        self.get_i64(&format!("NAXIS{}", n)).unwrap_or(0).max(0) as usize
+    }
+
+    fn bitpix(&self) -> Result<i64> {
+        self.get_i64("BITPIX")
+            .ok_or_else(|| anyhow!("Header is missing BITPIX"))
+    }
+
+    /// Number of bytes in the data unit, excluding padding
+    pub fn data_bytes(&self) -> Result<usize> {
+        let bitpix = self.bitpix()?;
+        let naxis = self.get_i64("NAXIS").unwrap_or(0).max(0) as usize;
+        if naxis == 0 {
+            return Ok(0);
+        }
+        let pcount = self.get_i64("PCOUNT").unwrap_or(0).max(0) as usize;
+        let gcount = self.get_i64("GCOUNT").unwrap_or(1).max(0) as usize;
+        let elements: usize = (1..=naxis).map(|n| self.naxis(n)).product();
+        Ok(bitpix.unsigned_abs() as usize / 8 * gcount * (pcount + elements))
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Option<(Header, usize)>> {
+        let mut header = Header::default();
+        let mut pos = 0;
+        while pos + CARD_SIZE <= bytes.len() {
+            let card = String::from_utf8_lossy(&bytes[pos..pos + CARD_SIZE]).to_string();
+            pos += CARD_SIZE;
+            if card.trim_end() == "END" {
+                return Ok(Some((header, padded_len(pos))));
+            }
+            header.cards.push(Card::parse(&card)?);
+        }
+        Ok(None)
+    }
+
+    fn format(&self) -> Result<Vec<u8>> {
+        let mut out: Vec<u8> = vec![];
+        for c in self.cards.iter() {
+            out.extend(c.format()?.bytes());
+        }
+        out.extend(format!("{:<80}", "END").bytes());
+        out.resize(padded_len(out.len()), b' ');
+        Ok(out)
+    }
+}
+
+/// A header/data unit along with the location of its data within the file
+#[derive(Debug, Clone)]
+pub struct Hdu {
+    pub header: Header,
+    pub data_offset: usize,
+}
+
+impl Hdu {
+    /// Whether the HDU is a primary or IMAGE extension with at least two axes
+    pub fn is_image(&self) -> bool {
+        let is_image_ext = match self.header.get_str("XTENSION") {
+            Some(x) => x.trim() == "IMAGE",
+            None => self.header.get("SIMPLE").is_some(),
+        };
+        is_image_ext && self.header.get_i64("NAXIS").unwrap_or(0) >= 2
+    }
+
+    pub fn raster_layout(&self) -> Result<RasterLayout> {
+        let naxis = self.header.get_i64("NAXIS").unwrap_or(0);
+        if !(2..=3).contains(&naxis) {
+            return Err(anyhow!("Unsupported number of image axes: {}", naxis));
+        }
+
+        let sample_type = sample_type_from_bitpix(self.header.bitpix()?)?;
+        let bands = if naxis == 3 { self.header.naxis(3) } else { 1 };
+        let mut layout = RasterLayout::new(
+            self.header.naxis(1),
+            self.header.naxis(2),
+            bands,
+            sample_type,
+            Endian::BigEndian,
+            Interleave::BandSequential,
+        );
+        layout.offset = self.data_offset;
+        layout.scaling_factor = self.header.get_f64("BSCALE").unwrap_or(1.0) as f32;
+        layout.value_offset = self.header.get_f64("BZERO").unwrap_or(0.0) as f32;
+        Ok(layout)
+    }
+}
+
+pub fn sample_type_from_bitpix(bitpix: i64) -> Result<SampleType> {
+    match bitpix {
+        8 => Ok(SampleType::UnsignedInt8),
+        16 => Ok(SampleType::SignedInt16),
+        32 => Ok(SampleType::SignedInt32),
+        -32 => Ok(SampleType::Float32),
+        -64 => Ok(SampleType::Float64),
+        _ => Err(anyhow!("Unsupported BITPIX value: {}", bitpix)),
+    }
+}
+
+fn read_hdus_from_reader(reader: &BinFileReader) -> Result<Vec<Hdu>> {
+    let mut hdus: Vec<Hdu> = vec![];
+    let mut pos = 0;
+    while pos < reader.len() {
+        // Headers are read a block at a time since their length is unknown until END
+        let mut header_bytes: Vec<u8> = vec![];
+        let parsed = loop {
+            if pos + header_bytes.len() + BLOCK_SIZE > reader.len() {
+                break None;
+            }
+            header_bytes.extend(reader.read_bytes(pos + header_bytes.len(), BLOCK_SIZE));
+            if let Some(p) = Header::parse(&header_bytes)? {
+                break Some(p);
+            }
+        };
+
+        let (header, header_len) = match parsed {
+            Some(p) => p,
+            None if hdus.is_empty() => return Err(anyhow!("FITS header is not terminated")),
+            None => break,
+        };
+
+        let data_bytes = header.data_bytes()?;
+        let data_offset = pos + header_len;
+        hdus.push(Hdu {
+            header,
+            data_offset,
+        });
+        pos = data_offset + padded_len(data_bytes);
+    }
+    Ok(hdus)
+}
+
+/// Reads the headers of all HDUs in a FITS file
+pub fn read_hdus(file_path: &str) -> Result<Vec<Hdu>> {
+    error::check_file_exists(file_path)?;
+    read_hdus_from_reader(&BinFileReader::open(file_path)?)
+}
+
+/// Reads the image in the HDU at the specified index
+pub fn read_hdu_image(file_path: &str, index: usize) -> Result<Image> {
+    error::check_file_exists(file_path)?;
+    let reader = BinFileReader::open(file_path)?;
+    let hdus = read_hdus_from_reader(&reader)?;
+    let hdu = hdus
+        .get(index)
+        .ok_or_else(|| anyhow!("HDU index out of bounds: {}", index))?;
+    if !hdu.is_image() {
+        return Err(anyhow!("HDU {} does not contain an image", index));
+    }
+    let layout = hdu.raster_layout()?;
+    raster::read_image(&reader, &layout, layout.sample_type.image_mode())
+}
+
+/// Reads the first image in the file, either from the primary HDU or the first IMAGE extension
+pub fn read_image(file_path: &str) -> Result<Image> {
+    let hdus = read_hdus(file_path)?;
+    let index = hdus
+        .iter()
+        .position(|h| h.is_image())
+        .ok_or_else(|| anyhow!("File does not contain an image"))?;
+    read_hdu_image(file_path, index)
+}
+
+/// Checks whether a file begins with a FITS primary header
+pub fn is_fits_file(file_path: &str) -> bool {
+    match fs::File::open(file_path) {
+        Ok(f) => {
+            let mut buf = [0_u8; 9];
+            f.take(9).read(&mut buf).unwrap_or(0) == 9 && &buf == b"SIMPLE  ="
+        }
+        Err(_) => false,
+    }
+}
+
+// Records the processing history of an image as HISTORY cards, one step per card and
+// continued across cards where a step exceeds the card width.
+fn history_cards(image: &Image) -> Vec<Card> {
+    let width = CARD_SIZE - 8;
+    image
+        .get_metadata()
+        .history
+        .iter()
+        .flat_map(|step| {
+            let text: Vec<char> = step
+                .to_string()
+                .chars()
+                .map(|c| {
+                    if c.is_ascii() && !c.is_ascii_control() {
+                        c
+                    } else {
+                        '?'
+                    }
+                })
+                .collect();
+            text.chunks(width)
+                .map(|c| Card::commentary("HISTORY", &c.iter().collect::<String>()))
+                .collect::<Vec<Card>>()
+        })
+        .collect()
+}
+
+/// Writes the bands of an image as a 32 bit floating point data cube in the primary HDU. The
+/// supplied cards are appended after the structural keywords, followed by HISTORY cards
+/// recording the processing history of the image.
+pub fn write_image_with_cards(image: &Image, to_file: &str, cards: &[Card]) -> Result<()> {
+    error::check_parent_writable(to_file)?;
+
+    let bands = image.num_bands();
+    let mut header = Header::default();
+    header.cards.push(Card::new(
+        "SIMPLE",
+        Value::Symbol(String::from("T")),
+        Some("conforms to FITS standard"),
+    ));
+    header.cards.push(Card::new(
+        "BITPIX",
+        Value::Integer(-32),
+        Some("IEEE single precision"),
+    ));
+    header.cards.push(Card::new(
+        "NAXIS",
+        Value::Integer(if bands > 1 { 3 } else { 2 }),
+        None,
+    ));
+    header.cards.push(Card::new(
+        "NAXIS1",
+        Value::Integer(image.width as i64),
+        Some("width"),
+    ));
+    header.cards.push(Card::new(
+        "NAXIS2",
+        Value::Integer(image.height as i64),
+        Some("height"),
+    ));
+    if bands > 1 {
+        header.cards.push(Card::new(
+            "NAXIS3",
+            Value::Integer(bands as i64),
+            Some("bands"),
+        ));
+    }
+    header
+        .cards
+        .push(Card::new("EXTEND", Value::Symbol(String::from("T")), None));
+    header.cards.push(Card::new(
+        "DATE",
+        Value::Text(chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S").to_string()),
+        Some("file creation date (UTC)"),
+    ));
+    header.cards.push(Card::new(
+        "ORIGIN",
+        Value::Text(String::from("sciimg")),
+        None,
+    ));
+    header.cards.extend(cards.iter().cloned());
+    header.cards.extend(history_cards(image));
+
+    let layout = RasterLayout::new(
+        image.width,
+        image.height,
+        bands,
+        SampleType::Float32,
+        Endian::BigEndian,
+        Interleave::BandSequential,
+    );
+
+    let mut out = header.format()?;
+    out.extend(raster::encode_image(image, &layout)?);
+    out.resize(padded_len(out.len()), 0);
+
+    fs::write(to_file, out)?;
+    Ok(())
+}
+
+/// Writes the bands of an image as a 32 bit floating point data cube
+pub fn write_image(image: &Image, to_file: &str) -> Result<()> {
+    write_image_with_cards(image, to_file, &[])
+}
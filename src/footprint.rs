@@ -0,0 +1,175 @@
+/*
+    Ground footprints and fields of view of camera models.
+
+    The footprint of an image is found by tracing rays through pixels along the image border
+    and intersecting them with a surface, either a plane for local terrain or a sphere or
+    ellipsoid for orbital imaging. Rays which miss the surface, such as those above the horizon,
+    are left out of the footprint polygon.
+*/
+
+use crate::{
+    camera::model::{CameraModel, ImageCoordinate, LookVector},
+    vector::Vector,
+};
+
+use anyhow::{anyhow, Result};
+
+/// A surface onto which image footprints are projected
+#[derive(Debug, Clone, PartialEq)]
+pub enum Surface {
+    Plane { point: Vector, normal: Vector },
+    Sphere { center: Vector, radius: f64 },
+
+    // Triaxial ellipsoid aligned with the coordinate axes
+    Ellipsoid { center: Vector, radii: Vector },
+}
+
+impl Surface {
+    pub fn intersect(&self, ray: &LookVector) -> Option<Vector> {
+        match self {
+            Surface::Plane { point, normal } => ray.intersect_plane(point, normal),
+            Surface::Sphere { center, radius } => ray.intersect_sphere(center, *radius),
+            Surface::Ellipsoid { center, radii } => ray.intersect_ellipsoid(center, radii),
+        }
+    }
+}
+
+/// The field of view of a camera as the rays through its four corner pixels
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Frustum {
+    pub origin: Vector,
+
+    // Unit look directions through the top left, top right, bottom right and bottom left
+    // corner pixels
+    pub corners: [Vector; 4],
+}
+
+impl Frustum {
+    /// Corner points of the frustum at a distance from its origin
+    pub fn corners_at(&self, range: f64) -> [Vector; 4] {
+        self.corners.map(|c| self.origin.add(&c.scale(range)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Footprint {
+    // Intersections of the border rays with the surface, clockwise from the top left corner
+    // of the image
+    pub polygon: Vec<Vector>,
+
+    // Whether every border ray intersected the surface
+    pub complete: bool,
+
+    pub frustum: Frustum,
+
+    // Horizontal and vertical field of view, in radians
+    pub fov_horizontal: f64,
+    pub fov_vertical: f64,
+}
+
+/// Approximate horizontal and vertical fields of view, in radians, of an image of the given
+/// size, from the angular size of a pixel at the center of the camera model
+pub fn field_of_view(camera_model: &CameraModel, width: usize, height: usize) -> (f64, f64) {
+    (
+        camera_model.pixel_angle_horiz() * width as f64,
+        camera_model.pixel_angle_vert() * height as f64,
+    )
+}
+
+// Pixel coordinates along the image border, clockwise from the top left corner, with
+// `samples_per_edge` points along each edge including its corners
+fn border_coordinates(width: usize, height: usize, samples_per_edge: usize) -> Vec<(f64, f64)> {
+    let right = (width - 1) as f64;
+    let bottom = (height - 1) as f64;
+    let steps = (samples_per_edge - 1) as f64;
+
+    let mut coordinates = vec![];
+    for i in 0..samples_per_edge - 1 {
+        let f = i as f64 / steps;
+        coordinates.push((f * right, 0.0));
+    }
+    for i in 0..samples_per_edge - 1 {
+        let f = i as f64 / steps;
+        coordinates.push((right, f * bottom));
+    }
+    for i in 0..samples_per_edge - 1 {
+        let f = i as f64 / steps;
+        coordinates.push((right - f * right, bottom));
+    }
+    for i in 0..samples_per_edge - 1 {
+        let f = i as f64 / steps;
+        coordinates.push((0.0, bottom - f * bottom));
+    }
+    coordinates
+}
+
+fn look_vector(camera_model: &CameraModel, sample: f64, line: f64) -> Result<LookVector> {
+    camera_model.ls_to_look_vector(&ImageCoordinate { line, sample })
+}
+
+/// Computes the frustum of an image of the given size
+pub fn frustum(camera_model: &CameraModel, width: usize, height: usize) -> Result<Frustum> {
+    if !camera_model.is_valid() {
+        return Err(anyhow!("Cannot compute frustum of an invalid camera model"));
+    }
+    if width == 0 || height == 0 {
+        return Err(anyhow!("Invalid image size: {}x{}", width, height));
+    }
+
+    let right = (width - 1) as f64;
+    let bottom = (height - 1) as f64;
+    let mut corners = [Vector::default(); 4];
+    for (i, (sample, line)) in [(0.0, 0.0), (right, 0.0), (right, bottom), (0.0, bottom)]
+        .iter()
+        .enumerate()
+    {
+        corners[i] = look_vector(camera_model, *sample, *line)?
+            .look_direction
+            .normalized();
+    }
+
+    Ok(Frustum {
+        origin: camera_model.c(),
+        corners,
+    })
+}
+
+/// Computes the footprint of an image of the given size on a surface, tracing
+/// `samples_per_edge` rays along each edge of the image, corners included.
+pub fn compute_footprint(
+    camera_model: &CameraModel,
+    width: usize,
+    height: usize,
+    surface: &Surface,
+    samples_per_edge: usize,
+) -> Result<Footprint> {
+    if width < 2 || height < 2 {
+        return Err(anyhow!("Invalid image size: {}x{}", width, height));
+    }
+    if samples_per_edge < 2 {
+        return Err(anyhow!(
+            "At least two samples per edge are required, got {}",
+            samples_per_edge
+        ));
+    }
+
+    let frustum = frustum(camera_model, width, height)?;
+
+    let mut polygon = vec![];
+    let mut complete = true;
+    for (sample, line) in border_coordinates(width, height, samples_per_edge) {
+        match surface.intersect(&look_vector(camera_model, sample, line)?) {
+            Some(p) => polygon.push(p),
+            None => complete = false,
+        }
+    }
+
+    let (fov_horizontal, fov_vertical) = field_of_view(camera_model, width, height);
+    Ok(Footprint {
+        polygon,
+        complete,
+        frustum,
+        fov_horizontal,
+        fov_vertical,
+    })
+}
@@ -103,12 +103,9 @@ pub fn guassian_blur_nband(buffers: &mut [ImageBuffer], sigma: f32) -> Result<Ve
 Hopefully a little faster?, naive optimisations.
 There's no test for this one so, we'll see how we go..
 */
-pub fn guassian_blur_nband(
-    buffers: &mut [ImageBuffer],
-    sigma: f32,
-) -> error::Result<Vec<ImageBuffer>> {
+pub fn guassian_blur_nband(buffers: &mut [ImageBuffer], sigma: f32) -> Result<Vec<ImageBuffer>> {
     if buffers.is_empty() {
-        return Err("No buffers provided");
+        return Err(anyhow!("No buffers provided"));
     }
 
     let sig_squared = sigma.powi(2);
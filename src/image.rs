@@ -1,9 +1,21 @@
 use crate::{
-    debayer, decompanding, enums, hotpixel, imagebuffer::ImageBuffer, imagebuffer::Offset,
-    imagerot, inpaint, lowpass, max, min, noise, path, resize, Mask, MaskVec,
+    camera::model::CameraModel,
+    debayer, decompanding, enums, envi,
+    error::{self, SciImgError},
+    fits, hotpixel,
+    imagebuffer::ImageBuffer,
+    imagebuffer::Offset,
+    imagerot, inpaint, lowpass, max,
+    metadata::{Metadata, ProcessingStep},
+    min, noise, path, pds3, pds4, pngio,
+    raster::SampleType,
+    reproject::{self, Interpolation},
+    resize,
+    sidecar::{self, Sidecar},
+    tiffio, vicar, Mask, MaskVec,
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use image::{open, ColorType::*, DynamicImage, Luma, Rgb, Rgba};
 
 // A simple image raster buffer.
@@ -16,6 +28,7 @@ pub struct Image {
     pub height: usize,
     mode: enums::ImageMode,
     empty: bool,
+    metadata: Metadata,
 }
 
 macro_rules! check_band_in_bounds {
@@ -26,15 +39,70 @@ macro_rules! check_band_in_bounds {
     };
 }
 
+macro_rules! check_band_in_bounds_or_err {
+    ($band:expr, $self:ident) => {
+        if $band >= $self.bands.len() {
+            return Err(SciImgError::BandOutOfBounds($band));
+        }
+    };
+}
+
+macro_rules! check_rgb_band_count {
+    ($self:ident) => {
+        if $self.bands.len() < 3 {
+            return Err(SciImgError::UnsupportedBandCount($self.bands.len()));
+        }
+    };
+}
+
 fn image_uses_alpha(buffer: &DynamicImage) -> bool {
     matches!(buffer.color(), La8 | Rgba8 | La16 | Rgba16 | Rgba32F)
 }
 
-fn image_bitmode(buffer: &DynamicImage) -> enums::ImageMode {
+fn is_png_path(file_path: &str) -> bool {
+    matches!(
+        path::get_extension(file_path).map(|e| e.to_lowercase()),
+        Some(e) if e == "png"
+    )
+}
+
+fn calibration_step(
+    operation: &str,
+    flat_field: &Image,
+    dark_field: &Image,
+    dark_flat_field: &Image,
+) -> ProcessingStep {
+    ProcessingStep::new(operation)
+        .with_parameter("flat_field", !flat_field.is_empty())
+        .with_parameter("dark_field", !dark_field.is_empty())
+        .with_parameter("dark_flat_field", !dark_flat_field.is_empty())
+}
+
+fn is_tiff_path(file_path: &str) -> bool {
+    matches!(
+        path::get_extension(file_path).map(|e| e.to_lowercase()),
+        Some(e) if e == "tif" || e == "tiff"
+    )
+}
+
+fn is_exr_path(file_path: &str) -> bool {
+    matches!(
+        path::get_extension(file_path).map(|e| e.to_lowercase()),
+        Some(e) if e == "exr"
+    )
+}
+
+fn image_bitmode(buffer: &DynamicImage) -> error::Result<enums::ImageMode> {
     match buffer.color() {
-        L8 | La8 | Rgb8 | Rgba8 => enums::ImageMode::U8BIT,
-        L16 | La16 | Rgb16 | Rgba16 => enums::ImageMode::U16BIT,
-        _ => panic!("Unsupported 32-bit image format"),
+        L8 | La8 | Rgb8 | Rgba8 => Ok(enums::ImageMode::U8BIT),
+        L16 | La16 | Rgb16 | Rgba16 => Ok(enums::ImageMode::U16BIT),
+        // Floating point data has no integer range of its own and is carried as 16 bit, with
+        // `Metadata::float_samples` set so that saving keeps the values as floats
+        Rgb32F | Rgba32F => Ok(enums::ImageMode::U16BIT),
+        c => Err(SciImgError::UnsupportedFormat(format!(
+            "Unsupported color type: {:?}",
+            c
+        ))),
     }
 }
 
@@ -46,9 +114,9 @@ macro_rules! load_image {
         let height = dims.1 as usize;
 
         let mut rgbimage = if $has_alpha {
-            Image::new_with_bands_masked(width, height, 3, $image_mode, true).unwrap()
+            Image::new_with_bands_masked(width, height, 3, $image_mode, true)?
         } else {
-            Image::new_with_bands(width, height, 3, $image_mode).unwrap()
+            Image::new_with_bands(width, height, 3, $image_mode)?
         };
 
         for y in 0..height {
@@ -84,6 +152,7 @@ impl Image {
             height,
             mode,
             empty: false,
+            metadata: Metadata::default(),
         })
     }
 
@@ -105,6 +174,7 @@ impl Image {
             height,
             mode,
             empty: false,
+            metadata: Metadata::default(),
         })
     }
 
@@ -128,6 +198,7 @@ impl Image {
             height,
             mode,
             empty: false,
+            metadata: Metadata::default(),
         })
     }
 
@@ -140,33 +211,104 @@ impl Image {
             height: 0,
             mode: enums::ImageMode::U8BIT,
             empty: true,
+            metadata: Metadata::default(),
         })
     }
 
-    pub fn open_str(file_path: &str) -> Result<Image> {
+    pub fn open_str(file_path: &str) -> error::Result<Image> {
         Image::open(file_path)
     }
 
-    pub fn open(file_path: &str) -> Result<Image> {
-        if !path::file_exists(file_path) {
-            panic!("File not found: {}", file_path);
+    pub fn open(file_path: &str) -> error::Result<Image> {
+        error::check_file_exists(file_path)?;
+
+        if fits::is_fits_file(file_path) {
+            return Ok(fits::read_image(file_path)?);
+        }
+
+        if pds3::is_pds3_file(file_path) {
+            return Ok(pds3::read_image(file_path)?);
+        }
+
+        if pds4::is_pds4_file(file_path) {
+            return Ok(pds4::read_image(file_path)?);
+        }
+
+        if vicar::is_vicar_file(file_path) {
+            return Ok(vicar::read_image(file_path)?);
+        }
+
+        if envi::is_envi_file(file_path) {
+            return Ok(envi::read_image(file_path)?);
+        }
+
+        if tiffio::is_multipage_tiff(file_path) {
+            return Ok(tiffio::read_bands(file_path)?);
+        }
+
+        if tiffio::is_float_tiff(file_path) {
+            return Ok(tiffio::read_image(file_path)?);
         }
 
-        let buffer = open(file_path).unwrap();
+        let buffer = open(file_path)?;
 
         let has_alpha = image_uses_alpha(&buffer);
-        let image_mode = image_bitmode(&buffer);
+        let image_mode = image_bitmode(&buffer)?;
 
-        match image_mode {
-            enums::ImageMode::U8BIT => {
-                let image_data = buffer.into_rgba8();
-                load_image!(image_data, has_alpha, image_mode)
+        let float_samples = matches!(buffer.color(), Rgb32F | Rgba32F);
+        let loaded: error::Result<Image> = if float_samples {
+            let image_data = buffer.into_rgba32f();
+            load_image!(image_data, has_alpha, image_mode)
+        } else {
+            match image_mode {
+                enums::ImageMode::U8BIT => {
+                    let image_data = buffer.into_rgba8();
+                    load_image!(image_data, has_alpha, image_mode)
+                }
+                _ => {
+                    let image_data = buffer.into_rgba16();
+                    load_image!(image_data, has_alpha, image_mode)
+                }
             }
-            _ => {
-                let image_data = buffer.into_rgba16();
-                load_image!(image_data, has_alpha, image_mode)
+        };
+
+        let mut image = loaded?;
+        image.metadata.float_samples = float_samples;
+
+        // Restore the processing history of PNGs written by `save`. A history which can't be
+        // read or parsed, such as one written by other software, is ignored.
+        if is_png_path(file_path) {
+            if let Ok(Some(json)) = pngio::read_text_value(file_path, pngio::HISTORY_KEYWORD) {
+                if let Ok(history) = Metadata::history_from_json(&json) {
+                    image.metadata.history = history;
+                }
             }
         }
+
+        Ok(image)
+    }
+
+    /// Opens an image along with the sidecar written by `save_with_sidecar`, restoring its
+    /// image mode and metadata. Bands replicated when saving a single band image to a
+    /// three channel format are dropped.
+    pub fn open_with_sidecar(file_path: &str) -> error::Result<Image> {
+        let sidecar = Sidecar::from_file(&sidecar::sidecar_path(file_path))?;
+        let mut image = Image::open(file_path)?;
+
+        if image.width != sidecar.width || image.height != sidecar.height {
+            return Err(SciImgError::InvalidFormat(format!(
+                "Sidecar dimensions {}x{} do not match image dimensions {}x{}",
+                sidecar.width, sidecar.height, image.width, image.height
+            )));
+        }
+        if sidecar.bands > image.bands.len() {
+            return Err(SciImgError::UnsupportedBandCount(sidecar.bands));
+        }
+
+        image.bands.truncate(sidecar.bands);
+        image.mode = sidecar.mode;
+        image.metadata = sidecar.metadata;
+        Ok(image)
     }
 
     pub fn new_from_buffers_rgb(
@@ -183,6 +325,7 @@ impl Image {
             height: red.height,
             mode,
             empty: false,
+            metadata: Metadata::default(),
         })
     }
 
@@ -198,6 +341,22 @@ impl Image {
         self.mode = mode;
     }
 
+    pub fn get_metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn get_metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
+
+    pub fn set_metadata(&mut self, metadata: &Metadata) {
+        self.metadata = metadata.clone();
+    }
+
+    fn add_history(&mut self, step: ProcessingStep) {
+        self.metadata.add_history(step);
+    }
+
     pub fn num_bands(&self) -> usize {
         self.bands.len()
     }
@@ -348,7 +507,20 @@ impl Image {
         dark_flat_field: &Image,
     ) {
         check_band_in_bounds!(band, self);
+        self.add_history(
+            calibration_step("calibrate_band", flat_field, dark_field, dark_flat_field)
+                .with_parameter("band", band),
+        );
+        self.apply_calibration_on_band(band, flat_field, dark_field, dark_flat_field);
+    }
 
+    fn apply_calibration_on_band(
+        &mut self,
+        band: usize,
+        flat_field: &Image,
+        dark_field: &Image,
+        dark_flat_field: &Image,
+    ) {
         if !flat_field.is_empty() && !dark_field.is_empty() && !dark_flat_field.is_empty() {
             let flat_minus_darkflat = flat_field.bands[band]
                 .subtract(&dark_flat_field.bands[band])
@@ -387,8 +559,14 @@ impl Image {
     }
 
     pub fn calibrate(&mut self, flat_field: &Image, dark_field: &Image, dark_flat_field: &Image) {
+        self.add_history(calibration_step(
+            "calibrate",
+            flat_field,
+            dark_field,
+            dark_flat_field,
+        ));
         for i in 0..self.bands.len() {
-            self.calibrate_band(i, flat_field, dark_field, dark_flat_field);
+            self.apply_calibration_on_band(i, flat_field, dark_field, dark_flat_field);
         }
     }
 
@@ -402,6 +580,7 @@ impl Image {
     }
 
     pub fn apply_flat(&mut self, flat: &Image) {
+        self.add_history(ProcessingStep::new("flat_field"));
         for i in 0..self.bands.len() {
             let flat_buffer = if flat.num_bands() > i {
                 flat.get_band(i)
@@ -437,6 +616,9 @@ impl Image {
     }
 
     pub fn compand(&mut self, ilt: &[u32; 256]) {
+        self.add_history(
+            ProcessingStep::new("compand").with_parameter("ilt", decompanding::ilt_name(ilt)),
+        );
         for i in 0..self.bands.len() {
             decompanding::compand_buffer(&mut self.bands[i], ilt);
         }
@@ -444,6 +626,9 @@ impl Image {
     }
 
     pub fn decompand(&mut self, ilt: &[u32; 256]) {
+        self.add_history(
+            ProcessingStep::new("decompand").with_parameter("ilt", decompanding::ilt_name(ilt)),
+        );
         for i in 0..self.bands.len() {
             decompanding::decompand_buffer(&mut self.bands[i], ilt);
         }
@@ -458,6 +643,9 @@ impl Image {
         let use_band = 0;
         check_band_in_bounds!(use_band, self);
 
+        self.add_history(
+            ProcessingStep::new("debayer").with_parameter("method", format!("{:?}", method)),
+        );
         let debayered = debayer::debayer(&self.bands[use_band], method).unwrap();
         self.bands = vec![
             debayered.bands[0].clone(),
@@ -467,9 +655,13 @@ impl Image {
     }
 
     pub fn reduce_color_noise(&mut self, amount: i32) {
+        self.add_history(
+            ProcessingStep::new("reduce_color_noise").with_parameter("amount", amount),
+        );
         let orig_mode = self.mode;
         let (_, maxval) = self.get_min_max_all_channel();
-        self.normalize_to_8bit_with_max(maxval);
+        self.apply_normalization(255.0, maxval, true);
+        self.mode = enums::ImageMode::U8BIT;
 
         let result = noise::color_noise_reduction(&mut self.clone(), amount);
         for i in 0..self.bands.len() {
@@ -477,9 +669,11 @@ impl Image {
         }
 
         if orig_mode == enums::ImageMode::U12BIT {
-            self.normalize_to_12bit_with_max(maxval, 255.0);
+            self.apply_normalization(maxval, 255.0, false);
+            self.mode = enums::ImageMode::U12BIT;
         } else if orig_mode == enums::ImageMode::U16BIT {
-            self.normalize_to_16bit_with_max(255.0);
+            self.apply_normalization(65535.0, 255.0, true);
+            self.mode = enums::ImageMode::U16BIT;
         }
     }
 
@@ -490,14 +684,34 @@ impl Image {
 
     pub fn hot_pixel_correction_on_band(&mut self, window_size: i32, threshold: f32, band: usize) {
         check_band_in_bounds!(band, self);
+        self.add_history(
+            ProcessingStep::new("hot_pixel_correction_on_band")
+                .with_parameter("window_size", window_size)
+                .with_parameter("threshold", threshold)
+                .with_parameter("band", band),
+        );
+        self.apply_hot_pixel_correction_on_band(window_size, threshold, band);
+    }
+
+    fn apply_hot_pixel_correction_on_band(
+        &mut self,
+        window_size: i32,
+        threshold: f32,
+        band: usize,
+    ) {
         self.bands[band] = hotpixel::hot_pixel_detection(&self.bands[band], window_size, threshold)
             .unwrap()
             .buffer;
     }
 
     pub fn hot_pixel_correction(&mut self, window_size: i32, threshold: f32) {
+        self.add_history(
+            ProcessingStep::new("hot_pixel_correction")
+                .with_parameter("window_size", window_size)
+                .with_parameter("threshold", threshold),
+        );
         for i in 0..self.bands.len() {
-            self.hot_pixel_correction_on_band(window_size, threshold, i);
+            self.apply_hot_pixel_correction_on_band(window_size, threshold, i);
         }
     }
 
@@ -505,6 +719,9 @@ impl Image {
         for i in 0..self.bands.len() {
             self.bands[i] = self.bands[i].get_subframe(x, y, width, height).unwrap();
         }
+        if let Some(m) = self.metadata.camera_model.as_ref().filter(|m| m.is_valid()) {
+            self.metadata.camera_model = m.subframe(x as f64, y as f64).ok();
+        }
         self.width = width;
         self.height = height;
     }
@@ -522,6 +739,12 @@ impl Image {
         for i in 0..self.bands.len() {
             self.rotate_band(rotation_radians, i);
         }
+
+        if let Some(m) = self.metadata.camera_model.as_ref().filter(|m| m.is_valid()) {
+            self.metadata.camera_model = m
+                .rotated(rotation_radians as f64, self.width, self.height)
+                .ok();
+        }
     }
 
     fn is_pixel_grayscale(&self, x: usize, y: usize) -> bool {
@@ -592,32 +815,50 @@ impl Image {
     }
 
     pub fn normalize_between(&mut self, min: f32, max: f32) {
+        self.add_history(
+            ProcessingStep::new("normalize_between")
+                .with_parameter("min", min)
+                .with_parameter("max", max),
+        );
         for i in 0..self.bands.len() {
             self.bands[i] = self.bands[i].normalize(min, max).unwrap();
         }
     }
 
-    pub fn normalize_to_8bit_with_max(&mut self, max: f32) {
+    // Scales every band from [0, from_max] to [0, to_max], optionally clipping to the
+    // output range. Does not change the image mode.
+    fn apply_normalization(&mut self, to_max: f32, from_max: f32, clip: bool) {
         for i in 0..self.bands.len() {
             self.bands[i] = self.bands[i]
-                .normalize_force_minmax(0.0, 255.0, 0.0, max)
+                .normalize_force_minmax(0.0, to_max, 0.0, from_max)
                 .unwrap();
-            self.bands[i].clip_mut(0.0, 255.0);
+            if clip {
+                self.bands[i].clip_mut(0.0, to_max);
+            }
         }
+    }
+
+    pub fn normalize_to_8bit_with_max(&mut self, max: f32) {
+        self.add_history(
+            ProcessingStep::new("normalize_to_8bit_with_max").with_parameter("max", max),
+        );
+        self.apply_normalization(255.0, max, true);
         self.mode = enums::ImageMode::U8BIT;
     }
 
     pub fn normalize_to_8bit_decorrelated(&mut self) {
-        self.normalize_decorrelated(255.0);
+        self.add_history(ProcessingStep::new("normalize_to_8bit_decorrelated"));
+        self.apply_decorrelated_normalization(255.0);
         self.mode = enums::ImageMode::U8BIT;
     }
 
     pub fn normalize_to_12bit_with_max(&mut self, max12bit: f32, max: f32) {
-        for i in 0..self.bands.len() {
-            self.bands[i] = self.bands[i]
-                .normalize_force_minmax(0.0, max12bit, 0.0, max)
-                .unwrap();
-        }
+        self.add_history(
+            ProcessingStep::new("normalize_to_12bit_with_max")
+                .with_parameter("max12bit", max12bit)
+                .with_parameter("max", max),
+        );
+        self.apply_normalization(max12bit, max, false);
         self.mode = enums::ImageMode::U12BIT;
     }
 
@@ -642,6 +883,25 @@ impl Image {
         to_max: f32,
         from_min: f32,
         from_max: f32,
+    ) {
+        self.add_history(
+            ProcessingStep::new("normalize_band_to_with_min_max")
+                .with_parameter("band", band)
+                .with_parameter("to_min", to_min)
+                .with_parameter("to_max", to_max)
+                .with_parameter("from_min", from_min)
+                .with_parameter("from_max", from_max),
+        );
+        self.apply_normalization_on_band(band, to_min, to_max, from_min, from_max);
+    }
+
+    fn apply_normalization_on_band(
+        &mut self,
+        band: usize,
+        to_min: f32,
+        to_max: f32,
+        from_min: f32,
+        from_max: f32,
     ) {
         self.set_band(
             &self
@@ -653,61 +913,86 @@ impl Image {
     }
 
     pub fn normalize_decorrelated(&mut self, max: f32) {
+        self.add_history(ProcessingStep::new("normalize_decorrelated").with_parameter("max", max));
+        self.apply_decorrelated_normalization(max);
+    }
+
+    fn apply_decorrelated_normalization(&mut self, max: f32) {
         let prepped = self.color_range_determine_prep();
         for b in 0..3 {
             let mm = prepped.get_band(b).get_min_max();
-            self.normalize_band_to_with_min_max(b, 0.0, max, mm.min, mm.max);
+            self.apply_normalization_on_band(b, 0.0, max, mm.min, mm.max);
         }
 
         self.set_mode(enums::ImageMode::U16BIT);
     }
 
     pub fn normalize_to_16bit_decorrelated(&mut self) {
-        self.normalize_decorrelated(65535.0);
+        self.add_history(ProcessingStep::new("normalize_to_16bit_decorrelated"));
+        self.apply_decorrelated_normalization(65535.0);
         self.mode = enums::ImageMode::U16BIT;
     }
 
     pub fn normalize_to_16bit_with_max(&mut self, max: f32) {
-        for i in 0..self.bands.len() {
-            self.bands[i] = self.bands[i]
-                .normalize_force_minmax(0.0, 65535.0, 0.0, max)
-                .unwrap();
-            self.bands[i].clip_mut(0.0, 65535.0);
-        }
+        self.add_history(
+            ProcessingStep::new("normalize_to_16bit_with_max").with_parameter("max", max),
+        );
+        self.apply_normalization(65535.0, max, true);
         self.mode = enums::ImageMode::U16BIT;
     }
 
     pub fn normalize_band_to_12bit(&mut self, band: usize, max12bit: f32) {
         check_band_in_bounds!(band, self);
         let mnmx = self.bands[band].get_min_max();
-        self.normalize_to_12bit_with_max(max12bit, mnmx.max);
+        self.add_history(
+            ProcessingStep::new("normalize_band_to_12bit")
+                .with_parameter("band", band)
+                .with_parameter("max12bit", max12bit)
+                .with_parameter("max", mnmx.max),
+        );
+        self.apply_normalization(max12bit, mnmx.max, false);
+        self.mode = enums::ImageMode::U12BIT;
     }
 
     pub fn normalize_to_12bit(&mut self, max12bit: f32) {
         let (_, maxval) = self.get_min_max_all_channel();
-        self.normalize_to_12bit_with_max(max12bit, maxval);
+        self.add_history(
+            ProcessingStep::new("normalize_to_12bit")
+                .with_parameter("max12bit", max12bit)
+                .with_parameter("max", maxval),
+        );
+        self.apply_normalization(max12bit, maxval, false);
+        self.mode = enums::ImageMode::U12BIT;
     }
 
     pub fn normalize_to_8bit(&mut self) {
         let (_, maxval) = self.get_min_max_all_channel();
-        self.normalize_to_8bit_with_max(maxval);
+        self.add_history(ProcessingStep::new("normalize_to_8bit").with_parameter("max", maxval));
+        self.apply_normalization(255.0, maxval, true);
+        self.mode = enums::ImageMode::U8BIT;
     }
 
     pub fn normalize_to_16bit(&mut self) {
         let (_, maxval) = self.get_min_max_all_channel();
-        self.normalize_to_16bit_with_max(maxval);
+        self.add_history(ProcessingStep::new("normalize_to_16bit").with_parameter("max", maxval));
+        self.apply_normalization(65535.0, maxval, true);
+        self.mode = enums::ImageMode::U16BIT;
     }
 
     pub fn normalize_16bit_to_8bit(&mut self) {
-        self.normalize_to_8bit_with_max(65535.0);
+        self.add_history(ProcessingStep::new("normalize_16bit_to_8bit"));
+        self.apply_normalization(255.0, 65535.0, true);
+        self.mode = enums::ImageMode::U8BIT;
     }
 
     pub fn normalize_8bit_to_16bit(&mut self) {
-        self.normalize_to_16bit_with_max(255.0);
+        self.add_history(ProcessingStep::new("normalize_8bit_to_16bit"));
+        self.apply_normalization(65535.0, 255.0, true);
+        self.mode = enums::ImageMode::U16BIT;
     }
 
-    fn save_16bit_mono(&self, to_file: &str, band: usize) {
-        check_band_in_bounds!(band, self);
+    fn save_16bit_mono(&self, to_file: &str, band: usize) -> error::Result<()> {
+        check_band_in_bounds_or_err!(band, self);
         let mut out_img =
             DynamicImage::new_luma16(self.width as u32, self.height as u32).into_luma16();
 
@@ -721,18 +1006,11 @@ impl Image {
             }
         }
 
-        if path::parent_exists_and_writable(to_file) {
-            out_img.save(to_file).unwrap();
-        } else {
-            panic!(
-                "Parent path does not exist or is unwritable: {}",
-                path::get_parent(to_file)
-            );
-        }
+        self.write_output(DynamicImage::from(out_img), to_file)
     }
 
-    fn save_16bit_rgba(&self, to_file: &str) {
-        check_band_in_bounds!(2, self);
+    fn save_16bit_rgba(&self, to_file: &str) -> error::Result<()> {
+        check_rgb_band_count!(self);
         let mut out_img =
             DynamicImage::new_rgba16(self.width as u32, self.height as u32).into_rgba16();
 
@@ -755,18 +1033,11 @@ impl Image {
             }
         }
 
-        if path::parent_exists_and_writable(to_file) {
-            out_img.save(to_file).unwrap();
-        } else {
-            panic!(
-                "Parent path does not exist or is unwritable: {}",
-                path::get_parent(to_file)
-            );
-        }
+        self.write_output(DynamicImage::from(out_img), to_file)
     }
 
-    fn save_16bit_rgb(&self, to_file: &str) {
-        check_band_in_bounds!(2, self);
+    fn save_16bit_rgb(&self, to_file: &str) -> error::Result<()> {
+        check_rgb_band_count!(self);
         let mut out_img =
             DynamicImage::new_rgb16(self.width as u32, self.height as u32).into_rgb16();
 
@@ -784,30 +1055,23 @@ impl Image {
             }
         }
 
-        if path::parent_exists_and_writable(to_file) {
-            out_img.save(to_file).unwrap();
-        } else {
-            panic!(
-                "Parent path does not exist or is unwritable: {}",
-                path::get_parent(to_file)
-            );
-        }
+        self.write_output(DynamicImage::from(out_img), to_file)
     }
 
-    fn save_16bit(&self, to_file: &str) {
+    fn save_16bit(&self, to_file: &str) -> error::Result<()> {
         if self.bands.len() == 1 {
-            self.save_16bit_mono(to_file, 0);
+            self.save_16bit_mono(to_file, 0)
         } else if self.bands.len() >= 3 && self.uses_alpha {
-            self.save_16bit_rgba(to_file);
+            self.save_16bit_rgba(to_file)
         } else if self.bands.len() >= 3 && !self.uses_alpha {
-            self.save_16bit_rgb(to_file);
+            self.save_16bit_rgb(to_file)
         } else {
-            panic!("Unsupported number of bands. Cannot save as implemented");
+            Err(SciImgError::UnsupportedBandCount(self.bands.len()))
         }
     }
 
-    fn save_8bit_mono(&self, to_file: &str, band: usize) {
-        check_band_in_bounds!(band, self);
+    fn save_8bit_mono(&self, to_file: &str, band: usize) -> error::Result<()> {
+        check_band_in_bounds_or_err!(band, self);
 
         let mut out_img =
             DynamicImage::new_luma8(self.width as u32, self.height as u32).into_luma8();
@@ -822,18 +1086,11 @@ impl Image {
             }
         }
 
-        if path::parent_exists_and_writable(to_file) {
-            out_img.save(to_file).unwrap();
-        } else {
-            panic!(
-                "Parent path does not exist or is unwritable: {}",
-                path::get_parent(to_file)
-            );
-        }
+        self.write_output(DynamicImage::from(out_img), to_file)
     }
 
-    fn save_8bit_rgba(&self, to_file: &str) {
-        check_band_in_bounds!(2, self);
+    fn save_8bit_rgba(&self, to_file: &str) -> error::Result<()> {
+        check_rgb_band_count!(self);
 
         let mut out_img =
             DynamicImage::new_rgba8(self.width as u32, self.height as u32).into_rgba8();
@@ -857,18 +1114,11 @@ impl Image {
             }
         }
 
-        if path::parent_exists_and_writable(to_file) {
-            out_img.save(to_file).unwrap();
-        } else {
-            panic!(
-                "Parent path does not exist or is unwritable: {}",
-                path::get_parent(to_file)
-            );
-        }
+        self.write_output(DynamicImage::from(out_img), to_file)
     }
 
-    fn save_8bit_rgb(&self, to_file: &str) {
-        check_band_in_bounds!(2, self);
+    fn save_8bit_rgb(&self, to_file: &str) -> error::Result<()> {
+        check_rgb_band_count!(self);
 
         let mut out_img = DynamicImage::new_rgb8(self.width as u32, self.height as u32).into_rgb8();
 
@@ -886,54 +1136,153 @@ impl Image {
             }
         }
 
-        if path::parent_exists_and_writable(to_file) {
-            out_img.save(to_file).unwrap();
-        } else {
-            panic!(
-                "Parent path does not exist or is unwritable: {}",
-                path::get_parent(to_file)
-            );
-        }
+        self.write_output(DynamicImage::from(out_img), to_file)
     }
 
-    fn save_8bit(&self, to_file: &str) {
+    fn save_8bit(&self, to_file: &str) -> error::Result<()> {
         if self.bands.len() == 1 {
-            self.save_8bit_mono(to_file, 0);
+            self.save_8bit_mono(to_file, 0)
         } else if self.bands.len() >= 3 && self.uses_alpha {
-            self.save_8bit_rgba(to_file);
+            self.save_8bit_rgba(to_file)
         } else if self.bands.len() >= 3 && !self.uses_alpha {
-            self.save_8bit_rgb(to_file);
+            self.save_8bit_rgb(to_file)
+        } else {
+            Err(SciImgError::UnsupportedBandCount(self.bands.len()))
+        }
+    }
+
+    // PNG output embeds the processing history, when there is any, as a text chunk
+    fn write_output(&self, out_img: DynamicImage, to_file: &str) -> error::Result<()> {
+        error::check_parent_writable(to_file)?;
+        if !self.metadata.history.is_empty() && is_png_path(to_file) {
+            pngio::write_image(
+                &out_img,
+                to_file,
+                &[(pngio::HISTORY_KEYWORD, self.metadata.history_to_json()?)],
+            )?;
         } else {
-            panic!("Unsupported number of bands. Cannot save as implemented");
+            out_img.save(to_file)?;
         }
+        Ok(())
     }
 
-    pub fn save_mono(&self, to_file: &str, band: usize) {
+    pub fn save_mono(&self, to_file: &str, band: usize) -> error::Result<()> {
         match self.mode {
             enums::ImageMode::U8BIT => self.save_8bit_mono(to_file, band),
             _ => self.save_16bit_mono(to_file, band),
-        };
+        }
     }
 
-    pub fn save_rgba(&self, to_file: &str) {
+    pub fn save_rgba(&self, to_file: &str) -> error::Result<()> {
         match self.mode {
             enums::ImageMode::U8BIT => self.save_8bit_rgba(to_file),
             _ => self.save_16bit_rgba(to_file),
-        };
+        }
     }
 
-    pub fn save_rgb(&self, to_file: &str) {
+    pub fn save_rgb(&self, to_file: &str) -> error::Result<()> {
         match self.mode {
             enums::ImageMode::U8BIT => self.save_8bit_rgb(to_file),
             _ => self.save_16bit_rgb(to_file),
-        };
+        }
+    }
+
+    /// Saves the image along with a JSON sidecar holding its dimensions, image mode and
+    /// metadata. The sidecar is named by appending .json to the image file name.
+    pub fn save_with_sidecar(&self, to_file: &str) -> error::Result<()> {
+        self.save(to_file)?;
+        Ok(Sidecar::from_image(self).save(&sidecar::sidecar_path(to_file))?)
     }
 
-    pub fn save(&self, to_file: &str) {
+    // Images that can't be represented as grayscale or RGB(A) are written with every band
+    // when saving to TIFF. Other formats only take the first three bands.
+    pub fn save(&self, to_file: &str) -> error::Result<()> {
+        if self.bands.len() != 1 && self.bands.len() != 3 && is_tiff_path(to_file) {
+            return self.save_bands(to_file);
+        }
+
+        // Images loaded from floating point samples stay floating point where the format allows
+        if self.metadata.float_samples && (is_tiff_path(to_file) || is_exr_path(to_file)) {
+            return self.save_float(to_file);
+        }
+
         match self.mode {
             enums::ImageMode::U8BIT => self.save_8bit(to_file),
             _ => self.save_16bit(to_file),
+        }
+    }
+
+    // Saves every band of the image as a page of a multi-page TIFF, as 32 bit float samples for
+    // images loaded from floating point data, 8 bit samples for 8 bit images and 16 bit samples
+    // otherwise. Read back with `Image::open` or `tiffio::read_bands`.
+    pub fn save_bands(&self, to_file: &str) -> error::Result<()> {
+        if !is_tiff_path(to_file) {
+            return Err(SciImgError::UnsupportedFormat(format!(
+                "Multi-band output requires a TIFF file: {}",
+                to_file
+            )));
+        }
+
+        let sample_type = match self.mode {
+            _ if self.metadata.float_samples => SampleType::Float32,
+            enums::ImageMode::U8BIT => SampleType::UnsignedInt8,
+            _ => SampleType::UnsignedInt16,
         };
+        Ok(tiffio::write_bands(self, to_file, sample_type)?)
+    }
+
+    // Writes an RGB(A) OpenEXR file with 32 bit float samples. Single band images are
+    // replicated across the three channels.
+    fn save_exr(&self, to_file: &str) -> error::Result<()> {
+        let band = |b: usize| if self.bands.len() >= 3 { b } else { 0 };
+        let out_img = if self.uses_alpha {
+            DynamicImage::ImageRgba32F(image::ImageBuffer::from_fn(
+                self.width as u32,
+                self.height as u32,
+                |x, y| {
+                    let (x, y) = (x as usize, y as usize);
+                    Rgba([
+                        self.bands[band(0)].get(x, y),
+                        self.bands[band(1)].get(x, y),
+                        self.bands[band(2)].get(x, y),
+                        if self.get_alpha_at(x, y) { 1.0 } else { 0.0 },
+                    ])
+                },
+            ))
+        } else {
+            DynamicImage::ImageRgb32F(image::ImageBuffer::from_fn(
+                self.width as u32,
+                self.height as u32,
+                |x, y| {
+                    let (x, y) = (x as usize, y as usize);
+                    Rgb([
+                        self.bands[band(0)].get(x, y),
+                        self.bands[band(1)].get(x, y),
+                        self.bands[band(2)].get(x, y),
+                    ])
+                },
+            ))
+        };
+        out_img.save(to_file)?;
+        Ok(())
+    }
+
+    // Saves the image without rounding or clipping as a 32 bit float TIFF (.tif, .tiff)
+    // or OpenEXR (.exr) file, determined by the file extension.
+    pub fn save_float(&self, to_file: &str) -> error::Result<()> {
+        error::check_parent_writable(to_file)?;
+
+        match path::get_extension(to_file).map(|e| e.to_lowercase()) {
+            Some(_) if is_tiff_path(to_file) => Ok(tiffio::write_image(self, to_file)?),
+            Some(_) if is_exr_path(to_file) => match self.bands.len() {
+                1 | 3 => self.save_exr(to_file),
+                n => Err(SciImgError::UnsupportedBandCount(n)),
+            },
+            _ => Err(SciImgError::UnsupportedFormat(format!(
+                "No floating point output for file: {}",
+                to_file
+            ))),
+        }
     }
 
     pub fn resize_to(&mut self, to_width: usize, to_height: usize) {
@@ -942,7 +1291,43 @@ impl Image {
             .iter()
             .map(|b| resize::resize_to(b, to_width, to_height).expect("Failed to resize image"))
             .collect();
+        if let Some(m) = self.metadata.camera_model.as_ref().filter(|m| m.is_valid()) {
+            self.metadata.camera_model =
+                m.resized(self.width, self.height, to_width, to_height).ok();
+        }
         self.width = to_width;
         self.height = to_height;
     }
+
+    /// Removes lens distortion by resampling the image from its CAHVOR or CAHVORE camera model
+    /// into the linear CAHV model produced by `CameraModel::linearize`. Returns the undistorted
+    /// image and its CAHV model. Pixels which fall outside the original field of view are
+    /// masked out in the alpha band.
+    pub fn linearize_with_model(
+        &self,
+        camera_model: &CameraModel,
+        out_width: usize,
+        out_height: usize,
+    ) -> Result<(Image, CameraModel)> {
+        let linear_model = camera_model
+            .linearize(self.width, self.height, out_width, out_height)
+            .map_err(|e| anyhow!("Failed to linearize camera model: {}", e))?;
+
+        let mut linearized = reproject::resample(
+            self,
+            camera_model,
+            &linear_model,
+            out_width,
+            out_height,
+            Interpolation::Bicubic,
+            None,
+        )?;
+        linearized.add_history(
+            ProcessingStep::new("linearize")
+                .with_parameter("width", out_width)
+                .with_parameter("height", out_height),
+        );
+
+        Ok((linearized, linear_model))
+    }
 }
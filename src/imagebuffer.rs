@@ -1,4 +1,7 @@
-use crate::{enums, max, min, path, Dn, DnVec, Mask, MaskVec, MaskedDnVec, MinMax, VecMath};
+use crate::{
+    enums, envi, error, fits, max, min, pds3, pds4, tiffio, vicar, Dn, DnVec, Mask, MaskVec,
+    MaskedDnVec, MinMax, VecMath,
+};
 
 extern crate image;
 use anyhow::Result;
@@ -290,12 +293,34 @@ impl ImageBuffer {
         ImageBuffer::from_vec_as_mode(&v, width, height, enums::ImageMode::U16BIT)
     }
 
-    pub fn from_file(file_path: &str) -> Result<ImageBuffer> {
-        if !path::file_exists(file_path) {
-            panic!("File not found: {}", file_path);
+    pub fn from_file(file_path: &str) -> error::Result<ImageBuffer> {
+        error::check_file_exists(file_path)?;
+
+        if fits::is_fits_file(file_path) {
+            return Ok(fits::read_image(file_path)?.get_band(0).clone());
+        }
+
+        if pds3::is_pds3_file(file_path) {
+            return Ok(pds3::read_image(file_path)?.get_band(0).clone());
+        }
+
+        if pds4::is_pds4_file(file_path) {
+            return Ok(pds4::read_image(file_path)?.get_band(0).clone());
         }
 
-        let image_data = open(file_path).unwrap().into_luma16();
+        if vicar::is_vicar_file(file_path) {
+            return Ok(vicar::read_image(file_path)?.get_band(0).clone());
+        }
+
+        if envi::is_envi_file(file_path) {
+            return Ok(envi::read_image(file_path)?.get_band(0).clone());
+        }
+
+        if tiffio::is_float_tiff(file_path) {
+            return Ok(tiffio::read_buffer(file_path)?);
+        }
+
+        let image_data = open(file_path)?.into_luma16();
         let dims = image_data.dimensions();
 
         let width = dims.0 as usize;
@@ -312,7 +337,7 @@ impl ImageBuffer {
             }
         }
 
-        ImageBuffer::from_vec(&v, width, height)
+        Ok(ImageBuffer::from_vec(&v, width, height)?)
     }
 
     fn new_from_op_masked(
@@ -868,7 +893,7 @@ impl ImageBuffer {
         out_img
     }
 
-    pub fn save_16bit(&self, to_file: &str) {
+    pub fn save_16bit(&self, to_file: &str) -> error::Result<()> {
         let mut out_img =
             DynamicImage::new_rgba16(self.width as u32, self.height as u32).into_rgba16();
 
@@ -884,17 +909,12 @@ impl ImageBuffer {
             }
         }
 
-        if path::parent_exists_and_writable(to_file) {
-            out_img.save(to_file).unwrap();
-        } else {
-            panic!(
-                "Parent path does not exist or is unwritable: {}",
-                path::get_parent(to_file)
-            );
-        }
+        error::check_parent_writable(to_file)?;
+        out_img.save(to_file)?;
+        Ok(())
     }
 
-    pub fn save_8bit(&self, to_file: &str) {
+    pub fn save_8bit(&self, to_file: &str) -> error::Result<()> {
         let mut out_img =
             DynamicImage::new_rgba8(self.width as u32, self.height as u32).into_rgba8();
 
@@ -910,20 +930,20 @@ impl ImageBuffer {
             }
         }
 
-        if path::parent_exists_and_writable(to_file) {
-            out_img.save(to_file).unwrap();
-        } else {
-            panic!(
-                "Parent path does not exist or is unwritable: {}",
-                path::get_parent(to_file)
-            );
-        }
+        error::check_parent_writable(to_file)?;
+        out_img.save(to_file)?;
+        Ok(())
     }
 
-    pub fn save(&self, to_file: &str, mode: enums::ImageMode) {
+    pub fn save(&self, to_file: &str, mode: enums::ImageMode) -> error::Result<()> {
         match mode {
             enums::ImageMode::U8BIT => self.save_8bit(to_file),
             _ => self.save_16bit(to_file),
-        };
+        }
+    }
+
+    // Saves the buffer without rounding or clipping as a 32 bit float TIFF
+    pub fn save_float(&self, to_file: &str) -> error::Result<()> {
+        Ok(tiffio::write_buffer(self, to_file)?)
     }
 }
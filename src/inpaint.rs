@@ -284,10 +284,8 @@ pub fn apply_inpaint_to_buffer_with_mask(rgb: &Image, mask_src: &ImageBuffer) ->
         infill(&mut working_buffer, &mut mask, &pt);
     }
 
-    let newimage = match vec_to_rgb_image(&working_buffer) {
-        Ok(i) => i,
-        Err(e) => return Err(e),
-    };
+    let mut newimage = vec_to_rgb_image(&working_buffer)?;
+    newimage.set_metadata(rgb.get_metadata());
 
     Ok(newimage)
 }
@@ -13,11 +13,15 @@ pub mod blur;
 pub mod camera;
 pub mod debayer;
 pub mod decompanding;
+pub mod dem;
+pub mod disparity;
 pub mod drawable;
 pub mod enums;
+pub mod envi;
 
-#[deprecated]
 pub mod error;
+pub mod fits;
+pub mod footprint;
 pub mod guassianblur;
 pub mod hotpixel;
 pub mod image;
@@ -27,16 +31,28 @@ pub mod inpaint;
 pub mod lowpass;
 pub mod matrix;
 pub mod medianblur;
+pub mod metadata;
+pub mod mosaic;
 pub mod noise;
 pub mod path;
+pub mod pds3;
+pub mod pds4;
+pub mod pngio;
+pub mod pointcloud;
 pub mod prelude;
 pub mod quality;
 pub mod quaternion;
+pub mod raster;
+pub mod reproject;
 pub mod resize;
+pub mod sidecar;
 pub mod stats;
+pub mod stereo;
+pub mod tiffio;
 pub mod unsharp;
 pub mod util;
 pub mod vector;
+pub mod vicar;
 
 // Dn -> Digital number / image pixel value as 32 bit floating point.
 pub type Dn = f32;
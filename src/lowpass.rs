@@ -47,6 +47,7 @@ pub fn lowpass_imagebuffer(imagebuff: &ImageBuffer, window_size: usize) -> Image
 
 pub fn lowpass(image: &Image, window_size: usize) -> Image {
     let mut lowpass_image = Image::new(image.width, image.height, image.get_mode()).unwrap();
+    lowpass_image.set_metadata(image.get_metadata());
 
     (0..image.num_bands()).for_each(|b| {
         let buffer = image.get_band(b);
@@ -0,0 +1,140 @@
+use crate::camera::model::CameraModel;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A single processing operation applied to an image, with the parameters it was run with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessingStep {
+    pub operation: String,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub parameters: BTreeMap<String, String>,
+
+    // UTC time the operation was applied
+    pub timestamp: String,
+}
+
+impl ProcessingStep {
+    pub fn new(operation: &str) -> ProcessingStep {
+        ProcessingStep {
+            operation: operation.to_string(),
+            parameters: BTreeMap::new(),
+            timestamp: chrono::Utc::now()
+                .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+                .to_string(),
+        }
+    }
+
+    pub fn with_parameter<T: ToString>(mut self, name: &str, value: T) -> ProcessingStep {
+        self.parameters.insert(name.to_string(), value.to_string());
+        self
+    }
+}
+
+// Formatted as `operation(name=value, ...)`, omitting the parentheses when there are no
+// parameters.
+impl fmt::Display for ProcessingStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.operation)?;
+        if !self.parameters.is_empty() {
+            let params: Vec<String> = self
+                .parameters
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect();
+            write!(f, "({})", params.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+fn is_unset_camera_model(camera_model: &Option<CameraModel>) -> bool {
+    match camera_model {
+        Some(m) => !m.is_valid(),
+        None => true,
+    }
+}
+
+/// Observation and geometry information carried along with an image through processing.
+/// Fields are optional since the availability of each depends on the source product.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metadata {
+    // Instrument or camera identifier (e.g. NAVCAM_LEFT, MCZ_RIGHT)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instrument: Option<String>,
+
+    // Name of the filter the image was acquired through
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+
+    // Exposure duration, in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exposure_time: Option<f64>,
+
+    // Mission sol of acquisition
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sol: Option<u32>,
+
+    // Spacecraft clock at the start of acquisition
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sclk: Option<f64>,
+
+    // UTC time at the start of acquisition, as recorded by the source product
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<String>,
+
+    // Invalid models are left out, as they carry no information
+    #[serde(skip_serializing_if = "is_unset_camera_model")]
+    pub camera_model: Option<CameraModel>,
+
+    // Center wavelength of each band, in `wavelength_units`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub wavelengths: Vec<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wavelength_units: Option<String>,
+
+    // Processing operations applied to the image, in the order they were applied
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<ProcessingStep>,
+
+    // Whether the pixel values were loaded from floating point samples. `Image::save` writes
+    // such images to TIFF and OpenEXR as 32 bit floats rather than scaling them into the
+    // integer range of the image mode.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub float_samples: bool,
+}
+
+impl Metadata {
+    pub fn new() -> Metadata {
+        Metadata::default()
+    }
+
+    pub fn with_camera_model(camera_model: &CameraModel) -> Metadata {
+        Metadata {
+            camera_model: Some(camera_model.clone()),
+            ..Default::default()
+        }
+    }
+
+    /// Center wavelength of a band, if known
+    pub fn band_wavelength(&self, band: usize) -> Option<f64> {
+        self.wavelengths.get(band).copied()
+    }
+
+    pub fn add_history(&mut self, step: ProcessingStep) {
+        self.history.push(step);
+    }
+
+    /// Exports the processing history as a JSON array
+    pub fn history_to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.history)?)
+    }
+
+    pub fn history_from_json(json: &str) -> Result<Vec<ProcessingStep>> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
@@ -0,0 +1,420 @@
+/*
+    Mosaicking of many camera frames into a shared panoramic projection.
+
+    Every pixel of the mosaic is mapped to a look direction from the mosaic origin, in azimuth
+    and elevation as given by Vector::get_az() and Vector::get_el(), with +Z up and azimuth
+    increasing counterclockwise from +X when viewed from above. The point along that direction,
+    either at infinity or at a fixed range from the origin, is projected into each frame through
+    its camera model and sampled where it lands. Where frames overlap, their samples are
+    combined according to the blend mode.
+
+    Output views are oriented as seen by an observer at the origin with +Z up, so neither the
+    cylindrical nor the perspective projection is mirrored, and the polar projection is viewed
+    from above with +X to the right and +Y up.
+*/
+
+use crate::{
+    camera::{
+        cahv::Cahv,
+        model::{CameraModel, ImageCoordinate},
+    },
+    image::Image,
+    metadata::ProcessingStep,
+    reproject::{self, Interpolation},
+    vector::Vector,
+};
+
+use anyhow::{anyhow, Result};
+use std::f64::consts::FRAC_PI_2;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Projection {
+    // Azimuth along columns, decreasing from `max_azimuth` at the left edge, and elevation along
+    // rows, decreasing from `max_elevation` at the top edge. Angles are in radians.
+    Cylindrical {
+        min_azimuth: f64,
+        max_azimuth: f64,
+        min_elevation: f64,
+        max_elevation: f64,
+    },
+
+    // Pinhole view centered on an azimuth and elevation, with the angular resolution of the
+    // mosaic at its center. Angles are in radians.
+    Perspective {
+        azimuth: f64,
+        elevation: f64,
+        fov_horizontal: f64,
+        fov_vertical: f64,
+    },
+
+    // Overhead view with the nadir at the center of the image, azimuth around it and elevation
+    // increasing outwards up to `max_elevation`, in radians.
+    Polar {
+        max_elevation: f64,
+    },
+}
+
+impl Projection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Projection::Cylindrical { .. } => "cylindrical",
+            Projection::Perspective { .. } => "perspective",
+            Projection::Polar { .. } => "polar",
+        }
+    }
+}
+
+/// How samples from overlapping frames are combined
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BlendMode {
+    // The earliest frame in the list covering a pixel wins
+    First,
+
+    // The latest frame in the list covering a pixel wins
+    Last,
+
+    // Mean of all frames covering a pixel
+    Average,
+
+    // Mean weighted by distance from the edges of each frame, to hide seams
+    Feather,
+}
+
+impl BlendMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlendMode::First => "first",
+            BlendMode::Last => "last",
+            BlendMode::Average => "average",
+            BlendMode::Feather => "feather",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MosaicOptions {
+    pub projection: Projection,
+
+    // Angular size of a mosaic pixel, in radians
+    pub resolution: f64,
+
+    pub blend: BlendMode,
+    pub interpolation: Interpolation,
+
+    // Distance from the origin at which points are projected into the frames, or None for
+    // infinity. A finite range accounts for parallax between cameras offset from the origin.
+    pub projection_range: Option<f64>,
+}
+
+impl Default for MosaicOptions {
+    fn default() -> Self {
+        MosaicOptions {
+            projection: Projection::Cylindrical {
+                min_azimuth: -std::f64::consts::PI,
+                max_azimuth: std::f64::consts::PI,
+                min_elevation: -FRAC_PI_2,
+                max_elevation: FRAC_PI_2,
+            },
+            resolution: 0.001,
+            blend: BlendMode::Feather,
+            interpolation: Interpolation::Bilinear,
+            projection_range: None,
+        }
+    }
+}
+
+/// Unit vector pointing along an azimuth and elevation, the inverse of Vector::get_az() and
+/// Vector::get_el()
+pub fn az_el_to_vector(azimuth: f64, elevation: f64) -> Vector {
+    Vector::new(
+        elevation.cos() * azimuth.cos(),
+        elevation.cos() * azimuth.sin(),
+        elevation.sin(),
+    )
+}
+
+// Number of pixels needed to span an angle
+fn span_pixels(angle: f64, resolution: f64) -> usize {
+    (angle / resolution).round() as usize + 1
+}
+
+// Pinhole camera model at the origin for a perspective mosaic
+fn perspective_model(
+    origin: &Vector,
+    azimuth: f64,
+    elevation: f64,
+    resolution: f64,
+    width: usize,
+    height: usize,
+) -> CameraModel {
+    let a = az_el_to_vector(azimuth, elevation);
+    let h_dir = Vector::new(azimuth.sin(), -azimuth.cos(), 0.0);
+    let v_dir = a.cross_product(&h_dir);
+
+    let focal_length = 1.0 / resolution.tan();
+    let hc = (width - 1) as f64 / 2.0;
+    let vc = (height - 1) as f64 / 2.0;
+
+    CameraModel::new(Box::new(Cahv {
+        c: *origin,
+        a,
+        h: h_dir.scale(focal_length).add(&a.scale(hc)),
+        v: v_dir.scale(focal_length).add(&a.scale(vc)),
+    }))
+}
+
+// Maps mosaic pixels to look directions from the origin
+enum PixelMapping {
+    Cylindrical {
+        max_azimuth: f64,
+        max_elevation: f64,
+    },
+    Perspective(CameraModel),
+    Polar {
+        center: f64,
+        max_elevation: f64,
+    },
+}
+
+impl PixelMapping {
+    fn look_direction(&self, x: usize, y: usize, resolution: f64) -> Option<Vector> {
+        match self {
+            PixelMapping::Cylindrical {
+                max_azimuth,
+                max_elevation,
+            } => Some(az_el_to_vector(
+                max_azimuth - x as f64 * resolution,
+                max_elevation - y as f64 * resolution,
+            )),
+            PixelMapping::Perspective(model) => model
+                .ls_to_look_vector(&ImageCoordinate {
+                    line: y as f64,
+                    sample: x as f64,
+                })
+                .ok()
+                .map(|lv| lv.look_direction),
+            PixelMapping::Polar {
+                center,
+                max_elevation,
+            } => {
+                let offset = Vector::new(x as f64 - center, center - y as f64, 0.0);
+                let elevation = -FRAC_PI_2 + offset.len() * resolution;
+                if elevation > *max_elevation {
+                    None
+                } else {
+                    Some(az_el_to_vector(offset.get_az(), elevation))
+                }
+            }
+        }
+    }
+}
+
+// Lays out the mosaic for a projection, returning its pixel mapping and size
+fn layout(
+    projection: &Projection,
+    origin: &Vector,
+    resolution: f64,
+) -> Result<(PixelMapping, usize, usize)> {
+    match *projection {
+        Projection::Cylindrical {
+            min_azimuth,
+            max_azimuth,
+            min_elevation,
+            max_elevation,
+        } => {
+            if max_azimuth <= min_azimuth || max_elevation <= min_elevation {
+                return Err(anyhow!("Invalid cylindrical mosaic bounds"));
+            }
+            if min_elevation < -FRAC_PI_2 || max_elevation > FRAC_PI_2 {
+                return Err(anyhow!("Elevation must be within -90 and 90 degrees"));
+            }
+            Ok((
+                PixelMapping::Cylindrical {
+                    max_azimuth,
+                    max_elevation,
+                },
+                span_pixels(max_azimuth - min_azimuth, resolution),
+                span_pixels(max_elevation - min_elevation, resolution),
+            ))
+        }
+        Projection::Perspective {
+            azimuth,
+            elevation,
+            fov_horizontal,
+            fov_vertical,
+        } => {
+            if fov_horizontal <= 0.0
+                || fov_vertical <= 0.0
+                || fov_horizontal >= std::f64::consts::PI
+                || fov_vertical >= std::f64::consts::PI
+            {
+                return Err(anyhow!(
+                    "Perspective field of view must be between 0 and 180 degrees"
+                ));
+            }
+            if elevation.abs() >= FRAC_PI_2 {
+                return Err(anyhow!(
+                    "Perspective mosaic cannot be centered on the zenith or nadir"
+                ));
+            }
+
+            // Sized so the edges of the image subtend the requested field of view
+            let focal_length = 1.0 / resolution.tan();
+            let width = (2.0 * focal_length * (fov_horizontal / 2.0).tan()).round() as usize + 1;
+            let height = (2.0 * focal_length * (fov_vertical / 2.0).tan()).round() as usize + 1;
+            Ok((
+                PixelMapping::Perspective(perspective_model(
+                    origin, azimuth, elevation, resolution, width, height,
+                )),
+                width,
+                height,
+            ))
+        }
+        Projection::Polar { max_elevation } => {
+            if max_elevation <= -FRAC_PI_2 || max_elevation > FRAC_PI_2 {
+                return Err(anyhow!(
+                    "Polar mosaic maximum elevation must be above -90 and at most 90 degrees"
+                ));
+            }
+            let radius = ((max_elevation + FRAC_PI_2) / resolution).round() as usize;
+            Ok((
+                PixelMapping::Polar {
+                    center: radius as f64,
+                    max_elevation,
+                },
+                radius * 2 + 1,
+                radius * 2 + 1,
+            ))
+        }
+    }
+}
+
+// Weight of a sample from a frame under the blend mode. Feathering weights fall off linearly
+// to the frame edges, never quite reaching zero so a lone frame still covers its border.
+fn blend_weight(frame: &Image, ls: &ImageCoordinate, blend: BlendMode) -> f32 {
+    match blend {
+        BlendMode::Feather => {
+            let dx = ls.sample.min((frame.width - 1) as f64 - ls.sample);
+            let dy = ls.line.min((frame.height - 1) as f64 - ls.line);
+            (dx.min(dy).max(0.0) + 1.0) as f32
+        }
+        _ => 1.0,
+    }
+}
+
+/// Projects frames, each with its camera model, into a mosaic around `origin`.
+///
+/// All frames must have the same number of bands, and the mosaic takes the image mode and
+/// metadata of the first frame. Mosaic pixels which no frame covers are masked out in the alpha
+/// band. For perspective mosaics the camera model of the view is attached to the metadata,
+/// otherwise the camera model is cleared.
+pub fn build_mosaic(
+    frames: &[(Image, CameraModel)],
+    origin: &Vector,
+    options: &MosaicOptions,
+) -> Result<Image> {
+    let (first, _) = frames
+        .first()
+        .ok_or_else(|| anyhow!("Cannot build a mosaic without frames"))?;
+    let num_bands = first.num_bands();
+    for (frame, model) in frames.iter() {
+        if frame.is_empty() || frame.width == 0 || frame.height == 0 {
+            return Err(anyhow!("Cannot build a mosaic from an empty frame"));
+        }
+        if frame.num_bands() != num_bands {
+            return Err(anyhow!(
+                "Frames must have the same number of bands, expected {}, got {}",
+                num_bands,
+                frame.num_bands()
+            ));
+        }
+        if !model.is_valid() {
+            return Err(anyhow!(
+                "Cannot build a mosaic with an invalid camera model"
+            ));
+        }
+    }
+    if options.resolution <= 0.0 {
+        return Err(anyhow!("Invalid mosaic resolution: {}", options.resolution));
+    }
+
+    let (mapping, width, height) = layout(&options.projection, origin, options.resolution)?;
+    let mut mosaic =
+        Image::new_with_bands_masked(width, height, num_bands, first.get_mode(), false)?;
+
+    let mut values = vec![0.0_f32; num_bands];
+    for y in 0..height {
+        for x in 0..width {
+            let direction = match mapping.look_direction(x, y, options.resolution) {
+                Some(d) => d,
+                None => continue,
+            };
+
+            values.iter_mut().for_each(|v| *v = 0.0);
+            let mut total_weight = 0.0;
+            for (frame, model) in frames.iter() {
+                let ls = match reproject::source_coordinate(
+                    frame,
+                    model,
+                    origin,
+                    &direction,
+                    options.projection_range,
+                ) {
+                    Some(ls) => ls,
+                    None => continue,
+                };
+
+                let weight = blend_weight(frame, &ls, options.blend);
+                if options.blend == BlendMode::Last {
+                    values.iter_mut().for_each(|v| *v = 0.0);
+                    total_weight = 0.0;
+                }
+                for (b, v) in values.iter_mut().enumerate() {
+                    if let Some(s) = reproject::sample(
+                        frame.get_band(b),
+                        ls.sample,
+                        ls.line,
+                        options.interpolation,
+                    ) {
+                        *v += s * weight;
+                    }
+                }
+                total_weight += weight;
+
+                if options.blend == BlendMode::First {
+                    break;
+                }
+            }
+
+            if total_weight > 0.0 {
+                for (b, v) in values.iter().enumerate() {
+                    mosaic.put(x, y, v / total_weight, b);
+                }
+                mosaic.put_alpha(x, y, true);
+            }
+        }
+    }
+
+    let mut metadata = first.get_metadata().clone();
+    metadata.camera_model = match mapping {
+        PixelMapping::Perspective(model) => Some(model),
+        _ => None,
+    };
+    metadata.add_history(
+        ProcessingStep::new("mosaic")
+            .with_parameter("projection", options.projection.name())
+            .with_parameter("resolution", options.resolution)
+            .with_parameter("blend", options.blend.name())
+            .with_parameter("interpolation", options.interpolation.name())
+            .with_parameter("frames", frames.len())
+            .with_parameter(
+                "range",
+                options
+                    .projection_range
+                    .map_or(String::from("infinity"), |r| r.to_string()),
+            ),
+    );
+    mosaic.set_metadata(&metadata);
+
+    Ok(mosaic)
+}
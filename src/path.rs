@@ -1,6 +1,6 @@
 use std::env;
 use std::ffi::OsStr;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub fn get_extension(chk_path: &str) -> Option<&str> {
     Path::new(chk_path).extension().and_then(OsStr::to_str)
@@ -54,3 +54,21 @@ pub fn cwd() -> String {
         None => String::from("./"),
     }
 }
+
+// Locates a file referenced relative to another file (e.g. a detached data file named in
+// a label). Product file names are often recorded in upper case but distributed in lower
+// case, so both variants are tried.
+pub fn locate_sibling_file(chk_path: &str, file_name: &str) -> Option<PathBuf> {
+    let parent = Path::new(chk_path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+
+    [
+        file_name.to_string(),
+        file_name.to_lowercase(),
+        file_name.to_uppercase(),
+    ]
+    .iter()
+    .map(|c| parent.join(c))
+    .find(|p| p.exists())
+}
@@ -0,0 +1,695 @@
+/*
+    Native reader for PDS3 products.
+
+    Parses Object Description Language (ODL) labels, either attached to the front of the
+    product or detached in a separate .LBL file, into a tree of objects, groups and keywords.
+    The raster referenced by a pointer such as ^IMAGE is then read directly from the file.
+
+    Reference: PDS Standards Reference, Chapter 12 (Object Description Language)
+    https://pds.nasa.gov/datastandards/pds3/standards/
+*/
+
+use crate::{
+    binfilereader::{BinFileReader, Endian},
+    enums::ImageMode,
+    error::{self, SciImgError},
+    image::Image,
+    path,
+    raster::{self, Interleave, RasterLayout, SampleType},
+};
+
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Integer(i64),
+    Real(f64),
+
+    // Double-quoted text string
+    Text(String),
+
+    // Single-quoted or unquoted literal, including dates and times
+    Symbol(String),
+
+    // A numeric value followed by a units expression, e.g. 1.5 <m>
+    WithUnits(Box<Value>, String),
+
+    // Ordered collection in parentheses
+    Sequence(Vec<Value>),
+
+    // Unordered collection in braces
+    Set(Vec<Value>),
+}
+
+impl Value {
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            Value::Real(f) if f.fract() == 0.0 => Some(*f as i64),
+            Value::WithUnits(v, _) => v.as_i64(),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Real(f) => Some(*f),
+            Value::WithUnits(v, _) => v.as_f64(),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(s) | Value::Symbol(s) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn as_vec(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Sequence(v) | Value::Set(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the members of a sequence as floating point values
+    pub fn as_f64_vec(&self) -> Option<Vec<f64>> {
+        self.as_vec()?.iter().map(|v| v.as_f64()).collect()
+    }
+
+    pub fn units(&self) -> Option<&str> {
+        match self {
+            Value::WithUnits(_, u) => Some(u.as_str()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyword {
+    pub name: String,
+    pub value: Value,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BlockType {
+    Object,
+    Group,
+}
+
+/// An OBJECT or GROUP within the label, along with its keywords and nested blocks.
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub block_type: BlockType,
+    pub name: String,
+    pub keywords: Vec<Keyword>,
+    pub blocks: Vec<Block>,
+}
+
+impl Block {
+    fn new(block_type: BlockType, name: &str) -> Block {
+        Block {
+            block_type,
+            name: name.to_string(),
+            keywords: vec![],
+            blocks: vec![],
+        }
+    }
+
+    /// Value of a keyword defined directly within this block
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.keywords
+            .iter()
+            .find(|k| k.name.eq_ignore_ascii_case(name))
+            .map(|k| &k.value)
+    }
+
+    pub fn get_i64(&self, name: &str) -> Option<i64> {
+        self.get(name).and_then(|v| v.as_i64())
+    }
+
+    pub fn get_f64(&self, name: &str) -> Option<f64> {
+        self.get(name).and_then(|v| v.as_f64())
+    }
+
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        self.get(name).and_then(|v| v.as_str())
+    }
+
+    /// Depth-first search for the first object or group with the specified name
+    pub fn find_block(&self, name: &str) -> Option<&Block> {
+        for b in self.blocks.iter() {
+            if b.name.eq_ignore_ascii_case(name) {
+                return Some(b);
+            }
+            if let Some(found) = b.find_block(name) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    /// Depth-first search for the first object (not group) with the specified name
+    pub fn find_object(&self, name: &str) -> Option<&Block> {
+        for b in self.blocks.iter() {
+            if b.block_type == BlockType::Object && b.name.eq_ignore_ascii_case(name) {
+                return Some(b);
+            }
+            if let Some(found) = b.find_object(name) {
+                return Some(found);
+            }
+        }
+        None
+    }
+}
+
+/// A parsed PDS3 label.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub root: Block,
+}
+
+impl Label {
+    pub fn parse(text: &[u8]) -> Result<Label> {
+        let mut parser = Parser::new(text);
+        Ok(Label {
+            root: parser.parse_label()?,
+        })
+    }
+
+    pub fn parse_str(text: &str) -> Result<Label> {
+        Label::parse(text.as_bytes())
+    }
+
+    pub fn from_file(file_path: &str) -> Result<Label> {
+        error::check_file_exists(file_path)?;
+        Label::parse(&read_label_bytes(file_path)?)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.root.get(name)
+    }
+
+    pub fn get_i64(&self, name: &str) -> Option<i64> {
+        self.root.get_i64(name)
+    }
+
+    pub fn get_f64(&self, name: &str) -> Option<f64> {
+        self.root.get_f64(name)
+    }
+
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        self.root.get_str(name)
+    }
+
+    pub fn find_block(&self, name: &str) -> Option<&Block> {
+        self.root.find_block(name)
+    }
+
+    pub fn find_object(&self, name: &str) -> Option<&Block> {
+        self.root.find_object(name)
+    }
+
+    /// Value of a data pointer such as ^IMAGE. The leading caret is optional.
+    pub fn pointer(&self, name: &str) -> Option<&Value> {
+        let name = name.trim_start_matches('^');
+        self.get(&format!("^{}", name))
+    }
+
+    /// Resolves a data pointer to the file containing the data and the byte offset
+    /// at which it starts. Detached files are located relative to the label.
+    pub fn resolve_pointer(&self, name: &str, label_path: &str) -> Result<(PathBuf, usize)> {
+        let pointer = self
+            .pointer(name)
+            .ok_or_else(|| anyhow!("Label does not contain a ^{} pointer", name))?;
+
+        let record_bytes = label_usize(self.get_i64("RECORD_BYTES").unwrap_or(0), "RECORD_BYTES")?;
+
+        let location_to_offset = |v: &Value| -> Result<usize> {
+            let location = v
+                .as_i64()
+                .ok_or_else(|| anyhow!("Invalid pointer location: {:?}", v))?;
+            let location = label_usize(location, "pointer location")?;
+            if location < 1 {
+                return Err(anyhow!("Invalid pointer location: {}", location));
+            }
+            match v.units() {
+                Some(u) if u.eq_ignore_ascii_case("BYTES") => Ok(location - 1),
+                _ => {
+                    if record_bytes == 0 {
+                        Err(anyhow!(
+                            "Pointer is in records but RECORD_BYTES is not defined"
+                        ))
+                    } else {
+                        (location - 1)
+                            .checked_mul(record_bytes)
+                            .ok_or_else(|| anyhow!("Pointer location is too large: {}", location))
+                    }
+                }
+            }
+        };
+
+        match pointer {
+            Value::Text(f) | Value::Symbol(f) => Ok((locate_detached_file(label_path, f)?, 0)),
+            Value::Sequence(s) if s.len() == 2 => match s[0].as_str() {
+                Some(f) => Ok((
+                    locate_detached_file(label_path, f)?,
+                    location_to_offset(&s[1])?,
+                )),
+                None => Err(anyhow!("Invalid pointer value: {:?}", pointer)),
+            },
+            Value::Sequence(s) if s.len() == 1 => match s[0].as_str() {
+                Some(f) => Ok((locate_detached_file(label_path, f)?, 0)),
+                None => Err(anyhow!("Invalid pointer value: {:?}", pointer)),
+            },
+            Value::Integer(_) | Value::WithUnits(_, _) => {
+                Ok((PathBuf::from(label_path), location_to_offset(pointer)?))
+            }
+            _ => Err(anyhow!("Invalid pointer value: {:?}", pointer)),
+        }
+    }
+}
+
+fn locate_detached_file(label_path: &str, file_name: &str) -> Result<PathBuf> {
+    path::locate_sibling_file(label_path, file_name)
+        .ok_or_else(|| anyhow!("Detached data file not found: {}", file_name))
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a [u8]) -> Parser<'a> {
+        Parser { data, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_ascii_whitespace() || c == 0 {
+                self.pos += 1;
+            } else if c == b'/' && self.data.get(self.pos + 1) == Some(&b'*') {
+                self.pos += 2;
+                while self.pos < self.data.len()
+                    && !(self.data[self.pos] == b'*' && self.data.get(self.pos + 1) == Some(&b'/'))
+                {
+                    self.pos += 1;
+                }
+                self.pos = usize::min(self.pos + 2, self.data.len());
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn read_identifier(&mut self) -> String {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == b'_' || c == b':' || c == b'^' {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        String::from_utf8_lossy(&self.data[start..self.pos]).to_string()
+    }
+
+    fn read_until(&mut self, terminator: u8) -> Result<String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c == terminator {
+                let s = String::from_utf8_lossy(&self.data[start..self.pos]).to_string();
+                self.pos += 1;
+                return Ok(s);
+            }
+            self.pos += 1;
+        }
+        Err(anyhow!(
+            "Unterminated value starting at byte {}, expected '{}'",
+            start,
+            terminator as char
+        ))
+    }
+
+    fn parse_collection(&mut self, close: u8) -> Result<Vec<Value>> {
+        let mut values = vec![];
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(c) if c == close => {
+                    self.pos += 1;
+                    return Ok(values);
+                }
+                Some(b',') => {
+                    self.pos += 1;
+                }
+                Some(_) => values.push(self.parse_value()?),
+                None => return Err(anyhow!("Unterminated sequence or set")),
+            }
+        }
+    }
+
+    fn parse_bare(&mut self) -> Result<Value> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_whitespace() || b",(){}<\"".contains(&c) {
+                break;
+            }
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(anyhow!("Expected a value at byte {}", start));
+        }
+        let s = String::from_utf8_lossy(&self.data[start..self.pos]).to_string();
+        Ok(parse_scalar(&s))
+    }
+
+    fn parse_value(&mut self) -> Result<Value> {
+        self.skip_whitespace();
+        let value = match self.peek() {
+            Some(b'(') => {
+                self.pos += 1;
+                Value::Sequence(self.parse_collection(b')')?)
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                Value::Set(self.parse_collection(b'}')?)
+            }
+            Some(b'"') => {
+                self.pos += 1;
+                Value::Text(self.read_until(b'"')?)
+            }
+            Some(b'\'') => {
+                self.pos += 1;
+                Value::Symbol(self.read_until(b'\'')?)
+            }
+            Some(_) => self.parse_bare()?,
+            None => return Err(anyhow!("Unexpected end of label while reading value")),
+        };
+
+        // A units expression may follow a value
+        let save = self.pos;
+        self.skip_whitespace();
+        if self.peek() == Some(b'<') {
+            self.pos += 1;
+            let units = self.read_until(b'>')?;
+            Ok(Value::WithUnits(Box::new(value), units.trim().to_string()))
+        } else {
+            self.pos = save;
+            Ok(value)
+        }
+    }
+
+    fn parse_label(&mut self) -> Result<Block> {
+        let mut stack: Vec<Block> = vec![Block::new(BlockType::Group, "ROOT")];
+
+        loop {
+            self.skip_whitespace();
+            if self.peek().is_none() {
+                break;
+            }
+
+            let name = self.read_identifier();
+            if name.is_empty() {
+                return Err(anyhow!(
+                    "Invalid label statement at byte {}: '{}'",
+                    self.pos,
+                    self.peek().unwrap() as char
+                ));
+            }
+
+            let upper = name.to_uppercase();
+            if upper == "END" {
+                break;
+            }
+
+            self.skip_whitespace();
+            let has_value = self.peek() == Some(b'=');
+            if has_value {
+                self.pos += 1;
+            }
+
+            match upper.as_str() {
+                "OBJECT" | "GROUP" => {
+                    let block_name = self.parse_value()?;
+                    let block_type = if upper == "OBJECT" {
+                        BlockType::Object
+                    } else {
+                        BlockType::Group
+                    };
+                    stack.push(Block::new(
+                        block_type,
+                        block_name.as_str().unwrap_or_default(),
+                    ));
+                }
+                "END_OBJECT" | "END_GROUP" => {
+                    if has_value {
+                        self.parse_value()?;
+                    }
+                    if stack.len() < 2 {
+                        return Err(anyhow!("Unbalanced {} in label", upper));
+                    }
+                    let block = stack.pop().unwrap();
+                    stack.last_mut().unwrap().blocks.push(block);
+                }
+                _ => {
+                    if !has_value {
+                        return Err(anyhow!("Expected '=' after keyword {}", name));
+                    }
+                    let value = self.parse_value()?;
+                    stack
+                        .last_mut()
+                        .unwrap()
+                        .keywords
+                        .push(Keyword { name, value });
+                }
+            }
+        }
+
+        if stack.len() != 1 {
+            return Err(anyhow!(
+                "Unterminated object or group in label: {}",
+                stack.last().unwrap().name
+            ));
+        }
+
+        Ok(stack.pop().unwrap())
+    }
+}
+
+fn parse_scalar(s: &str) -> Value {
+    // Based integers, e.g. 16#0FFF# or 2#0000111111111111#
+    if s.ends_with('#') {
+        let parts: Vec<&str> = s.trim_end_matches('#').splitn(2, '#').collect();
+        if parts.len() == 2 {
+            if let Ok(radix) = parts[0].parse::<u32>() {
+                if (2..=36).contains(&radix) {
+                    if let Ok(i) = i64::from_str_radix(parts[1], radix) {
+                        return Value::Integer(i);
+                    }
+                }
+            }
+        }
+    }
+
+    if let Ok(i) = s.parse::<i64>() {
+        Value::Integer(i)
+    } else if let Ok(f) = s.parse::<f64>() {
+        Value::Real(f)
+    } else {
+        Value::Symbol(s.to_string())
+    }
+}
+
+fn sample_type_from_label(sample_type: &str, sample_bits: usize) -> Result<(SampleType, Endian)> {
+    let st = sample_type.to_uppercase();
+
+    let endian = if st.starts_with("LSB_") || st.starts_with("PC_") || st.starts_with("VAX_") {
+        Endian::LittleEndian
+    } else {
+        Endian::BigEndian
+    };
+
+    if st.starts_with("VAX_") && st.ends_with("REAL") {
+        return Err(anyhow!("VAX floating point samples are not supported"));
+    }
+
+    let is_real = st.ends_with("REAL");
+    let is_unsigned = st.contains("UNSIGNED") || st.contains("BIT_STRING");
+
+    let sample_type = match (sample_bits, is_real, is_unsigned) {
+        (8, false, true) => SampleType::UnsignedInt8,
+        (8, false, false) => SampleType::SignedInt8,
+        (16, false, true) => SampleType::UnsignedInt16,
+        (16, false, false) => SampleType::SignedInt16,
+        (32, false, true) => SampleType::UnsignedInt32,
+        (32, false, false) => SampleType::SignedInt32,
+        (32, true, _) => SampleType::Float32,
+        (64, true, _) => SampleType::Float64,
+        _ => {
+            return Err(anyhow!(
+                "Unsupported sample type: {} with {} bits",
+                sample_type,
+                sample_bits
+            ))
+        }
+    };
+
+    Ok((sample_type, endian))
+}
+
+fn interleave_from_label(band_storage_type: &str) -> Result<Interleave> {
+    match band_storage_type.to_uppercase().as_str() {
+        "BAND_SEQUENTIAL" => Ok(Interleave::BandSequential),
+        "LINE_INTERLEAVED" => Ok(Interleave::LineInterleaved),
+        "SAMPLE_INTERLEAVED" => Ok(Interleave::SampleInterleaved),
+        _ => Err(anyhow!(
+            "Unsupported band storage type: {}",
+            band_storage_type
+        )),
+    }
+}
+
+fn image_mode_from_object(object: &Block, sample_type: SampleType) -> ImageMode {
+    if sample_type.is_float() {
+        return ImageMode::U16BIT;
+    }
+
+    let valid_bits = match object.get_i64("SAMPLE_BIT_MASK") {
+        Some(mask) if mask > 0 => mask.count_ones() as usize,
+        _ => sample_type.bytes() * 8,
+    };
+
+    if valid_bits <= 8 {
+        ImageMode::U8BIT
+    } else if valid_bits <= 12 {
+        ImageMode::U12BIT
+    } else {
+        ImageMode::U16BIT
+    }
+}
+
+// Converts a label integer to a size, count or offset, rejecting negative values
+fn label_usize(value: i64, name: &str) -> Result<usize> {
+    usize::try_from(value).map_err(|_| {
+        SciImgError::InvalidFormat(format!("Invalid {} value in label: {}", name, value)).into()
+    })
+}
+
+/// Builds the raster layout described by an IMAGE object
+pub fn raster_layout_from_object(object: &Block, offset: usize) -> Result<RasterLayout> {
+    let lines = label_usize(
+        object
+            .get_i64("LINES")
+            .ok_or_else(|| anyhow!("Image object is missing LINES"))?,
+        "LINES",
+    )?;
+    let line_samples = label_usize(
+        object
+            .get_i64("LINE_SAMPLES")
+            .ok_or_else(|| anyhow!("Image object is missing LINE_SAMPLES"))?,
+        "LINE_SAMPLES",
+    )?;
+    let bands = label_usize(object.get_i64("BANDS").unwrap_or(1), "BANDS")?;
+    let sample_bits = label_usize(
+        object
+            .get_i64("SAMPLE_BITS")
+            .ok_or_else(|| anyhow!("Image object is missing SAMPLE_BITS"))?,
+        "SAMPLE_BITS",
+    )?;
+
+    let (sample_type, endian) = sample_type_from_label(
+        object
+            .get_str("SAMPLE_TYPE")
+            .ok_or_else(|| anyhow!("Image object is missing SAMPLE_TYPE"))?,
+        sample_bits,
+    )?;
+
+    let interleave = match object.get_str("BAND_STORAGE_TYPE") {
+        Some(s) if bands > 1 => interleave_from_label(s)?,
+        _ => Interleave::BandSequential,
+    };
+
+    let mut layout = RasterLayout::new(line_samples, lines, bands, sample_type, endian, interleave);
+    layout.offset = offset;
+    layout.line_prefix_bytes = label_usize(
+        object.get_i64("LINE_PREFIX_BYTES").unwrap_or(0),
+        "LINE_PREFIX_BYTES",
+    )?;
+    layout.line_suffix_bytes = label_usize(
+        object.get_i64("LINE_SUFFIX_BYTES").unwrap_or(0),
+        "LINE_SUFFIX_BYTES",
+    )?;
+    layout.scaling_factor = object.get_f64("SCALING_FACTOR").unwrap_or(1.0) as f32;
+    layout.value_offset = object.get_f64("OFFSET").unwrap_or(0.0) as f32;
+
+    Ok(layout)
+}
+
+/// Reads the image object referenced by the named pointer (e.g. "IMAGE")
+pub fn read_image_object(label: &Label, label_path: &str, pointer_name: &str) -> Result<Image> {
+    let object_name = pointer_name.trim_start_matches('^');
+    let object = label
+        .find_object(object_name)
+        .ok_or_else(|| anyhow!("Label does not contain an {} object", object_name))?;
+
+    let (data_path, offset) = label.resolve_pointer(object_name, label_path)?;
+    let layout = raster_layout_from_object(object, offset)?;
+    let mode = image_mode_from_object(object, layout.sample_type);
+
+    let reader = BinFileReader::open(&data_path)?;
+    raster::read_image(&reader, &layout, mode)
+}
+
+/// Opens a PDS3 product from either an attached-label file or a detached .LBL label
+/// and reads its primary image.
+pub fn read_image(file_path: &str) -> Result<Image> {
+    let label = Label::from_file(file_path)?;
+    read_image_object(&label, file_path, "IMAGE")
+}
+
+// Reads the label from the start of a file up to and including its END statement, leaving the
+// data of attached-label products unread.
+fn read_label_bytes(file_path: &str) -> Result<Vec<u8>> {
+    let mut reader = BufReader::new(fs::File::open(file_path)?);
+    let mut data: Vec<u8> = vec![];
+    let mut line: Vec<u8> = vec![];
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        data.extend_from_slice(&line);
+        if String::from_utf8_lossy(&line)
+            .trim()
+            .eq_ignore_ascii_case("END")
+        {
+            break;
+        }
+    }
+    Ok(data)
+}
+
+/// Checks whether a file begins with a PDS3 label
+pub fn is_pds3_file(file_path: &str) -> bool {
+    match fs::File::open(file_path) {
+        Ok(f) => {
+            let mut buf = [0_u8; 64];
+            let n = f.take(64).read(&mut buf).unwrap_or(0);
+            let head = String::from_utf8_lossy(&buf[..n]).to_uppercase();
+            head.trim_start().starts_with("PDS_VERSION_ID")
+                || head.trim_start().starts_with("ODL_VERSION_ID")
+        }
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,320 @@
+/*
+    Reader for PDS4 array products.
+
+    The XML label is parsed into a simple owned element tree which remains available for
+    downstream metadata lookups. Array_2D_Image and Array_3D_Image descriptions within each
+    File_Area_Observational are resolved into raster layouts and read via BinFileReader.
+
+    Reference: PDS4 Standards Reference, Section 5 (Array classes)
+    https://pds.nasa.gov/datastandards/documents/sr/
+*/
+
+use crate::{
+    binfilereader::{BinFileReader, Endian},
+    error,
+    image::Image,
+    path,
+    raster::{self, Interleave, RasterLayout, SampleType},
+};
+
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::PathBuf;
+
+/// A single XML element from the label. Namespace prefixes are stripped from names.
+#[derive(Debug, Clone)]
+pub struct Element {
+    pub name: String,
+    pub text: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+}
+
+impl Element {
+    fn from_node(node: &roxmltree::Node) -> Element {
+        Element {
+            name: node.tag_name().name().to_string(),
+            text: node.text().unwrap_or_default().trim().to_string(),
+            attributes: node
+                .attributes()
+                .map(|a| (a.name().to_string(), a.value().to_string()))
+                .collect(),
+            children: node
+                .children()
+                .filter(|n| n.is_element())
+                .map(|n| Element::from_node(&n))
+                .collect(),
+        }
+    }
+
+    /// First direct child with the specified name
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// All direct children with the specified name
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    /// Depth-first search for the first descendant with the specified name
+    pub fn find(&self, name: &str) -> Option<&Element> {
+        for c in self.children.iter() {
+            if c.name == name {
+                return Some(c);
+            }
+            if let Some(found) = c.find(name) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    /// Resolves a slash-separated path of child names, e.g.
+    /// "Observation_Area/Time_Coordinates/start_date_time"
+    pub fn find_path(&self, element_path: &str) -> Option<&Element> {
+        element_path
+            .split('/')
+            .filter(|p| !p.is_empty())
+            .try_fold(self, |e, p| e.child(p))
+    }
+
+    /// Text content of the element found at the specified path
+    pub fn text_at(&self, element_path: &str) -> Option<&str> {
+        self.find_path(element_path).map(|e| e.text.as_str())
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn text_as_f64(&self) -> Option<f64> {
+        self.text.parse::<f64>().ok()
+    }
+
+    pub fn text_as_usize(&self) -> Option<usize> {
+        self.text.parse::<usize>().ok()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArrayAxis {
+    pub name: String,
+    pub elements: usize,
+    pub sequence_number: usize,
+}
+
+/// An Array_2D_Image or Array_3D_Image description along with the file it is stored in
+#[derive(Debug, Clone)]
+pub struct ArrayImage {
+    pub file_name: String,
+    pub local_identifier: Option<String>,
+    pub offset: usize,
+    pub data_type: String,
+    pub scaling_factor: f64,
+    pub value_offset: f64,
+
+    // Sorted by sequence number, slowest varying first
+    pub axes: Vec<ArrayAxis>,
+}
+
+impl ArrayImage {
+    fn from_element(file_name: &str, array: &Element) -> Result<ArrayImage> {
+        let offset = array
+            .child("offset")
+            .and_then(|e| e.text_as_usize())
+            .ok_or_else(|| anyhow!("Array is missing a valid offset"))?;
+
+        let element_array = array
+            .child("Element_Array")
+            .ok_or_else(|| anyhow!("Array is missing Element_Array"))?;
+
+        let data_type = element_array
+            .text_at("data_type")
+            .ok_or_else(|| anyhow!("Element_Array is missing data_type"))?
+            .to_string();
+
+        let mut axes = array
+            .children_named("Axis_Array")
+            .map(|a| {
+                Ok(ArrayAxis {
+                    name: a
+                        .text_at("axis_name")
+                        .ok_or_else(|| anyhow!("Axis_Array is missing axis_name"))?
+                        .to_string(),
+                    elements: a
+                        .child("elements")
+                        .and_then(|e| e.text_as_usize())
+                        .ok_or_else(|| anyhow!("Axis_Array is missing elements"))?,
+                    sequence_number: a
+                        .child("sequence_number")
+                        .and_then(|e| e.text_as_usize())
+                        .ok_or_else(|| anyhow!("Axis_Array is missing sequence_number"))?,
+                })
+            })
+            .collect::<Result<Vec<ArrayAxis>>>()?;
+        axes.sort_by_key(|a| a.sequence_number);
+
+        Ok(ArrayImage {
+            file_name: file_name.to_string(),
+            local_identifier: array.text_at("local_identifier").map(String::from),
+            offset,
+            data_type,
+            scaling_factor: element_array
+                .child("scaling_factor")
+                .and_then(|e| e.text_as_f64())
+                .unwrap_or(1.0),
+            value_offset: element_array
+                .child("value_offset")
+                .and_then(|e| e.text_as_f64())
+                .unwrap_or(0.0),
+            axes,
+        })
+    }
+
+    fn axis(&self, name: &str) -> Option<&ArrayAxis> {
+        self.axes.iter().find(|a| a.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn width(&self) -> usize {
+        self.axis("Sample").map(|a| a.elements).unwrap_or(0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.axis("Line").map(|a| a.elements).unwrap_or(0)
+    }
+
+    pub fn bands(&self) -> usize {
+        self.axis("Band").map(|a| a.elements).unwrap_or(1)
+    }
+
+    pub fn interleave(&self) -> Result<Interleave> {
+        let order: Vec<String> = self.axes.iter().map(|a| a.name.to_lowercase()).collect();
+        let order: Vec<&str> = order.iter().map(|s| s.as_str()).collect();
+        match order[..] {
+            ["line", "sample"] => Ok(Interleave::BandSequential),
+            ["band", "line", "sample"] => Ok(Interleave::BandSequential),
+            ["line", "band", "sample"] => Ok(Interleave::LineInterleaved),
+            ["line", "sample", "band"] => Ok(Interleave::SampleInterleaved),
+            _ => Err(anyhow!("Unsupported array axis order: {:?}", order)),
+        }
+    }
+
+    pub fn raster_layout(&self) -> Result<RasterLayout> {
+        let (sample_type, endian) = sample_type_from_data_type(&self.data_type)?;
+        let mut layout = RasterLayout::new(
+            self.width(),
+            self.height(),
+            self.bands(),
+            sample_type,
+            endian,
+            self.interleave()?,
+        );
+        layout.offset = self.offset;
+        layout.scaling_factor = self.scaling_factor as f32;
+        layout.value_offset = self.value_offset as f32;
+        Ok(layout)
+    }
+}
+
+fn sample_type_from_data_type(data_type: &str) -> Result<(SampleType, Endian)> {
+    let endian = if data_type.contains("LSB") {
+        Endian::LittleEndian
+    } else {
+        Endian::BigEndian
+    };
+
+    let sample_type = match data_type {
+        "UnsignedByte" => SampleType::UnsignedInt8,
+        "SignedByte" => SampleType::SignedInt8,
+        "UnsignedMSB2" | "UnsignedLSB2" => SampleType::UnsignedInt16,
+        "SignedMSB2" | "SignedLSB2" => SampleType::SignedInt16,
+        "UnsignedMSB4" | "UnsignedLSB4" => SampleType::UnsignedInt32,
+        "SignedMSB4" | "SignedLSB4" => SampleType::SignedInt32,
+        "IEEE754MSBSingle" | "IEEE754LSBSingle" => SampleType::Float32,
+        "IEEE754MSBDouble" | "IEEE754LSBDouble" => SampleType::Float64,
+        _ => return Err(anyhow!("Unsupported PDS4 data type: {}", data_type)),
+    };
+
+    Ok((sample_type, endian))
+}
+
+/// A parsed PDS4 label
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub root: Element,
+    pub arrays: Vec<ArrayImage>,
+}
+
+impl Label {
+    pub fn parse_str(text: &str) -> Result<Label> {
+        let doc = roxmltree::Document::parse(text)?;
+        let root = Element::from_node(&doc.root_element());
+
+        let mut arrays: Vec<ArrayImage> = vec![];
+        for file_area in root.children_named("File_Area_Observational") {
+            let file_name = file_area
+                .text_at("File/file_name")
+                .ok_or_else(|| anyhow!("File_Area_Observational is missing File/file_name"))?;
+            for array in file_area.children.iter() {
+                if array.name == "Array_2D_Image" || array.name == "Array_3D_Image" {
+                    arrays.push(ArrayImage::from_element(file_name, array)?);
+                }
+            }
+        }
+
+        Ok(Label { root, arrays })
+    }
+
+    pub fn from_file(file_path: &str) -> Result<Label> {
+        error::check_file_exists(file_path)?;
+        Label::parse_str(&fs::read_to_string(file_path)?)
+    }
+
+    /// Text content of the element at the specified path relative to the product root
+    pub fn text_at(&self, element_path: &str) -> Option<&str> {
+        self.root.text_at(element_path)
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Element> {
+        self.root.find(name)
+    }
+
+    pub fn logical_identifier(&self) -> Option<&str> {
+        self.text_at("Identification_Area/logical_identifier")
+    }
+}
+
+/// Reads the specified array into an image. The data file is resolved relative to the label.
+pub fn read_array(label_path: &str, array: &ArrayImage) -> Result<Image> {
+    let data_path: PathBuf = path::locate_sibling_file(label_path, &array.file_name)
+        .ok_or_else(|| anyhow!("Data file not found: {}", array.file_name))?;
+
+    let layout = array.raster_layout()?;
+    let reader = BinFileReader::open(&data_path)?;
+    raster::read_image(&reader, &layout, layout.sample_type.image_mode())
+}
+
+/// Opens a PDS4 label and reads the first image array it describes
+pub fn read_image(label_path: &str) -> Result<Image> {
+    let label = Label::from_file(label_path)?;
+    let array = label
+        .arrays
+        .first()
+        .ok_or_else(|| anyhow!("Label does not describe an image array"))?;
+    read_array(label_path, array)
+}
+
+/// Checks whether a file is a PDS4 XML label
+pub fn is_pds4_file(file_path: &str) -> bool {
+    match path::get_extension(file_path) {
+        Some(e) if e.eq_ignore_ascii_case("xml") => match fs::read_to_string(file_path) {
+            Ok(s) => s.contains("http://pds.nasa.gov/pds4/pds/v1"),
+            Err(_) => false,
+        },
+        _ => false,
+    }
+}
@@ -0,0 +1,81 @@
+/*
+    PNG output with embedded text metadata.
+
+    The image crate does not expose PNG text chunks, so images carrying metadata are encoded
+    through the png crate directly. Text is stored as iTXt (UTF-8) chunks written ahead of the
+    image data, allowing it to be read back without decoding the pixels.
+*/
+
+use crate::error;
+
+use anyhow::{anyhow, Result};
+use image::DynamicImage;
+use png::{BitDepth, ColorType, Decoder, Encoder};
+use std::fs::File;
+use std::io::BufWriter;
+
+/// Keyword of the text chunk holding the processing history as JSON
+pub const HISTORY_KEYWORD: &str = "sciimg:history";
+
+/// Writes an 8 or 16 bit grayscale, RGB or RGBA image as a PNG with the supplied
+/// keyword/text pairs as text chunks
+pub fn write_image(image: &DynamicImage, to_file: &str, text: &[(&str, String)]) -> Result<()> {
+    error::check_parent_writable(to_file)?;
+
+    // PNG stores 16 bit samples big endian
+    let be_bytes = |v: &[u16]| -> Vec<u8> { v.iter().flat_map(|s| s.to_be_bytes()).collect() };
+
+    let (color, depth, data) = match image {
+        DynamicImage::ImageLuma8(i) => (ColorType::Grayscale, BitDepth::Eight, i.to_vec()),
+        DynamicImage::ImageRgb8(i) => (ColorType::Rgb, BitDepth::Eight, i.to_vec()),
+        DynamicImage::ImageRgba8(i) => (ColorType::Rgba, BitDepth::Eight, i.to_vec()),
+        DynamicImage::ImageLuma16(i) => (ColorType::Grayscale, BitDepth::Sixteen, be_bytes(i)),
+        DynamicImage::ImageRgb16(i) => (ColorType::Rgb, BitDepth::Sixteen, be_bytes(i)),
+        DynamicImage::ImageRgba16(i) => (ColorType::Rgba, BitDepth::Sixteen, be_bytes(i)),
+        i => return Err(anyhow!("Unsupported PNG color type: {:?}", i.color())),
+    };
+
+    let mut encoder = Encoder::new(
+        BufWriter::new(File::create(to_file)?),
+        image.width(),
+        image.height(),
+    );
+    encoder.set_color(color);
+    encoder.set_depth(depth);
+    for (keyword, value) in text {
+        encoder.add_itxt_chunk(keyword.to_string(), value.to_string())?;
+    }
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Reads the keyword/text pairs of the text chunks preceding the image data
+pub fn read_text(file_path: &str) -> Result<Vec<(String, String)>> {
+    error::check_file_exists(file_path)?;
+    let reader = Decoder::new(File::open(file_path)?).read_info()?;
+    let info = reader.info();
+
+    let mut text: Vec<(String, String)> = info
+        .uncompressed_latin1_text
+        .iter()
+        .map(|t| (t.keyword.clone(), t.text.clone()))
+        .collect();
+    for t in info.compressed_latin1_text.iter() {
+        text.push((t.keyword.clone(), t.get_text()?));
+    }
+    for t in info.utf8_text.iter() {
+        text.push((t.keyword.clone(), t.get_text()?));
+    }
+    Ok(text)
+}
+
+/// Reads the value of a single text chunk, if present
+pub fn read_text_value(file_path: &str, keyword: &str) -> Result<Option<String>> {
+    Ok(read_text(file_path)?
+        .into_iter()
+        .find(|(k, _)| k == keyword)
+        .map(|(_, v)| v))
+}
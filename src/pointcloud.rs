@@ -0,0 +1,491 @@
+/*
+    Per-pixel XYZ point clouds and mesh export.
+
+    XYZ images hold the X, Y and Z coordinates of the surface seen by each pixel as three
+    bands, in the frame of the camera model, in the manner of the PDS XYZ RDR products. Pixels
+    without a valid point are masked out in the alpha band and left at (0, 0, 0).
+
+    Meshes are built by connecting each 2x2 block of valid neighboring pixels into two triangles,
+    and can be written as ASCII PLY or as OBJ with vertex colors taken from a co-registered image.
+
+    Derived products (range, surface normals, slope and roughness) are computed from the valid
+    pixels of an XYZ image, with pixels for which they can't be determined masked out.
+*/
+
+use crate::{
+    camera::model::{CameraModel, ImageCoordinate},
+    enums::ImageMode,
+    error,
+    image::Image,
+    imagebuffer::ImageBuffer,
+    stereo,
+    vector::Vector,
+};
+
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+fn new_xyz_image(width: usize, height: usize, camera_model: &CameraModel) -> Result<Image> {
+    let mut xyz = Image::new_with_bands_masked(width, height, 3, ImageMode::U16BIT, false)?;
+    xyz.get_metadata_mut().camera_model = Some(camera_model.clone());
+    Ok(xyz)
+}
+
+// Point of a pixel of an XYZ image, if valid
+fn xyz_point(xyz: &Image, x: usize, y: usize) -> Option<Vector> {
+    if xyz.get_alpha_at(x, y) {
+        Some(Vector::new(
+            xyz.get_band(0).get(x, y) as f64,
+            xyz.get_band(1).get(x, y) as f64,
+            xyz.get_band(2).get(x, y) as f64,
+        ))
+    } else {
+        None
+    }
+}
+
+fn check_xyz_bands(xyz: &Image) -> Result<()> {
+    if xyz.num_bands() != 3 {
+        Err(anyhow!(
+            "XYZ image requires 3 bands, found {}",
+            xyz.num_bands()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn put_xyz(xyz: &mut Image, x: usize, y: usize, point: &Vector) {
+    xyz.put(x, y, point.x as f32, 0);
+    xyz.put(x, y, point.y as f32, 1);
+    xyz.put(x, y, point.z as f32, 2);
+    xyz.put_alpha(x, y, true);
+}
+
+/// Computes an XYZ image from a range image, with each range measured from the camera center
+/// along the look vector of its pixel. Masked out pixels, and those with a range that is not
+/// positive, are left invalid.
+pub fn xyz_from_range(range: &ImageBuffer, camera_model: &CameraModel) -> Result<Image> {
+    if !camera_model.is_valid() {
+        return Err(anyhow!("Cannot compute XYZ with an invalid camera model"));
+    }
+
+    let mut xyz = new_xyz_image(range.width, range.height, camera_model)?;
+    for y in 0..range.height {
+        for x in 0..range.width {
+            let r = range.get(x, y) as f64;
+            if !range.get_mask_at_point(x, y) || !r.is_finite() || r <= 0.0 {
+                continue;
+            }
+            if let Ok(lv) = camera_model.ls_to_look_vector(&ImageCoordinate {
+                line: y as f64,
+                sample: x as f64,
+            }) {
+                put_xyz(&mut xyz, x, y, &lv.intersect_to_sphere(r));
+            }
+        }
+    }
+    Ok(xyz)
+}
+
+/// Computes an XYZ image by triangulating a disparity map relative to the left image of an
+/// epipolar-aligned stereo pair, such as those produced by `stereo::rectify`. Pixels which are
+/// masked out of the disparity map or fail to triangulate are left invalid.
+pub fn xyz_from_disparity(
+    disparity: &ImageBuffer,
+    left_model: &CameraModel,
+    right_model: &CameraModel,
+) -> Result<Image> {
+    if !left_model.is_valid() || !right_model.is_valid() {
+        return Err(anyhow!("Cannot compute XYZ with an invalid camera model"));
+    }
+
+    let mut xyz = new_xyz_image(disparity.width, disparity.height, left_model)?;
+    for y in 0..disparity.height {
+        for x in 0..disparity.width {
+            if !disparity.get_mask_at_point(x, y) {
+                continue;
+            }
+            let left = ImageCoordinate {
+                line: y as f64,
+                sample: x as f64,
+            };
+            let right = ImageCoordinate {
+                line: y as f64,
+                sample: x as f64 - disparity.get(x, y) as f64,
+            };
+            if let Ok(t) = stereo::triangulate(left_model, &left, right_model, &right) {
+                put_xyz(&mut xyz, x, y, &t.xyz);
+            }
+        }
+    }
+    Ok(xyz)
+}
+
+/// A triangle mesh with optional per-vertex colors
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vector>,
+
+    // Either empty, or one color per vertex
+    pub colors: Vec<[u8; 3]>,
+
+    // Vertex indices of each triangle
+    pub faces: Vec<[usize; 3]>,
+}
+
+// Vertex coordinates as written to file. XYZ images hold single precision values, so vertices
+// are written in single precision as well.
+fn vertex_coordinates(v: &Vector) -> (f32, f32, f32) {
+    (v.x as f32, v.y as f32, v.z as f32)
+}
+
+// 8-bit color of a pixel, with single band images rendered in grayscale
+fn pixel_color(image: &Image, x: usize, y: usize) -> [u8; 3] {
+    let max = ImageMode::maxvalue(image.get_mode());
+    let value = |band: usize| -> u8 {
+        let band = band.min(image.num_bands() - 1);
+        (image.get_band(band).get(x, y) / max * 255.0)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    };
+    [value(0), value(1), value(2)]
+}
+
+impl Mesh {
+    /// Builds a mesh from an XYZ image, with vertex colors from a co-registered image if
+    /// supplied. Triangles with an edge longer than `max_edge_length` are omitted, to avoid
+    /// bridging range discontinuities.
+    pub fn from_xyz(
+        xyz: &Image,
+        colors: Option<&Image>,
+        max_edge_length: Option<f64>,
+    ) -> Result<Mesh> {
+        check_xyz_bands(xyz)?;
+        if let Some(c) = colors {
+            if c.width != xyz.width || c.height != xyz.height || c.num_bands() == 0 {
+                return Err(anyhow!("Color image does not match the XYZ image"));
+            }
+        }
+
+        let mut mesh = Mesh::default();
+        let mut indices: Vec<Option<usize>> = vec![None; xyz.width * xyz.height];
+        for y in 0..xyz.height {
+            for x in 0..xyz.width {
+                if let Some(p) = xyz_point(xyz, x, y) {
+                    indices[y * xyz.width + x] = Some(mesh.vertices.len());
+                    mesh.vertices.push(p);
+                    if let Some(c) = colors {
+                        mesh.colors.push(pixel_color(c, x, y));
+                    }
+                }
+            }
+        }
+
+        let index = |x: usize, y: usize| indices[y * xyz.width + x];
+        for y in 0..xyz.height.saturating_sub(1) {
+            for x in 0..xyz.width.saturating_sub(1) {
+                let tl = index(x, y);
+                let tr = index(x + 1, y);
+                let bl = index(x, y + 1);
+                let br = index(x + 1, y + 1);
+                for triangle in [[tl, bl, tr], [tr, bl, br]] {
+                    if let [Some(a), Some(b), Some(c)] = triangle {
+                        mesh.add_face([a, b, c], max_edge_length);
+                    }
+                }
+            }
+        }
+
+        Ok(mesh)
+    }
+
+    fn add_face(&mut self, face: [usize; 3], max_edge_length: Option<f64>) {
+        if let Some(max) = max_edge_length {
+            let v = |i: usize| &self.vertices[face[i]];
+            if v(0).distance_to(v(1)) > max
+                || v(1).distance_to(v(2)) > max
+                || v(2).distance_to(v(0)) > max
+            {
+                return;
+            }
+        }
+        self.faces.push(face);
+    }
+
+    pub fn has_colors(&self) -> bool {
+        !self.colors.is_empty()
+    }
+
+    /// Writes the mesh as an ASCII PLY file
+    pub fn save_ply(&self, to_file: &str) -> Result<()> {
+        error::check_parent_writable(to_file)?;
+        let mut writer = BufWriter::new(File::create(to_file)?);
+
+        writeln!(writer, "ply")?;
+        writeln!(writer, "format ascii 1.0")?;
+        writeln!(writer, "comment Generated by sciimg")?;
+        writeln!(writer, "element vertex {}", self.vertices.len())?;
+        for axis in ["x", "y", "z"] {
+            writeln!(writer, "property float {}", axis)?;
+        }
+        if self.has_colors() {
+            for channel in ["red", "green", "blue"] {
+                writeln!(writer, "property uchar {}", channel)?;
+            }
+        }
+        writeln!(writer, "element face {}", self.faces.len())?;
+        writeln!(writer, "property list uchar int vertex_indices")?;
+        writeln!(writer, "end_header")?;
+
+        for (i, v) in self.vertices.iter().enumerate() {
+            let (x, y, z) = vertex_coordinates(v);
+            if self.has_colors() {
+                let [r, g, b] = self.colors[i];
+                writeln!(writer, "{} {} {} {} {} {}", x, y, z, r, g, b)?;
+            } else {
+                writeln!(writer, "{} {} {}", x, y, z)?;
+            }
+        }
+        for [a, b, c] in self.faces.iter() {
+            writeln!(writer, "3 {} {} {}", a, b, c)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the mesh as an OBJ file. Vertex colors are written as the widely supported
+    /// extension of appending RGB values between 0 and 1 to each vertex.
+    pub fn save_obj(&self, to_file: &str) -> Result<()> {
+        error::check_parent_writable(to_file)?;
+        let mut writer = BufWriter::new(File::create(to_file)?);
+
+        writeln!(writer, "# Generated by sciimg")?;
+        for (i, v) in self.vertices.iter().enumerate() {
+            let (x, y, z) = vertex_coordinates(v);
+            if self.has_colors() {
+                let [r, g, b] = self.colors[i].map(|c| c as f64 / 255.0);
+                writeln!(writer, "v {} {} {} {} {} {}", x, y, z, r, g, b)?;
+            } else {
+                writeln!(writer, "v {} {} {}", x, y, z)?;
+            }
+        }
+
+        // OBJ vertex indices start at 1
+        for [a, b, c] in self.faces.iter() {
+            writeln!(writer, "f {} {} {}", a + 1, b + 1, c + 1)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+// Sets a value of a masked output buffer, marking it valid. Masked values can't be written, so
+// the mask is set first.
+fn put_valid(buffer: &mut ImageBuffer, x: usize, y: usize, value: f32) {
+    buffer.put_mask(x, y, true);
+    buffer.put(x, y, value);
+}
+
+/// Computes the distance of each valid point of an XYZ image from the camera center
+pub fn range_map(xyz: &Image, camera_model: &CameraModel) -> Result<ImageBuffer> {
+    check_xyz_bands(xyz)?;
+    let c = camera_model.c();
+
+    let mut range = ImageBuffer::new_with_mask_as(xyz.width, xyz.height, false)?;
+    for y in 0..xyz.height {
+        for x in 0..xyz.width {
+            if let Some(p) = xyz_point(xyz, x, y) {
+                put_valid(&mut range, x, y, p.distance_to(&c) as f32);
+            }
+        }
+    }
+    Ok(range)
+}
+
+// Neighbor of a pixel along one axis, preferring the next pixel and falling back to the
+// previous one at the image edge or where the next pixel is invalid
+fn neighbor(xyz: &Image, x: usize, y: usize, dx: usize, dy: usize) -> Option<Vector> {
+    let next = if x + dx < xyz.width && y + dy < xyz.height {
+        xyz_point(xyz, x + dx, y + dy)
+    } else {
+        None
+    };
+    next.or_else(|| {
+        if x >= dx && y >= dy {
+            xyz_point(xyz, x - dx, y - dy)
+        } else {
+            None
+        }
+    })
+}
+
+/// Computes unit surface normals of an XYZ image from each point and its horizontal and
+/// vertical neighbors, oriented towards the camera. The result is a 3-band image of the X, Y
+/// and Z components of the normals, with each band masked where the normal can't be determined.
+pub fn surface_normals(xyz: &Image, camera_model: &CameraModel) -> Result<Image> {
+    check_xyz_bands(xyz)?;
+    let c = camera_model.c();
+
+    let mut normals =
+        Image::new_with_bands_masked(xyz.width, xyz.height, 3, ImageMode::U16BIT, false)?;
+    let mut bands: Vec<ImageBuffer> = (0..3)
+        .map(|_| ImageBuffer::new_with_mask_as(xyz.width, xyz.height, false))
+        .collect::<Result<_>>()?;
+
+    for y in 0..xyz.height {
+        for x in 0..xyz.width {
+            let (p, px, py) = match (
+                xyz_point(xyz, x, y),
+                neighbor(xyz, x, y, 1, 0),
+                neighbor(xyz, x, y, 0, 1),
+            ) {
+                (Some(p), Some(px), Some(py)) => (p, px, py),
+                _ => continue,
+            };
+
+            let mut n = Vector::normal_3pt(&px, &p, &py);
+            if n.len() == 0.0 || !n.x.is_finite() || !n.y.is_finite() || !n.z.is_finite() {
+                continue;
+            }
+            if n.dot_product(&c.subtract(&p)) < 0.0 {
+                n = n.inversed();
+            }
+
+            put_valid(&mut bands[0], x, y, n.x as f32);
+            put_valid(&mut bands[1], x, y, n.y as f32);
+            put_valid(&mut bands[2], x, y, n.z as f32);
+            normals.put_alpha(x, y, true);
+        }
+    }
+
+    for (b, band) in bands.iter().enumerate() {
+        normals.set_band(band, b);
+    }
+    normals.get_metadata_mut().camera_model = Some(camera_model.clone());
+    Ok(normals)
+}
+
+/// Computes the slope of the surface, in degrees, from surface normals as produced by
+/// `surface_normals`. Slope is measured from the plane perpendicular to the gravity vector,
+/// such that level ground has a slope of zero.
+pub fn slope_map(normals: &Image, gravity: &Vector) -> Result<ImageBuffer> {
+    check_xyz_bands(normals)?;
+    if gravity.len() == 0.0 {
+        return Err(anyhow!("Gravity vector cannot be zero"));
+    }
+    let up = gravity.inversed().normalized();
+
+    let mut slope = ImageBuffer::new_with_mask_as(normals.width, normals.height, false)?;
+    for y in 0..normals.height {
+        for x in 0..normals.width {
+            if let Some(n) = xyz_point(normals, x, y) {
+                let cos = n.normalized().dot_product(&up).clamp(-1.0, 1.0);
+                put_valid(&mut slope, x, y, cos.acos().to_degrees() as f32);
+            }
+        }
+    }
+    Ok(slope)
+}
+
+/// Computes surface roughness as the RMS deviation, along the gravity vector, of the points
+/// within a `window_size` x `window_size` window from their least squares plane. Pixels whose
+/// window holds fewer than four valid points are masked out.
+pub fn roughness_map(xyz: &Image, gravity: &Vector, window_size: usize) -> Result<ImageBuffer> {
+    check_xyz_bands(xyz)?;
+    if gravity.len() == 0.0 {
+        return Err(anyhow!("Gravity vector cannot be zero"));
+    }
+    if window_size < 3 || window_size % 2 != 1 {
+        return Err(anyhow!(
+            "Roughness window size must be odd and at least 3: {}",
+            window_size
+        ));
+    }
+
+    // Local frame with heights measured upwards, against gravity
+    let up = gravity.inversed().normalized();
+    let mut u = up.cross_product(&Vector::x_axis_vector());
+    if u.len() < 1.0e-6 {
+        u = up.cross_product(&Vector::y_axis_vector());
+    }
+    let u = u.normalized();
+    let v = up.cross_product(&u);
+
+    let half = window_size / 2;
+    let mut roughness = ImageBuffer::new_with_mask_as(xyz.width, xyz.height, false)?;
+    for y in 0..xyz.height {
+        for x in 0..xyz.width {
+            let center = match xyz_point(xyz, x, y) {
+                Some(p) => p,
+                None => continue,
+            };
+
+            // Coordinates relative to the center point, for numerical stability
+            let mut points: Vec<(f64, f64, f64)> = vec![];
+            for wy in y.saturating_sub(half)..(y + half + 1).min(xyz.height) {
+                for wx in x.saturating_sub(half)..(x + half + 1).min(xyz.width) {
+                    if let Some(p) = xyz_point(xyz, wx, wy) {
+                        let d = p.subtract(&center);
+                        points.push((d.dot_product(&u), d.dot_product(&v), d.dot_product(&up)));
+                    }
+                }
+            }
+
+            if let Some(rms) = plane_fit_rms(&points) {
+                put_valid(&mut roughness, x, y, rms as f32);
+            }
+        }
+    }
+    Ok(roughness)
+}
+
+// RMS residual of the least squares fit of h = a * u + b * v + c through the points, or None
+// if there are too few points or they don't constrain the plane
+fn plane_fit_rms(points: &[(f64, f64, f64)]) -> Option<f64> {
+    if points.len() < 4 {
+        return None;
+    }
+
+    let n = points.len() as f64;
+    let (mut su, mut sv, mut sh) = (0.0, 0.0, 0.0);
+    let (mut suu, mut svv, mut suv, mut suh, mut svh) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for (u, v, h) in points.iter() {
+        su += u;
+        sv += v;
+        sh += h;
+        suu += u * u;
+        svv += v * v;
+        suv += u * v;
+        suh += u * h;
+        svh += v * h;
+    }
+
+    // Normal equations, solved by Cramer's rule
+    let m = [[suu, suv, su], [suv, svv, sv], [su, sv, n]];
+    let rhs = [suh, svh, sh];
+    let det3 = |m: &[[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let det = det3(&m);
+    if det.abs() < 1.0e-12 {
+        return None;
+    }
+    let solve = |col: usize| {
+        let mut mc = m;
+        for (row, value) in rhs.iter().enumerate() {
+            mc[row][col] = *value;
+        }
+        det3(&mc) / det
+    };
+    let (a, b, c) = (solve(0), solve(1), solve(2));
+
+    let sum_sq: f64 = points
+        .iter()
+        .map(|(u, v, h)| (h - (a * u + b * v + c)).powi(2))
+        .sum();
+    Some((sum_sq / n).sqrt())
+}
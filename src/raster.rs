@@ -0,0 +1,322 @@
+use crate::{
+    binfilereader::{BinFileReader, Endian},
+    enums::ImageMode,
+    image::Image,
+    imagebuffer::ImageBuffer,
+    Dn,
+};
+
+use anyhow::{anyhow, Result};
+
+/// Primitive storage type of a single sample in a raw raster
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SampleType {
+    UnsignedInt8,
+    SignedInt8,
+    UnsignedInt16,
+    SignedInt16,
+    UnsignedInt32,
+    SignedInt32,
+    Float32,
+    Float64,
+}
+
+impl SampleType {
+    /// Number of bytes occupied by a single sample
+    pub fn bytes(self) -> usize {
+        match self {
+            SampleType::UnsignedInt8 | SampleType::SignedInt8 => 1,
+            SampleType::UnsignedInt16 | SampleType::SignedInt16 => 2,
+            SampleType::UnsignedInt32 | SampleType::SignedInt32 | SampleType::Float32 => 4,
+            SampleType::Float64 => 8,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, SampleType::Float32 | SampleType::Float64)
+    }
+
+    /// Best-fit image mode for a sample type. Wider types are carried as 16 bit
+    /// since the buffers themselves are floating point.
+    pub fn image_mode(self) -> ImageMode {
+        match self {
+            SampleType::UnsignedInt8 | SampleType::SignedInt8 => ImageMode::U8BIT,
+            _ => ImageMode::U16BIT,
+        }
+    }
+}
+
+/// Organization of bands within a raw raster
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Interleave {
+    // Band sequential (BSQ)
+    BandSequential,
+
+    // Band interleaved by line (BIL)
+    LineInterleaved,
+
+    // Band interleaved by pixel (BIP)
+    SampleInterleaved,
+}
+
+/// Describes where and how a raster is stored within a file.
+#[derive(Debug, Clone)]
+pub struct RasterLayout {
+    pub width: usize,
+    pub height: usize,
+    pub bands: usize,
+    pub sample_type: SampleType,
+    pub endian: Endian,
+    pub interleave: Interleave,
+
+    // Byte offset of the first record of the raster within the file
+    pub offset: usize,
+
+    // Bytes preceding/following the pixel data of each record
+    pub line_prefix_bytes: usize,
+    pub line_suffix_bytes: usize,
+
+    // Applied to each raw value as (value * scaling_factor) + value_offset
+    pub scaling_factor: Dn,
+    pub value_offset: Dn,
+}
+
+impl RasterLayout {
+    pub fn new(
+        width: usize,
+        height: usize,
+        bands: usize,
+        sample_type: SampleType,
+        endian: Endian,
+        interleave: Interleave,
+    ) -> RasterLayout {
+        RasterLayout {
+            width,
+            height,
+            bands,
+            sample_type,
+            endian,
+            interleave,
+            offset: 0,
+            line_prefix_bytes: 0,
+            line_suffix_bytes: 0,
+            scaling_factor: 1.0,
+            value_offset: 0.0,
+        }
+    }
+
+    /// Length, in bytes, of a single record (line) including prefix and suffix bytes
+    pub fn record_bytes(&self) -> usize {
+        let samples_per_record = match self.interleave {
+            Interleave::SampleInterleaved => self.width * self.bands,
+            _ => self.width,
+        };
+        self.line_prefix_bytes
+            + samples_per_record * self.sample_type.bytes()
+            + self.line_suffix_bytes
+    }
+
+    /// Total number of records in the raster
+    pub fn num_records(&self) -> usize {
+        match self.interleave {
+            Interleave::SampleInterleaved => self.height,
+            _ => self.height * self.bands,
+        }
+    }
+
+    /// Total number of bytes spanned by the raster, excluding the leading offset
+    pub fn raster_bytes(&self) -> usize {
+        self.record_bytes() * self.num_records()
+    }
+
+    /// Byte position just past the end of the raster, or None if it overflows, as can happen with
+    /// corrupt or malicious dimensions in a label
+    pub fn checked_end(&self) -> Option<usize> {
+        let samples_per_record = match self.interleave {
+            Interleave::SampleInterleaved => self.width.checked_mul(self.bands)?,
+            _ => self.width,
+        };
+        let num_records = match self.interleave {
+            Interleave::SampleInterleaved => self.height,
+            _ => self.height.checked_mul(self.bands)?,
+        };
+        let record_bytes = samples_per_record
+            .checked_mul(self.sample_type.bytes())?
+            .checked_add(self.line_prefix_bytes)?
+            .checked_add(self.line_suffix_bytes)?;
+        record_bytes
+            .checked_mul(num_records)?
+            .checked_add(self.offset)
+    }
+
+    /// Byte position of the sample at the specified band, line and sample
+    pub fn position_of(&self, band: usize, line: usize, sample: usize) -> usize {
+        let bytes = self.sample_type.bytes();
+        match self.interleave {
+            Interleave::BandSequential => {
+                self.offset
+                    + (band * self.height + line) * self.record_bytes()
+                    + self.line_prefix_bytes
+                    + sample * bytes
+            }
+            Interleave::LineInterleaved => {
+                self.offset
+                    + (line * self.bands + band) * self.record_bytes()
+                    + self.line_prefix_bytes
+                    + sample * bytes
+            }
+            Interleave::SampleInterleaved => {
+                self.offset
+                    + line * self.record_bytes()
+                    + self.line_prefix_bytes
+                    + (sample * self.bands + band) * bytes
+            }
+        }
+    }
+}
+
+pub fn read_sample(
+    reader: &BinFileReader,
+    start: usize,
+    sample_type: SampleType,
+    endian: Endian,
+) -> Dn {
+    match sample_type {
+        SampleType::UnsignedInt8 => reader.read_u8_with_endiness(start, endian) as Dn,
+        SampleType::SignedInt8 => reader.read_i8_with_endiness(start, endian) as Dn,
+        SampleType::UnsignedInt16 => reader.read_u16_with_endiness(start, endian) as Dn,
+        SampleType::SignedInt16 => reader.read_i16_with_endiness(start, endian) as Dn,
+        SampleType::UnsignedInt32 => reader.read_u32_with_endiness(start, endian) as Dn,
+        SampleType::SignedInt32 => reader.read_i32_with_endiness(start, endian) as Dn,
+        SampleType::Float32 => reader.read_f32_with_endiness(start, endian),
+        SampleType::Float64 => reader.read_f64_with_endiness(start, endian) as Dn,
+    }
+}
+
+/// Reads a single band of the raster into an image buffer
+pub fn read_band(
+    reader: &BinFileReader,
+    layout: &RasterLayout,
+    band: usize,
+) -> Result<ImageBuffer> {
+    if band >= layout.bands {
+        return Err(anyhow!("Band index out of bounds: {}", band));
+    }
+
+    let end = layout
+        .checked_end()
+        .ok_or_else(|| anyhow!("Raster dimensions are too large"))?;
+    if end > reader.len() {
+        return Err(anyhow!(
+            "Raster exceeds file length: Need {} bytes, file contains {}",
+            end,
+            reader.len()
+        ));
+    }
+
+    let mut buffer =
+        ImageBuffer::new_as_mode(layout.width, layout.height, layout.sample_type.image_mode())?;
+
+    for y in 0..layout.height {
+        for x in 0..layout.width {
+            let raw = read_sample(
+                reader,
+                layout.position_of(band, y, x),
+                layout.sample_type,
+                layout.endian,
+            );
+            buffer.put(x, y, raw * layout.scaling_factor + layout.value_offset);
+        }
+    }
+
+    Ok(buffer)
+}
+
+/// Reads all bands of the raster into a new image of the specified mode
+pub fn read_image(reader: &BinFileReader, layout: &RasterLayout, mode: ImageMode) -> Result<Image> {
+    let mut image = Image::new(layout.width, layout.height, mode)?;
+    for b in 0..layout.bands {
+        let mut band = read_band(reader, layout, b)?;
+        band.mode = mode;
+        image.push_band(&band);
+    }
+    Ok(image)
+}
+
+// Integer sample types are rounded and clamped to their representable range.
+pub fn encode_sample(out: &mut Vec<u8>, value: Dn, sample_type: SampleType, endian: Endian) {
+    macro_rules! encode_as {
+        ($type:ident, $v:expr) => {
+            out.extend_from_slice(&match endian {
+                Endian::BigEndian => $v.to_be_bytes(),
+                Endian::LittleEndian => $v.to_le_bytes(),
+                Endian::NativeEndian => $v.to_ne_bytes(),
+            })
+        };
+    }
+
+    macro_rules! encode_int {
+        ($type:ident) => {{
+            let v = (value as f64)
+                .round()
+                .clamp($type::MIN as f64, $type::MAX as f64) as $type;
+            encode_as!($type, v)
+        }};
+    }
+
+    match sample_type {
+        SampleType::UnsignedInt8 => encode_int!(u8),
+        SampleType::SignedInt8 => encode_int!(i8),
+        SampleType::UnsignedInt16 => encode_int!(u16),
+        SampleType::SignedInt16 => encode_int!(i16),
+        SampleType::UnsignedInt32 => encode_int!(u32),
+        SampleType::SignedInt32 => encode_int!(i32),
+        SampleType::Float32 => encode_as!(f32, value),
+        SampleType::Float64 => encode_as!(f64, value as f64),
+    }
+}
+
+/// Encodes the bands of an image into a raw raster as described by the layout. The layout
+/// offset is not included, and prefix/suffix bytes are zero filled. Scaling is inverted such
+/// that reading the raster back with the same layout returns the original values.
+pub fn encode_image(image: &Image, layout: &RasterLayout) -> Result<Vec<u8>> {
+    if image.num_bands() != layout.bands {
+        return Err(anyhow!(
+            "Layout specifies {} bands, image has {}",
+            layout.bands,
+            image.num_bands()
+        ));
+    }
+    if image.width != layout.width || image.height != layout.height {
+        return Err(anyhow!("Layout dimensions do not match image dimensions"));
+    }
+
+    let mut out: Vec<u8> = Vec::with_capacity(layout.raster_bytes());
+
+    let encode_value = |out: &mut Vec<u8>, band: usize, x: usize, y: usize| {
+        let value = (image.get_band(band).get(x, y) - layout.value_offset) / layout.scaling_factor;
+        encode_sample(out, value, layout.sample_type, layout.endian);
+    };
+
+    for record in 0..layout.num_records() {
+        out.resize(out.len() + layout.line_prefix_bytes, 0);
+        match layout.interleave {
+            Interleave::BandSequential => {
+                let (band, y) = (record / layout.height, record % layout.height);
+                (0..layout.width).for_each(|x| encode_value(&mut out, band, x, y));
+            }
+            Interleave::LineInterleaved => {
+                let (y, band) = (record / layout.bands, record % layout.bands);
+                (0..layout.width).for_each(|x| encode_value(&mut out, band, x, y));
+            }
+            Interleave::SampleInterleaved => {
+                for x in 0..layout.width {
+                    (0..layout.bands).for_each(|band| encode_value(&mut out, band, x, record));
+                }
+            }
+        }
+        out.resize(out.len() + layout.line_suffix_bytes, 0);
+    }
+
+    Ok(out)
+}
@@ -0,0 +1,283 @@
+/*
+    Image-to-image reprojection between camera models.
+
+    Each pixel of the target image is cast as a ray through the target camera model, and the
+    point where it lands is projected back into the source camera model to find the source
+    pixel to sample. Points are either taken at infinity, which suits warping a fisheye into
+    its linearized model or into the frame of a neighboring camera, or on a sphere of fixed
+    radius around the target camera. Target pixels which fall outside the source image, or
+    behind the source camera, are left transparent in the alpha mask.
+*/
+
+use crate::{
+    camera::model::{CameraModel, ImageCoordinate, ModelType},
+    image::Image,
+    imagebuffer::ImageBuffer,
+    metadata::ProcessingStep,
+    vector::Vector,
+};
+
+use anyhow::{anyhow, Result};
+
+// Distance at which points are placed to approximate infinity for models whose projection
+// doesn't support points at infinity (CAHVORE).
+static INFINITY_RANGE: f64 = 1.0e8;
+
+// Tolerance, in pixels, on locations along the image edges to absorb round-off in projection
+static EDGE_TOLERANCE: f64 = 1.0e-6;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Interpolation {
+    Bilinear,
+    Bicubic,
+}
+
+impl Interpolation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Interpolation::Bilinear => "bilinear",
+            Interpolation::Bicubic => "bicubic",
+        }
+    }
+}
+
+#[inline(always)]
+fn get_clamped(buffer: &ImageBuffer, x: isize, y: isize) -> f32 {
+    buffer.get(
+        x.clamp(0, buffer.width as isize - 1) as usize,
+        y.clamp(0, buffer.height as isize - 1) as usize,
+    )
+}
+
+fn in_bounds(buffer: &ImageBuffer, x: f64, y: f64) -> bool {
+    x >= -EDGE_TOLERANCE
+        && y >= -EDGE_TOLERANCE
+        && x <= (buffer.width - 1) as f64 + EDGE_TOLERANCE
+        && y <= (buffer.height - 1) as f64 + EDGE_TOLERANCE
+}
+
+/// Samples a buffer at a fractional pixel location by bilinear interpolation. Returns None if
+/// the location lies outside the buffer.
+pub fn sample_bilinear(buffer: &ImageBuffer, x: f64, y: f64) -> Option<f32> {
+    if !in_bounds(buffer, x, y) {
+        return None;
+    }
+
+    let xf = x.floor();
+    let yf = y.floor();
+    let xd = (x - xf) as f32;
+    let yd = (y - yf) as f32;
+    let (xi, yi) = (xf as isize, yf as isize);
+
+    let v00 = get_clamped(buffer, xi, yi);
+    let v01 = get_clamped(buffer, xi + 1, yi);
+    let v10 = get_clamped(buffer, xi, yi + 1);
+    let v11 = get_clamped(buffer, xi + 1, yi + 1);
+
+    let v0 = v00 * (1.0 - xd) + v01 * xd;
+    let v1 = v10 * (1.0 - xd) + v11 * xd;
+    Some(v0 * (1.0 - yd) + v1 * yd)
+}
+
+// Cubic convolution kernel weights (Keys, a = -0.5) for the four samples surrounding a
+// location at fractional offset t from the second sample.
+fn cubic_weights(t: f32) -> [f32; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        -0.5 * t3 + t2 - 0.5 * t,
+        1.5 * t3 - 2.5 * t2 + 1.0,
+        -1.5 * t3 + 2.0 * t2 + 0.5 * t,
+        0.5 * t3 - 0.5 * t2,
+    ]
+}
+
+/// Samples a buffer at a fractional pixel location by bicubic convolution, replicating edge
+/// pixels where the kernel extends past the buffer. Returns None if the location lies outside
+/// the buffer.
+pub fn sample_bicubic(buffer: &ImageBuffer, x: f64, y: f64) -> Option<f32> {
+    if !in_bounds(buffer, x, y) {
+        return None;
+    }
+
+    let xf = x.floor();
+    let yf = y.floor();
+    let wx = cubic_weights((x - xf) as f32);
+    let wy = cubic_weights((y - yf) as f32);
+    let (xi, yi) = (xf as isize, yf as isize);
+
+    let mut v = 0.0;
+    for (j, wy) in wy.iter().enumerate() {
+        let mut row = 0.0;
+        for (i, wx) in wx.iter().enumerate() {
+            row += wx * get_clamped(buffer, xi + i as isize - 1, yi + j as isize - 1);
+        }
+        v += wy * row;
+    }
+    Some(v)
+}
+
+/// Samples a buffer at a fractional pixel location using the specified interpolation
+pub fn sample(buffer: &ImageBuffer, x: f64, y: f64, interpolation: Interpolation) -> Option<f32> {
+    match interpolation {
+        Interpolation::Bilinear => sample_bilinear(buffer, x, y),
+        Interpolation::Bicubic => sample_bicubic(buffer, x, y),
+    }
+}
+
+// Projects a ray from the target camera into the source camera. CAHVORE models aren't
+// limited to a forward field of view, so only CAHV and CAHVOR reject points behind the camera.
+fn project_to_source(
+    source_model: &CameraModel,
+    origin: &Vector,
+    look_direction: &Vector,
+    projection_range: Option<f64>,
+) -> Option<ImageCoordinate> {
+    let is_cahvore = source_model.model_type() == ModelType::CAHVORE;
+
+    let point = match projection_range {
+        Some(range) => origin.add(&look_direction.normalized().scale(range)),
+        None if is_cahvore => source_model
+            .c()
+            .add(&look_direction.normalized().scale(INFINITY_RANGE)),
+        None => {
+            if look_direction.dot_product(&source_model.a()) <= 0.0 {
+                return None;
+            }
+            return Some(source_model.xyz_to_ls(look_direction, true));
+        }
+    };
+
+    if !is_cahvore
+        && point
+            .subtract(&source_model.c())
+            .dot_product(&source_model.a())
+            <= 0.0
+    {
+        return None;
+    }
+    Some(source_model.xyz_to_ls(&point, false))
+}
+
+// Projects a ray into a source image, returning the source pixel location if it lands within
+// the image on a pixel which isn't transparent.
+pub(crate) fn source_coordinate(
+    source: &Image,
+    source_model: &CameraModel,
+    origin: &Vector,
+    look_direction: &Vector,
+    projection_range: Option<f64>,
+) -> Option<ImageCoordinate> {
+    let ls = project_to_source(source_model, origin, look_direction, projection_range)?;
+
+    if !ls.sample.is_finite()
+        || !ls.line.is_finite()
+        || !in_bounds(source.get_band(0), ls.sample, ls.line)
+        || !source.get_alpha_at(
+            (ls.sample.round().max(0.0) as usize).min(source.width - 1),
+            (ls.line.round().max(0.0) as usize).min(source.height - 1),
+        )
+    {
+        return None;
+    }
+    Some(ls)
+}
+
+/// Reprojects an image from its camera model into a target camera model and size.
+///
+/// Points are projected at infinity when `projection_range` is None, otherwise onto a sphere of
+/// that radius centered on the target camera. Pixels of the result that map outside the source
+/// image, or onto transparent source pixels, are masked out in the alpha band. The result
+/// carries the source metadata with the camera model replaced by the target model.
+pub fn reproject(
+    source: &Image,
+    source_model: &CameraModel,
+    target_model: &CameraModel,
+    target_width: usize,
+    target_height: usize,
+    interpolation: Interpolation,
+    projection_range: Option<f64>,
+) -> Result<Image> {
+    let mut target = resample(
+        source,
+        source_model,
+        target_model,
+        target_width,
+        target_height,
+        interpolation,
+        projection_range,
+    )?;
+    target.get_metadata_mut().add_history(
+        ProcessingStep::new("reproject")
+            .with_parameter("interpolation", interpolation.name())
+            .with_parameter("width", target_width)
+            .with_parameter("height", target_height)
+            .with_parameter(
+                "range",
+                projection_range.map_or(String::from("infinity"), |r| r.to_string()),
+            ),
+    );
+    Ok(target)
+}
+
+// Reprojection without recording a processing step, for operations which record their own
+pub(crate) fn resample(
+    source: &Image,
+    source_model: &CameraModel,
+    target_model: &CameraModel,
+    target_width: usize,
+    target_height: usize,
+    interpolation: Interpolation,
+    projection_range: Option<f64>,
+) -> Result<Image> {
+    if !source_model.is_valid() || !target_model.is_valid() {
+        return Err(anyhow!("Cannot reproject with an invalid camera model"));
+    }
+    if source.is_empty() || source.width == 0 || source.height == 0 {
+        return Err(anyhow!("Cannot reproject an empty image"));
+    }
+
+    let mut target = Image::new_with_bands_masked(
+        target_width,
+        target_height,
+        source.num_bands(),
+        source.get_mode(),
+        false,
+    )?;
+
+    for y in 0..target_height {
+        for x in 0..target_width {
+            let lv = match target_model.ls_to_look_vector(&ImageCoordinate {
+                line: y as f64,
+                sample: x as f64,
+            }) {
+                Ok(lv) => lv,
+                Err(_) => continue,
+            };
+
+            let ls = match source_coordinate(
+                source,
+                source_model,
+                &lv.origin,
+                &lv.look_direction,
+                projection_range,
+            ) {
+                Some(ls) => ls,
+                None => continue,
+            };
+
+            for b in 0..source.num_bands() {
+                if let Some(v) = sample(source.get_band(b), ls.sample, ls.line, interpolation) {
+                    target.put(x, y, v, b);
+                }
+            }
+            target.put_alpha(x, y, true);
+        }
+    }
+
+    let mut metadata = source.get_metadata().clone();
+    metadata.camera_model = Some(target_model.clone());
+    target.set_metadata(&metadata);
+
+    Ok(target)
+}
@@ -0,0 +1,53 @@
+/*
+    JSON sidecar files carrying image geometry and processing state.
+
+    Standard image formats such as PNG and TIFF can't hold the image mode, camera model or
+    calibration history of an image. These are written to a JSON file alongside the image,
+    named by appending .json to the image file name (e.g. image.png -> image.png.json).
+*/
+
+use crate::{enums::ImageMode, error, image::Image, metadata::Metadata};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sidecar {
+    pub width: usize,
+    pub height: usize,
+    pub bands: usize,
+    pub mode: ImageMode,
+
+    // Camera model, observation information and processing history
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+impl Sidecar {
+    pub fn from_image(image: &Image) -> Sidecar {
+        Sidecar {
+            width: image.width,
+            height: image.height,
+            bands: image.num_bands(),
+            mode: image.get_mode(),
+            metadata: image.get_metadata().clone(),
+        }
+    }
+
+    pub fn from_file(file_path: &str) -> Result<Sidecar> {
+        error::check_file_exists(file_path)?;
+        Ok(serde_json::from_str(&fs::read_to_string(file_path)?)?)
+    }
+
+    pub fn save(&self, to_file: &str) -> Result<()> {
+        error::check_parent_writable(to_file)?;
+        fs::write(to_file, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Path of the sidecar file accompanying an image file
+pub fn sidecar_path(image_path: &str) -> String {
+    format!("{}.json", image_path)
+}
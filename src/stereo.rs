@@ -0,0 +1,273 @@
+/*
+    Stereo triangulation and rectification from a pair of camera models.
+
+    Matching pixels in the left and right images are cast as rays through their camera models.
+    Since the rays rarely intersect exactly, the point is taken as the midpoint of the segment
+    of closest approach between them, with the length of that segment (the miss distance)
+    serving as a measure of the quality of the match and the camera calibration.
+
+    Rectification resamples a pair into linear models sharing A, H and V, such that matching
+    points lie on the same image row, as needed for dense stereo matching.
+*/
+
+use crate::{
+    camera::cahv::Cahv,
+    camera::model::{CameraModel, ImageCoordinate, LookVector},
+    image::Image,
+    metadata::ProcessingStep,
+    reproject::{self, Interpolation},
+    vector::Vector,
+};
+
+use anyhow::{anyhow, Result};
+
+// Rays closer to parallel than this (sine squared of the angle between them) are rejected
+static PARALLEL_LIMIT: f64 = 1.0e-12;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Triangulation {
+    // Triangulated point, in the frame of the camera models
+    pub xyz: Vector,
+
+    // Distance between the two rays at their closest approach
+    pub miss_distance: f64,
+
+    // Distance from the left camera center to the point
+    pub range: f64,
+
+    // Estimated range uncertainty for one pixel of matching error
+    pub range_error: f64,
+}
+
+/// Finds the points of closest approach along two rays, returning the distance along each ray
+/// to its point. Returns None if the rays are parallel.
+pub fn closest_approach(left: &LookVector, right: &LookVector) -> Option<(f64, f64)> {
+    let d1 = left.look_direction.normalized();
+    let d2 = right.look_direction.normalized();
+    let w0 = left.origin.subtract(&right.origin);
+
+    let b = d1.dot_product(&d2);
+    let d = d1.dot_product(&w0);
+    let e = d2.dot_product(&w0);
+
+    // With unit directions, 1 - b^2 is the squared sine of the angle between the rays
+    let denom = 1.0 - b * b;
+    if denom < PARALLEL_LIMIT {
+        return None;
+    }
+
+    Some(((b * e - d) / denom, (e - b * d) / denom))
+}
+
+/// Intersects two rays at their closest points, returning the midpoint between them and the
+/// miss distance. Returns None if the rays are parallel or the point lies behind either origin.
+pub fn intersect_rays(left: &LookVector, right: &LookVector) -> Option<(Vector, f64)> {
+    let (s, t) = closest_approach(left, right)?;
+    if s < 0.0 || t < 0.0 {
+        return None;
+    }
+
+    let p1 = left.origin.add(&left.look_direction.normalized().scale(s));
+    let p2 = right
+        .origin
+        .add(&right.look_direction.normalized().scale(t));
+
+    Some((p1.add(&p2).scale(0.5), p1.distance_to(&p2)))
+}
+
+/// Triangulates the point seen at `left_coordinate` in the left camera and `right_coordinate`
+/// in the right camera.
+///
+/// The range error is estimated from the angular size of a left camera pixel and the component
+/// of the stereo baseline perpendicular to the line of sight, as range^2 * pixel angle /
+/// baseline. It scales linearly with the matching error, in pixels.
+pub fn triangulate(
+    left_model: &CameraModel,
+    left_coordinate: &ImageCoordinate,
+    right_model: &CameraModel,
+    right_coordinate: &ImageCoordinate,
+) -> Result<Triangulation> {
+    if !left_model.is_valid() || !right_model.is_valid() {
+        return Err(anyhow!("Cannot triangulate with an invalid camera model"));
+    }
+
+    let left = left_model.ls_to_look_vector(left_coordinate)?;
+    let right = right_model.ls_to_look_vector(right_coordinate)?;
+
+    let (xyz, miss_distance) = intersect_rays(&left, &right)
+        .ok_or_else(|| anyhow!("Rays do not converge in front of both cameras"))?;
+
+    let line_of_sight = xyz.subtract(&left.origin);
+    let range = line_of_sight.len();
+    let baseline = right
+        .origin
+        .subtract(&left.origin)
+        .cross_product(&line_of_sight.normalized())
+        .len();
+
+    let range_error = if baseline > 0.0 {
+        range * range * left_model.pixel_angle_horiz() / baseline
+    } else {
+        f64::INFINITY
+    };
+
+    Ok(Triangulation {
+        xyz,
+        miss_distance,
+        range,
+        range_error,
+    })
+}
+
+/// Triangulates a list of matched left/right image coordinates. Pairs which fail to triangulate
+/// are returned as None.
+pub fn triangulate_matches(
+    left_model: &CameraModel,
+    right_model: &CameraModel,
+    matches: &[(ImageCoordinate, ImageCoordinate)],
+) -> Vec<Option<Triangulation>> {
+    matches
+        .iter()
+        .map(|(l, r)| triangulate(left_model, l, right_model, r).ok())
+        .collect()
+}
+
+// Decomposes a linear model's H or V vector into its scale and center relative to A
+fn image_plane_axis(a: &Vector, hv: &Vector) -> (Vector, f64, f64) {
+    let center = a.dot_product(hv);
+    let scale = a.cross_product(hv).len();
+    (
+        hv.subtract(&a.scale(center)).scale(1.0 / scale),
+        scale,
+        center,
+    )
+}
+
+/// Computes a pair of aligned linear CAHV models for epipolar rectification of a stereo pair,
+/// following the approach of the JPL `cmod_cahv_warp_models`. Both models share the same A, H
+/// and V vectors, with H parallel to the stereo baseline and A perpendicular to it, so the
+/// epipolar lines of the rectified pair run along image rows. Each camera keeps its own C.
+///
+/// CAHVOR and CAHVORE models are first linearized from images of `width` x `height` to
+/// `out_width` x `out_height`, and the image plane scale and center of the pair are averaged.
+pub fn rectify_models(
+    left_model: &CameraModel,
+    right_model: &CameraModel,
+    width: usize,
+    height: usize,
+    out_width: usize,
+    out_height: usize,
+) -> Result<(CameraModel, CameraModel)> {
+    if !left_model.is_valid() || !right_model.is_valid() {
+        return Err(anyhow!("Cannot rectify an invalid camera model"));
+    }
+
+    let left = left_model
+        .linearize(width, height, out_width, out_height)
+        .map_err(|e| anyhow!("Failed to linearize left camera model: {}", e))?;
+    let right = right_model
+        .linearize(width, height, out_width, out_height)
+        .map_err(|e| anyhow!("Failed to linearize right camera model: {}", e))?;
+
+    let baseline = right.c().subtract(&left.c());
+    if baseline.len() == 0.0 {
+        return Err(anyhow!("Stereo cameras share the same center"));
+    }
+    let baseline = baseline.normalized();
+
+    let (h_left, hs_left, hc_left) = image_plane_axis(&left.a(), &left.h());
+    let (h_right, hs_right, hc_right) = image_plane_axis(&right.a(), &right.h());
+    let (v_left, vs_left, vc_left) = image_plane_axis(&left.a(), &left.v());
+    let (v_right, vs_right, vc_right) = image_plane_axis(&right.a(), &right.v());
+
+    // H runs along the baseline, in the direction of the original horizontal axis
+    let mut h = baseline;
+    if h.dot_product(&h_left.add(&h_right)) < 0.0 {
+        h = h.inversed();
+    }
+
+    // A is the mean pointing, made perpendicular to the baseline
+    let mean_a = left.a().add(&right.a());
+    let a = mean_a
+        .subtract(&h.scale(h.dot_product(&mean_a)))
+        .normalized();
+
+    let mut v = a.cross_product(&h).normalized();
+    if v.dot_product(&v_left.add(&v_right)) < 0.0 {
+        v = v.inversed();
+    }
+
+    let hs = (hs_left + hs_right) / 2.0;
+    let hc = (hc_left + hc_right) / 2.0;
+    let vs = (vs_left + vs_right) / 2.0;
+    let vc = (vc_left + vc_right) / 2.0;
+
+    let rectified = |c: Vector| {
+        CameraModel::new(Box::new(Cahv {
+            c,
+            a,
+            h: h.scale(hs).add(&a.scale(hc)),
+            v: v.scale(vs).add(&a.scale(vc)),
+        }))
+    };
+
+    Ok((rectified(left.c()), rectified(right.c())))
+}
+
+/// A stereo pair resampled into rectified camera models
+pub struct RectifiedPair {
+    pub left: Image,
+    pub left_model: CameraModel,
+    pub right: Image,
+    pub right_model: CameraModel,
+}
+
+/// Rectifies a stereo pair, resampling both images into the aligned models computed by
+/// `rectify_models`. Pixels of the rectified images which fall outside the original fields of
+/// view are masked out in the alpha band.
+pub fn rectify(
+    left: &Image,
+    left_model: &CameraModel,
+    right: &Image,
+    right_model: &CameraModel,
+    out_width: usize,
+    out_height: usize,
+) -> Result<RectifiedPair> {
+    if left.width != right.width || left.height != right.height {
+        return Err(anyhow!("Stereo images differ in size"));
+    }
+
+    let (left_rectified, right_rectified) = rectify_models(
+        left_model,
+        right_model,
+        left.width,
+        left.height,
+        out_width,
+        out_height,
+    )?;
+
+    let resample = |image: &Image, from: &CameraModel, to: &CameraModel| -> Result<Image> {
+        let mut resampled = reproject::resample(
+            image,
+            from,
+            to,
+            out_width,
+            out_height,
+            Interpolation::Bicubic,
+            None,
+        )?;
+        resampled.get_metadata_mut().add_history(
+            ProcessingStep::new("rectify")
+                .with_parameter("width", out_width)
+                .with_parameter("height", out_height),
+        );
+        Ok(resampled)
+    };
+
+    Ok(RectifiedPair {
+        left: resample(left, left_model, &left_rectified)?,
+        right: resample(right, right_model, &right_rectified)?,
+        left_model: left_rectified,
+        right_model: right_rectified,
+    })
+}
@@ -0,0 +1,258 @@
+/*
+    Lossless 32 bit floating point and multi-band TIFF input and output.
+
+    The image crate only encodes 8 and 16 bit integer TIFFs, so floating point data is written
+    and read through the tiff crate directly. Samples are stored as IEEE single precision
+    (SampleFormat = 3), preserving calibrated values exactly.
+
+    Images with an arbitrary number of bands are written as multi-page TIFFs, with each band
+    stored as a grayscale page in band order.
+*/
+
+use crate::{
+    enums::ImageMode,
+    error::{self, SciImgError},
+    image::Image,
+    imagebuffer::ImageBuffer,
+    raster::SampleType,
+    Dn,
+};
+
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::BufWriter;
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::encoder::{colortype, TiffEncoder};
+use tiff::tags::{SampleFormat, Tag};
+use tiff::ColorType;
+
+fn open_decoder(file_path: &str) -> Result<Decoder<File>> {
+    error::check_file_exists(file_path)?;
+    Ok(Decoder::new(File::open(file_path)?)?)
+}
+
+/// Checks whether a file is a TIFF with floating point samples
+pub fn is_float_tiff(file_path: &str) -> bool {
+    match open_decoder(file_path) {
+        Ok(mut decoder) => matches!(
+            decoder.find_tag_unsigned_vec::<u16>(Tag::SampleFormat),
+            Ok(Some(f)) if f.first() == Some(&SampleFormat::IEEEFP.to_u16())
+        ),
+        Err(_) => false,
+    }
+}
+
+/// Checks whether a file is a TIFF containing more than one page
+pub fn is_multipage_tiff(file_path: &str) -> bool {
+    match open_decoder(file_path) {
+        Ok(decoder) => decoder.more_images(),
+        Err(_) => false,
+    }
+}
+
+fn decoded_to_vec(result: DecodingResult) -> Vec<Dn> {
+    match result {
+        DecodingResult::U8(v) => v.into_iter().map(|s| s as Dn).collect(),
+        DecodingResult::U16(v) => v.into_iter().map(|s| s as Dn).collect(),
+        DecodingResult::U32(v) => v.into_iter().map(|s| s as Dn).collect(),
+        DecodingResult::U64(v) => v.into_iter().map(|s| s as Dn).collect(),
+        DecodingResult::F32(v) => v,
+        DecodingResult::F64(v) => v.into_iter().map(|s| s as Dn).collect(),
+        DecodingResult::I8(v) => v.into_iter().map(|s| s as Dn).collect(),
+        DecodingResult::I16(v) => v.into_iter().map(|s| s as Dn).collect(),
+        DecodingResult::I32(v) => v.into_iter().map(|s| s as Dn).collect(),
+        DecodingResult::I64(v) => v.into_iter().map(|s| s as Dn).collect(),
+    }
+}
+
+fn is_float_result(decoded: &DecodingResult) -> bool {
+    matches!(decoded, DecodingResult::F32(_) | DecodingResult::F64(_))
+}
+
+/// Reads the first page of a TIFF into an image without rounding. An alpha channel, if
+/// present, is loaded as the image alpha mask.
+pub fn read_image(file_path: &str) -> Result<Image> {
+    let mut decoder = open_decoder(file_path)?;
+    let (width, height) = decoder.dimensions()?;
+    let (width, height) = (width as usize, height as usize);
+
+    let (samples_per_pixel, has_alpha, mode) = match decoder.colortype()? {
+        ColorType::Gray(b) => (1, false, b),
+        ColorType::GrayA(b) => (2, true, b),
+        ColorType::RGB(b) => (3, false, b),
+        ColorType::RGBA(b) => (4, true, b),
+        c => return Err(anyhow!("Unsupported TIFF color type: {:?}", c)),
+    };
+    let mode = if mode == 8 {
+        ImageMode::U8BIT
+    } else {
+        ImageMode::U16BIT
+    };
+
+    let decoded = decoder.read_image()?;
+    let float_samples = is_float_result(&decoded);
+    let samples = decoded_to_vec(decoded);
+    if samples.len() != width * height * samples_per_pixel {
+        return Err(anyhow!("TIFF sample count does not match image dimensions"));
+    }
+
+    let num_bands = if has_alpha {
+        samples_per_pixel - 1
+    } else {
+        samples_per_pixel
+    };
+
+    let mut image = Image::new_with_bands_masked(width, height, num_bands, mode, has_alpha)?;
+    for y in 0..height {
+        for x in 0..width {
+            let i = (y * width + x) * samples_per_pixel;
+            for b in 0..num_bands {
+                image.put(x, y, samples[i + b], b);
+            }
+            if has_alpha {
+                image.put_alpha(x, y, samples[i + num_bands] > 0.0);
+            }
+        }
+    }
+    image.get_metadata_mut().float_samples = float_samples;
+    Ok(image)
+}
+
+/// Reads the first band of a TIFF into an image buffer without rounding
+pub fn read_buffer(file_path: &str) -> Result<ImageBuffer> {
+    Ok(read_image(file_path)?.get_band(0).clone())
+}
+
+/// Writes an image as a 32 bit floating point TIFF. Single band images are written as
+/// grayscale, three band images as RGB, or RGBA if the image uses alpha.
+pub fn write_image(image: &Image, to_file: &str) -> Result<()> {
+    error::check_parent_writable(to_file)?;
+
+    let (width, height) = (image.width as u32, image.height as u32);
+    let mut encoder = TiffEncoder::new(BufWriter::new(File::create(to_file)?))?;
+
+    let interleave = |bands: usize, with_alpha: bool| -> Vec<f32> {
+        let mut data: Vec<f32> = Vec::with_capacity(image.width * image.height * (bands + 1));
+        for y in 0..image.height {
+            for x in 0..image.width {
+                (0..bands).for_each(|b| data.push(image.get_band(b).get(x, y)));
+                if with_alpha {
+                    data.push(if image.get_alpha_at(x, y) { 1.0 } else { 0.0 });
+                }
+            }
+        }
+        data
+    };
+
+    match image.num_bands() {
+        1 => encoder.write_image::<colortype::Gray32Float>(width, height, &interleave(1, false))?,
+        3 if image.is_using_alpha() => {
+            encoder.write_image::<colortype::RGBA32Float>(width, height, &interleave(3, true))?
+        }
+        3 => encoder.write_image::<colortype::RGB32Float>(width, height, &interleave(3, false))?,
+        n => return Err(SciImgError::UnsupportedBandCount(n).into()),
+    };
+    Ok(())
+}
+
+/// Writes an image buffer as a single band 32 bit floating point TIFF
+pub fn write_buffer(buffer: &ImageBuffer, to_file: &str) -> Result<()> {
+    let mut image = Image::new(buffer.width, buffer.height, buffer.mode)?;
+    image.push_band(buffer);
+    write_image(&image, to_file)
+}
+
+/// Writes every band of an image as a separate grayscale page of a multi-page TIFF. Samples
+/// are stored as 8 or 16 bit unsigned integers, rounded and clamped, or as 32 bit floats.
+pub fn write_bands(image: &Image, to_file: &str, sample_type: SampleType) -> Result<()> {
+    error::check_parent_writable(to_file)?;
+    if image.num_bands() == 0 {
+        return Err(SciImgError::UnsupportedBandCount(0).into());
+    }
+
+    let (width, height) = (image.width as u32, image.height as u32);
+    let mut encoder = TiffEncoder::new(BufWriter::new(File::create(to_file)?))?;
+
+    for b in 0..image.num_bands() {
+        let band = image.get_band(b);
+        let values = (0..image.height).flat_map(|y| (0..image.width).map(move |x| band.get(x, y)));
+        match sample_type {
+            SampleType::UnsignedInt8 => {
+                let data: Vec<u8> = values.map(|v| v.round().clamp(0.0, 255.0) as u8).collect();
+                encoder.write_image::<colortype::Gray8>(width, height, &data)?
+            }
+            SampleType::UnsignedInt16 => {
+                let data: Vec<u16> = values
+                    .map(|v| v.round().clamp(0.0, 65535.0) as u16)
+                    .collect();
+                encoder.write_image::<colortype::Gray16>(width, height, &data)?
+            }
+            SampleType::Float32 => {
+                let data: Vec<f32> = values.collect();
+                encoder.write_image::<colortype::Gray32Float>(width, height, &data)?
+            }
+            t => {
+                return Err(anyhow!(
+                    "Unsupported sample type for multi-band TIFF: {:?}",
+                    t
+                ))
+            }
+        };
+    }
+    Ok(())
+}
+
+/// Reads every page of a TIFF into a single image, one band per sample of each page. All
+/// pages must share the same dimensions.
+pub fn read_bands(file_path: &str) -> Result<Image> {
+    let mut decoder = open_decoder(file_path)?;
+    let (width, height) = decoder.dimensions()?;
+    let (width, height) = (width as usize, height as usize);
+
+    let mut image: Option<Image> = None;
+    loop {
+        if decoder.dimensions()? != (width as u32, height as u32) {
+            return Err(anyhow!("TIFF pages differ in dimensions"));
+        }
+
+        let (samples_per_pixel, bits) = match decoder.colortype()? {
+            ColorType::Gray(b) => (1, b),
+            ColorType::GrayA(b) => (2, b),
+            ColorType::RGB(b) => (3, b),
+            ColorType::RGBA(b) => (4, b),
+            c => return Err(anyhow!("Unsupported TIFF color type: {:?}", c)),
+        };
+        let mode = if bits == 8 {
+            ImageMode::U8BIT
+        } else {
+            ImageMode::U16BIT
+        };
+        let image = image.get_or_insert(Image::new(width, height, mode)?);
+
+        let decoded = decoder.read_image()?;
+        if is_float_result(&decoded) {
+            image.get_metadata_mut().float_samples = true;
+        }
+        let samples = decoded_to_vec(decoded);
+        if samples.len() != width * height * samples_per_pixel {
+            return Err(anyhow!("TIFF sample count does not match image dimensions"));
+        }
+
+        for s in 0..samples_per_pixel {
+            let v: Vec<Dn> = samples
+                .iter()
+                .skip(s)
+                .step_by(samples_per_pixel)
+                .copied()
+                .collect();
+            image.push_band(&ImageBuffer::from_vec_as_mode(&v, width, height, mode)?);
+        }
+
+        if !decoder.more_images() {
+            break;
+        }
+        decoder.next_image()?;
+    }
+
+    image.ok_or_else(|| anyhow!("TIFF contains no pages"))
+}
@@ -1,5 +1,5 @@
 use crate::{enums::Axis, util::string_is_valid_f64};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use std::ops::Index;
 use std::ops::IndexMut;
 
@@ -337,25 +337,27 @@ fn vec_to_str(v: &[f64]) -> String {
     format!("({})", s)
 }
 
-fn str_to_vec(s: &str) -> Result<Vec<f64>> {
+// Parses a parenthesized, comma separated tuple of floats, e.g. "(1.0,2.0,3.0)"
+pub(crate) fn str_to_vec(s: &str) -> Result<Vec<f64>> {
+    let s0 = s.trim();
+    let s0 = s0
+        .strip_prefix('(')
+        .and_then(|s| s.strip_suffix(')'))
+        .ok_or_else(|| anyhow!("Invalid tuple string: {}", s))?;
     let mut tuple_vec: Vec<f64> = Vec::new();
-    let mut s0 = String::from(s);
-    s0.remove(0);
-    s0.remove(s0.len() - 1);
-    let split = s0.split(',');
-    for n in split {
+    for n in s0.split(',') {
         let n_t = n.trim();
         if string_is_valid_f64(n_t) {
             tuple_vec.push(n_t.parse::<f64>().unwrap());
         } else {
-            panic!("Encoutered invalid float value string: {}", n_t);
+            return Err(anyhow!("Encoutered invalid float value string: {}", n_t));
         }
     }
     Ok(tuple_vec)
 }
 
 pub mod vector_format {
-    use serde::{self, Deserialize, Deserializer, Serializer};
+    use serde::{self, de::Error, Deserialize, Deserializer, Serializer};
 
     use crate::vector::{str_to_vec, vec_to_str};
 
@@ -373,15 +375,17 @@ pub mod vector_format {
     where
         D: Deserializer<'de>,
     {
-        let r: Result<&str, D::Error> = Deserialize::deserialize(deserializer);
+        let r: Result<String, D::Error> = Deserialize::deserialize(deserializer);
         match r {
             Err(_) => Ok(Vector::default()),
-            Ok(s) => match s {
+            Ok(s) => match s.as_str() {
                 "UNK" => Ok(Vector::default()),
                 _ => {
-                    let tuple_vec = str_to_vec(s).unwrap();
-                    let vec = Vector::from_vec(&tuple_vec).unwrap();
-                    Ok(vec)
+                    let tuple_vec = str_to_vec(&s).map_err(D::Error::custom)?;
+                    if tuple_vec.len() != 3 {
+                        return Err(D::Error::custom("Vector requires exactly three values"));
+                    }
+                    Ok(Vector::new(tuple_vec[0], tuple_vec[1], tuple_vec[2]))
                 }
             },
         }
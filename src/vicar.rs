@@ -0,0 +1,584 @@
+/*
+    Reader and writer for VICAR formatted images.
+
+    A VICAR file begins with an ASCII label of LBLSIZE bytes made up of whitespace separated
+    KEY=VALUE pairs. The label is organized into the system section (describing the raster),
+    zero or more property sections (each started with PROPERTY='NAME') and the history
+    sections (each started with TASK='NAME'). An optional end-of-file label continues the
+    label after the raster when EOL=1.
+
+    Reference: VICAR File Format, https://www-mipl.jpl.nasa.gov/PDS_Docs/VICAR_File_Format.pdf
+*/
+
+use crate::{
+    binfilereader::{BinFileReader, Endian},
+    enums::ImageMode,
+    error,
+    image::Image,
+    pds3::{Keyword, Value},
+    raster::{self, Interleave, RasterLayout, SampleType},
+};
+
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::io::Read;
+
+/// A named property or history (task) section of the label
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub name: String,
+    pub keywords: Vec<Keyword>,
+}
+
+impl Section {
+    pub fn new(name: &str) -> Section {
+        Section {
+            name: name.to_string(),
+            keywords: vec![],
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        find_keyword(&self.keywords, name)
+    }
+
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        self.get(name).and_then(|v| v.as_str())
+    }
+
+    pub fn get_i64(&self, name: &str) -> Option<i64> {
+        self.get(name).and_then(|v| v.as_i64())
+    }
+
+    pub fn get_f64(&self, name: &str) -> Option<f64> {
+        self.get(name).and_then(|v| v.as_f64())
+    }
+
+    /// Adds or replaces a keyword in the section
+    pub fn set(&mut self, name: &str, value: Value) {
+        match self
+            .keywords
+            .iter_mut()
+            .find(|k| k.name.eq_ignore_ascii_case(name))
+        {
+            Some(k) => k.value = value,
+            None => self.keywords.push(Keyword {
+                name: name.to_string(),
+                value,
+            }),
+        }
+    }
+}
+
+fn find_keyword<'a>(keywords: &'a [Keyword], name: &str) -> Option<&'a Value> {
+    keywords
+        .iter()
+        .find(|k| k.name.eq_ignore_ascii_case(name))
+        .map(|k| &k.value)
+}
+
+/// A parsed VICAR label
+#[derive(Debug, Clone, Default)]
+pub struct Label {
+    pub system: Vec<Keyword>,
+    pub properties: Vec<Section>,
+    pub history: Vec<Section>,
+}
+
+enum CurrentSection {
+    System,
+    Property,
+    History,
+}
+
+impl Label {
+    pub fn parse_str(text: &str) -> Result<Label> {
+        let mut label = Label::default();
+        let mut parser = Parser::new(text.as_bytes());
+        let mut current = CurrentSection::System;
+
+        while let Some((name, value)) = parser.next_keyword()? {
+            let upper = name.to_uppercase();
+            if upper == "PROPERTY" {
+                label
+                    .properties
+                    .push(Section::new(value.as_str().unwrap_or_default()));
+                current = CurrentSection::Property;
+            } else if upper == "TASK" {
+                label
+                    .history
+                    .push(Section::new(value.as_str().unwrap_or_default()));
+                current = CurrentSection::History;
+            } else {
+                let keyword = Keyword { name, value };
+                match current {
+                    CurrentSection::System => label.system.push(keyword),
+                    CurrentSection::Property => {
+                        label.properties.last_mut().unwrap().keywords.push(keyword)
+                    }
+                    CurrentSection::History => {
+                        label.history.last_mut().unwrap().keywords.push(keyword)
+                    }
+                }
+            }
+        }
+
+        Ok(label)
+    }
+
+    /// Value of a keyword in the system section
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        find_keyword(&self.system, name)
+    }
+
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        self.get(name).and_then(|v| v.as_str())
+    }
+
+    pub fn get_i64(&self, name: &str) -> Option<i64> {
+        self.get(name).and_then(|v| v.as_i64())
+    }
+
+    pub fn property(&self, name: &str) -> Option<&Section> {
+        self.properties
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns the property section of the specified name, creating it if needed
+    pub fn property_mut(&mut self, name: &str) -> &mut Section {
+        match self
+            .properties
+            .iter()
+            .position(|p| p.name.eq_ignore_ascii_case(name))
+        {
+            Some(i) => &mut self.properties[i],
+            None => {
+                self.properties.push(Section::new(name));
+                self.properties.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Builds the raster layout described by the system section
+    pub fn raster_layout(&self) -> Result<RasterLayout> {
+        let lblsize = self.required_usize("LBLSIZE")?;
+        let recsize = self.required_usize("RECSIZE")?;
+        let nl = self.required_usize("NL")?;
+        let ns = self.required_usize("NS")?;
+        let nb = self.get_i64("NB").unwrap_or(1) as usize;
+        let nbb = self.get_i64("NBB").unwrap_or(0) as usize;
+        let nlb = self.get_i64("NLB").unwrap_or(0) as usize;
+
+        let format = self.get_str("FORMAT").unwrap_or("BYTE");
+        let intfmt = self.get_str("INTFMT").unwrap_or("HIGH");
+        let realfmt = self.get_str("REALFMT").unwrap_or("IEEE");
+
+        let sample_type = sample_type_from_format(format)?;
+        let endian = if sample_type.is_float() {
+            match realfmt.to_uppercase().as_str() {
+                "IEEE" => Endian::BigEndian,
+                "RIEEE" => Endian::LittleEndian,
+                _ => return Err(anyhow!("Unsupported real format: {}", realfmt)),
+            }
+        } else {
+            match intfmt.to_uppercase().as_str() {
+                "HIGH" => Endian::BigEndian,
+                "LOW" => Endian::LittleEndian,
+                _ => return Err(anyhow!("Unsupported integer format: {}", intfmt)),
+            }
+        };
+
+        let interleave = match self.get_str("ORG").unwrap_or("BSQ").to_uppercase().as_str() {
+            "BSQ" => Interleave::BandSequential,
+            "BIL" => Interleave::LineInterleaved,
+            "BIP" => Interleave::SampleInterleaved,
+            o => return Err(anyhow!("Unsupported file organization: {}", o)),
+        };
+
+        let mut layout = RasterLayout::new(ns, nl, nb, sample_type, endian, interleave);
+        layout.offset = lblsize + nlb * recsize;
+        layout.line_prefix_bytes = nbb;
+
+        if layout.record_bytes() != recsize {
+            return Err(anyhow!(
+                "Record size mismatch: RECSIZE is {}, expected {}",
+                recsize,
+                layout.record_bytes()
+            ));
+        }
+
+        Ok(layout)
+    }
+
+    fn required_usize(&self, name: &str) -> Result<usize> {
+        match self.get_i64(name) {
+            Some(v) if v >= 0 => Ok(v as usize),
+            _ => Err(anyhow!("Label is missing or has invalid {}", name)),
+        }
+    }
+
+    fn format_sections(&self, b: &mut String) {
+        for p in self.properties.iter() {
+            b.push_str(&format!(
+                "PROPERTY={}  ",
+                format_value(&Value::Text(p.name.clone()))
+            ));
+            format_keywords(b, &p.keywords);
+        }
+        for t in self.history.iter() {
+            b.push_str(&format!(
+                "TASK={}  ",
+                format_value(&Value::Text(t.name.clone()))
+            ));
+            format_keywords(b, &t.keywords);
+        }
+    }
+}
+
+fn format_keywords(b: &mut String, keywords: &[Keyword]) {
+    for k in keywords.iter() {
+        b.push_str(&format!("{}={}  ", k.name, format_value(&k.value)));
+    }
+}
+
+/// Formats a value as it would appear in a VICAR label
+pub fn format_value(value: &Value) -> String {
+    match value {
+        Value::Integer(i) => format!("{}", i),
+        Value::Real(f) => format!("{:?}", f),
+        Value::Text(s) | Value::Symbol(s) => format!("'{}'", s.replace('\'', "''")),
+        Value::WithUnits(v, _) => format_value(v),
+        Value::Sequence(v) | Value::Set(v) => format!(
+            "({})",
+            v.iter()
+                .map(format_value)
+                .collect::<Vec<String>>()
+                .join(",")
+        ),
+    }
+}
+
+pub fn sample_type_from_format(format: &str) -> Result<SampleType> {
+    match format.to_uppercase().as_str() {
+        "BYTE" => Ok(SampleType::UnsignedInt8),
+        "HALF" | "WORD" => Ok(SampleType::SignedInt16),
+        "FULL" | "LONG" => Ok(SampleType::SignedInt32),
+        "REAL" => Ok(SampleType::Float32),
+        "DOUB" => Ok(SampleType::Float64),
+        _ => Err(anyhow!("Unsupported pixel format: {}", format)),
+    }
+}
+
+pub fn format_from_sample_type(sample_type: SampleType) -> Result<&'static str> {
+    match sample_type {
+        SampleType::UnsignedInt8 => Ok("BYTE"),
+        SampleType::SignedInt16 => Ok("HALF"),
+        SampleType::SignedInt32 => Ok("FULL"),
+        SampleType::Float32 => Ok("REAL"),
+        SampleType::Float64 => Ok("DOUB"),
+        _ => Err(anyhow!(
+            "Sample type {:?} has no VICAR equivalent",
+            sample_type
+        )),
+    }
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a [u8]) -> Parser<'a> {
+        Parser { data, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_ascii_whitespace() || c == 0 {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn next_keyword(&mut self) -> Result<Option<(String, Value)>> {
+        self.skip_whitespace();
+        if self.peek().is_none() {
+            return Ok(None);
+        }
+
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == b'_' || c == b'-' {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        let name = String::from_utf8_lossy(&self.data[start..self.pos]).to_string();
+
+        self.skip_whitespace();
+        if name.is_empty() || self.peek() != Some(b'=') {
+            return Err(anyhow!("Invalid VICAR label keyword at byte {}", start));
+        }
+        self.pos += 1;
+
+        Ok(Some((name, self.parse_value()?)))
+    }
+
+    fn parse_value(&mut self) -> Result<Value> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'\'') => {
+                self.pos += 1;
+                let mut s: Vec<u8> = vec![];
+                loop {
+                    match self.peek() {
+                        Some(b'\'') if self.data.get(self.pos + 1) == Some(&b'\'') => {
+                            s.push(b'\'');
+                            self.pos += 2;
+                        }
+                        Some(b'\'') => {
+                            self.pos += 1;
+                            break;
+                        }
+                        Some(c) => {
+                            s.push(c);
+                            self.pos += 1;
+                        }
+                        None => return Err(anyhow!("Unterminated string in VICAR label")),
+                    }
+                }
+                Ok(Value::Text(String::from_utf8_lossy(&s).to_string()))
+            }
+            Some(b'(') => {
+                self.pos += 1;
+                let mut values = vec![];
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b')') => {
+                            self.pos += 1;
+                            return Ok(Value::Sequence(values));
+                        }
+                        Some(b',') => self.pos += 1,
+                        Some(_) => values.push(self.parse_value()?),
+                        None => return Err(anyhow!("Unterminated list in VICAR label")),
+                    }
+                }
+            }
+            Some(_) => {
+                let start = self.pos;
+                while let Some(c) = self.peek() {
+                    if c.is_ascii_whitespace() || c == 0 || c == b',' || c == b')' {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+                let s = String::from_utf8_lossy(&self.data[start..self.pos]).to_string();
+                Ok(if let Ok(i) = s.parse::<i64>() {
+                    Value::Integer(i)
+                } else if let Ok(f) = s.parse::<f64>() {
+                    Value::Real(f)
+                } else {
+                    Value::Symbol(s)
+                })
+            }
+            None => Err(anyhow!("Unexpected end of VICAR label")),
+        }
+    }
+}
+
+fn read_lblsize(bytes: &[u8]) -> Result<usize> {
+    let head = String::from_utf8_lossy(bytes);
+    if !head.starts_with("LBLSIZE=") {
+        return Err(anyhow!("Not a VICAR label: LBLSIZE not found"));
+    }
+    head["LBLSIZE=".len()..]
+        .trim_start()
+        .split(|c: char| !c.is_ascii_digit())
+        .next()
+        .and_then(|s| s.parse::<usize>().ok())
+        .ok_or_else(|| anyhow!("Invalid LBLSIZE value"))
+}
+
+fn read_label_from_reader(reader: &BinFileReader) -> Result<Label> {
+    let lblsize = read_lblsize(&reader.read_bytes(0, usize::min(40, reader.len())))?;
+    if lblsize > reader.len() {
+        return Err(anyhow!("LBLSIZE exceeds file length"));
+    }
+    let mut text = String::from_utf8_lossy(&reader.read_bytes(0, lblsize)).to_string();
+
+    let label = Label::parse_str(&text)?;
+    if label.get_i64("EOL").unwrap_or(0) == 1 {
+        let layout = label.raster_layout()?;
+        let nlb = label.get_i64("NLB").unwrap_or(0) as usize;
+        let recsize = layout.record_bytes();
+        let eol_start = lblsize + (nlb + layout.num_records()) * recsize;
+        if eol_start < reader.len() {
+            let remaining = reader.len() - eol_start;
+            let eol_size = read_lblsize(&reader.read_bytes(eol_start, usize::min(40, remaining)))?;
+            let eol_bytes = reader.read_bytes(eol_start, usize::min(eol_size, remaining));
+
+            // The LBLSIZE of the EOL label describes only itself and is dropped.
+            let eol_text = String::from_utf8_lossy(&eol_bytes).to_string();
+            let eol_text = eol_text
+                .trim_start_matches("LBLSIZE=")
+                .trim_start()
+                .trim_start_matches(|c: char| c.is_ascii_digit());
+            text.push(' ');
+            text.push_str(eol_text);
+            return Label::parse_str(&text);
+        }
+    }
+    Ok(label)
+}
+
+/// Reads the label of a VICAR file, including any end-of-file label
+pub fn read_label(file_path: &str) -> Result<Label> {
+    error::check_file_exists(file_path)?;
+    read_label_from_reader(&BinFileReader::open(file_path)?)
+}
+
+/// Reads a VICAR file into an image
+pub fn read_image(file_path: &str) -> Result<Image> {
+    error::check_file_exists(file_path)?;
+    let reader = BinFileReader::open(file_path)?;
+    let label = read_label_from_reader(&reader)?;
+    let layout = label.raster_layout()?;
+    raster::read_image(&reader, &layout, layout.sample_type.image_mode())
+}
+
+/// Checks whether a file begins with a VICAR label
+pub fn is_vicar_file(file_path: &str) -> bool {
+    match fs::File::open(file_path) {
+        Ok(f) => {
+            let mut buf = [0_u8; 8];
+            f.take(8).read(&mut buf).unwrap_or(0) == 8 && &buf == b"LBLSIZE="
+        }
+        Err(_) => false,
+    }
+}
+
+/// Default VICAR pixel format used to store an image of the specified mode. 16 bit
+/// data is stored as REAL since HALF is signed.
+pub fn default_sample_type(mode: ImageMode) -> SampleType {
+    match mode {
+        ImageMode::U8BIT => SampleType::UnsignedInt8,
+        ImageMode::U12BIT => SampleType::SignedInt16,
+        ImageMode::U16BIT => SampleType::Float32,
+    }
+}
+
+// Records each step of the processing history of an image as a history task, with the
+// step parameters as keywords.
+fn history_sections(image: &Image) -> Vec<Section> {
+    image
+        .get_metadata()
+        .history
+        .iter()
+        .map(|step| {
+            let mut task = Section::new(&step.operation.to_uppercase());
+            for (k, v) in step.parameters.iter() {
+                task.set(&k.to_uppercase(), Value::Text(v.clone()));
+            }
+            task.set("DAT_TIM", Value::Text(step.timestamp.clone()));
+            task
+        })
+        .collect()
+}
+
+/// Writes an image as a band sequential VICAR file, using the system-generated label along
+/// with the property and history sections of the supplied label. The processing history of
+/// the image is written as history tasks ahead of those of the label.
+pub fn write_image_with_label(
+    image: &Image,
+    to_file: &str,
+    sample_type: SampleType,
+    label: &Label,
+) -> Result<()> {
+    error::check_parent_writable(to_file)?;
+
+    let layout = RasterLayout::new(
+        image.width,
+        image.height,
+        image.num_bands(),
+        sample_type,
+        Endian::LittleEndian,
+        Interleave::BandSequential,
+    );
+    let recsize = layout.record_bytes();
+
+    let mut body = String::new();
+    body.push_str(&format!(
+        "FORMAT='{}'  TYPE='IMAGE'  BUFSIZ={}  DIM=3  EOL=0  RECSIZE={}  ORG='BSQ'  ",
+        format_from_sample_type(sample_type)?,
+        recsize,
+        recsize
+    ));
+    body.push_str(&format!(
+        "NL={}  NS={}  NB={}  N1={}  N2={}  N3={}  N4=0  NBB=0  NLB=0  ",
+        image.height,
+        image.width,
+        image.num_bands(),
+        image.width,
+        image.height,
+        image.num_bands()
+    ));
+    body.push_str("HOST='X86-64-LINX'  INTFMT='LOW'  REALFMT='RIEEE'  BHOST='X86-64-LINX'  ");
+    body.push_str(
+        "BINTFMT='LOW'  BREALFMT='RIEEE'  BLTYPE=''  COMPRESS='NONE'  EOCI1=0  EOCI2=0  ",
+    );
+    let mut label = label.clone();
+    label.history.splice(0..0, history_sections(image));
+    label.format_sections(&mut body);
+
+    // The LBLSIZE value is written as a fixed width field so the label length is known
+    // before the value is, then padded with nulls to a multiple of the record size.
+    let lblsize_field_len = "LBLSIZE=".len() + 16;
+    let min_len = lblsize_field_len + body.len() + 1;
+    let lblsize = min_len + (recsize - min_len % recsize) % recsize;
+
+    let mut out: Vec<u8> = format!("LBLSIZE={:<16}{}", lblsize, body).into_bytes();
+    out.resize(lblsize, 0);
+    out.extend(raster::encode_image(image, &layout)?);
+
+    fs::write(to_file, out)?;
+    Ok(())
+}
+
+/// Writes an image as a VICAR file using the default pixel format for its mode. A history
+/// task is added to record the writer.
+pub fn write_image(image: &Image, to_file: &str) -> Result<()> {
+    let mut label = Label::default();
+    let mut task = Section::new("SCIIMG");
+    task.set("USER", Value::Text(whoami()));
+    task.set(
+        "DAT_TIM",
+        Value::Text(
+            chrono::Local::now()
+                .format("%a %b %e %H:%M:%S %Y")
+                .to_string(),
+        ),
+    );
+    label.history.push(task);
+    write_image_with_label(
+        image,
+        to_file,
+        default_sample_type(image.get_mode()),
+        &label,
+    )
+}
+
+fn whoami() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| String::from("sciimg"))
+}
@@ -0,0 +1,439 @@
+use sciimg::{
+    camera::cahv::Cahv,
+    camera::cahvor::Cahvor,
+    camera::cahvore::{Cahvore, PupilType},
+    camera::label,
+    camera::model::{CameraModel, LookVector, ModelType},
+    enums::{Axis, ImageMode},
+    image::Image,
+    matrix::Matrix,
+    pds3::Value,
+    quaternion::Quaternion,
+    vector::Vector,
+    vicar,
+};
+
+const PDS3_CAHVORE_LABEL: &str = r#"PDS_VERSION_ID = PDS3
+GROUP = GEOMETRIC_CAMERA_MODEL_PARMS
+  MODEL_TYPE = CAHVORE
+  MODEL_COMPONENT_ID = ("C","A","H","V","O","R","E","T","P")
+  MODEL_COMPONENT_1 = (0.804, 0.545, -1.955)
+  MODEL_COMPONENT_2 = (0.806, 0.553, 0.211)
+  MODEL_COMPONENT_3 = (-4306.1, 7279.2, 1016.7)
+  MODEL_COMPONENT_4 = (1498.8, 2177.3, 8126.3)
+  MODEL_COMPONENT_5 = (0.806, 0.553, 0.211)
+  MODEL_COMPONENT_6 = (1.0e-06, -0.0064, 0.0131)
+  MODEL_COMPONENT_7 = (0.0, 0.0, 0.0)
+  MODEL_COMPONENT_8 = 3.0
+  MODEL_COMPONENT_9 = 0.25
+END_GROUP = GEOMETRIC_CAMERA_MODEL_PARMS
+END
+"#;
+
+fn assert_vec_eq(a: &Vector, b: &Vector) {
+    assert!((a.x - b.x).abs() < 1e-9 && (a.y - b.y).abs() < 1e-9 && (a.z - b.z).abs() < 1e-9);
+}
+
+#[test]
+fn test_cahvore_from_pds3_label() {
+    let model = label::from_label_str(PDS3_CAHVORE_LABEL).unwrap();
+
+    assert!(model.model_type() == ModelType::CAHVORE);
+    assert_vec_eq(&model.c(), &Vector::new(0.804, 0.545, -1.955));
+    assert_vec_eq(&model.h(), &Vector::new(-4306.1, 7279.2, 1016.7));
+    assert_vec_eq(&model.r(), &Vector::new(1.0e-06, -0.0064, 0.0131));
+    assert_eq!(model.pupil_type(), PupilType::General);
+    assert_eq!(model.linearity(), 0.25);
+}
+
+#[test]
+fn test_cahvor_from_vicar_label() {
+    let mut vicar_label = vicar::Label::default();
+    let property = vicar_label.property_mut("GEOMETRIC_CAMERA_MODEL");
+    let vector = |x: f64, y: f64, z: f64| {
+        Value::Sequence(vec![Value::Real(x), Value::Real(y), Value::Real(z)])
+    };
+    property.set("MODEL_TYPE", Value::Text(String::from("CAHVOR")));
+    property.set("MODEL_COMPONENT_1", vector(1.0, 2.0, 3.0));
+    property.set("MODEL_COMPONENT_2", vector(0.0, 1.0, 0.0));
+    property.set("MODEL_COMPONENT_3", vector(100.0, 0.0, 0.0));
+    property.set("MODEL_COMPONENT_4", vector(0.0, 0.0, 100.0));
+    property.set("MODEL_COMPONENT_5", vector(0.0, 1.0, 0.0));
+    property.set("MODEL_COMPONENT_6", vector(0.0, -0.1, 0.01));
+
+    let model = label::from_vicar_label(&vicar_label).unwrap();
+    assert!(model.model_type() == ModelType::CAHVOR);
+    assert_vec_eq(&model.o(), &Vector::new(0.0, 1.0, 0.0));
+    assert_vec_eq(&model.r(), &Vector::new(0.0, -0.1, 0.01));
+    assert_eq!(model.pupil_type(), PupilType::Perspective);
+}
+
+#[test]
+fn test_cahv_from_label_text_with_default_component_order() {
+    let model = label::from_label_str(
+        "LBLSIZE=400  FORMAT='BYTE'  PROPERTY='GEOMETRIC_CAMERA_MODEL'  MODEL_TYPE='CAHV'  \
+         MODEL_COMPONENT_1=(1.0,2.0,3.0)  MODEL_COMPONENT_2=(0.0,0.0,1.0)  \
+         MODEL_COMPONENT_3=(500.0,0.0,100.0)  MODEL_COMPONENT_4=(0.0,500.0,100.0)",
+    )
+    .unwrap();
+
+    assert!(model.model_type() == ModelType::CAHV);
+    assert_vec_eq(&model.a(), &Vector::new(0.0, 0.0, 1.0));
+    assert_vec_eq(&model.v(), &Vector::new(0.0, 500.0, 100.0));
+}
+
+#[test]
+fn test_invalid_camera_model_labels() {
+    assert!(label::from_label_str("PDS_VERSION_ID = PDS3\nEND\n").is_err());
+    assert!(label::from_label_str(
+        "GROUP = GEOMETRIC_CAMERA_MODEL\nMODEL_TYPE = CAHV\nMODEL_COMPONENT_1 = (1.0, 2.0)\n\
+         END_GROUP = GEOMETRIC_CAMERA_MODEL\nEND\n"
+    )
+    .is_err());
+}
+
+fn test_cahvore() -> Cahvore {
+    Cahvore {
+        c: Vector::new(0.804, 0.545, -1.955),
+        a: Vector::new(0.806, 0.553, 0.211),
+        h: Vector::new(-4306.1, 7279.2, 1016.7),
+        v: Vector::new(1498.8, 2177.3, 8126.3),
+        o: Vector::new(0.806, 0.553, 0.211),
+        r: Vector::new(1.0e-06, -0.0064, 0.0131),
+        e: Vector::new(0.001, -0.002, 0.003),
+        pupil_type: PupilType::General,
+        linearity: 0.25,
+    }
+}
+
+#[test]
+fn test_from_serialized_round_trip() {
+    let cahv = CameraModel::new(Box::new(Cahv {
+        c: Vector::new(1.0, 2.0, 3.0),
+        a: Vector::new(0.0, 0.0, 1.0),
+        h: Vector::new(500.0, 0.0, 100.0),
+        v: Vector::new(0.0, 500.0, 100.0),
+    }));
+    let parsed = CameraModel::from_serialized(&cahv.serialize()).unwrap();
+    assert!(parsed.model_type() == ModelType::CAHV);
+    assert_eq!(parsed.serialize(), cahv.serialize());
+
+    let cahvor = CameraModel::new(Box::new(Cahvor {
+        c: Vector::new(1.0, 2.0, 3.0),
+        a: Vector::new(0.0, 0.0, 1.0),
+        h: Vector::new(500.0, 0.0, 100.0),
+        v: Vector::new(0.0, 500.0, 100.0),
+        o: Vector::new(0.0, 0.01, 0.99),
+        r: Vector::new(0.0, -0.1, 0.01),
+    }));
+    let parsed = CameraModel::from_serialized(&cahvor.serialize()).unwrap();
+    assert!(parsed.model_type() == ModelType::CAHVOR);
+    assert_eq!(parsed.serialize(), cahvor.serialize());
+
+    let cahvore = CameraModel::new(Box::new(test_cahvore()));
+    let parsed = CameraModel::from_serialized(&cahvore.serialize()).unwrap();
+    assert!(parsed.model_type() == ModelType::CAHVORE);
+    assert_eq!(parsed.serialize(), cahvore.serialize());
+    assert_eq!(parsed.pupil_type(), PupilType::General);
+    assert_eq!(parsed.linearity(), 0.25);
+}
+
+#[test]
+fn test_from_serialized_matches_vector_format() {
+    // Each serialized component is formatted identically to the serde vector_format helper
+    let model = test_cahvore();
+    let json: serde_json::Value = serde_json::to_value(&model).unwrap();
+    let serialized = CameraModel::new(Box::new(model)).serialize();
+    let parts: Vec<&str> = serialized.split(';').collect();
+
+    for (i, field) in ["c", "a", "h", "v", "o", "r", "e"].iter().enumerate() {
+        assert_eq!(json[field].as_str(), Some(parts[i]));
+    }
+
+    let parsed = CameraModel::from_serialized(&serialized).unwrap();
+    let restored: Cahvore = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed.h(), restored.h);
+    assert_eq!(parsed.e(), restored.e);
+}
+
+#[test]
+fn test_from_serialized_invalid() {
+    assert!(CameraModel::from_serialized("").is_err());
+    assert!(CameraModel::from_serialized("(1,2,3);(1,2,3);(1,2,3)").is_err());
+    assert!(CameraModel::from_serialized("(1,2,3);(1,2,3);(1,2,3);(1,2)").is_err());
+    assert!(CameraModel::from_serialized("(1,2,3);(1,2,3);(1,2,3);(1,x,3)").is_err());
+    assert!(CameraModel::from_serialized("(1,2,3);(1,2,3);(1,2,3);1,2,3").is_err());
+}
+
+fn test_models() -> Vec<CameraModel> {
+    let cahvor = Cahvor {
+        c: Vector::new(0.876, 0.121, -1.924),
+        a: Vector::new(0.962, 0.058, 0.267),
+        h: Vector::new(-1140.2, 3425.9, 412.3),
+        v: Vector::new(-105.8, 421.4, 3581.2),
+        o: Vector::new(0.962, 0.058, 0.267),
+        r: Vector::new(1.0e-6, -0.023, 0.012),
+    };
+    vec![
+        CameraModel::new(Box::new(Cahv {
+            c: cahvor.c,
+            a: cahvor.a,
+            h: cahvor.h,
+            v: cahvor.v,
+        })),
+        CameraModel::new(Box::new(cahvor)),
+        CameraModel::new(Box::new(test_cahvore())),
+    ]
+}
+
+// Points in front of the camera, spread across the field of view
+fn scene_points(model: &CameraModel) -> Vec<Vector> {
+    let mut points = vec![];
+    for (dy, dz) in [(0.0, 0.0), (0.4, -0.2), (-0.3, 0.35), (0.25, 0.3)] {
+        let p = model.c().add(&model.a().scale(4.0));
+        points.push(p.add(&Vector::new(0.0, dy, dz)));
+    }
+    points
+}
+
+fn assert_transform<F>(transform: F, model: &CameraModel, transformed: &CameraModel)
+where
+    F: Fn(f64, f64) -> (f64, f64),
+{
+    for p in scene_points(model) {
+        let ls = model.xyz_to_ls(&p, false);
+        let (sample, line) = transform(ls.sample, ls.line);
+        let ls_t = transformed.xyz_to_ls(&p, false);
+        assert!(
+            (ls_t.sample - sample).abs() < 1e-6 && (ls_t.line - line).abs() < 1e-6,
+            "Expected ({}, {}), got ({}, {})",
+            sample,
+            line,
+            ls_t.sample,
+            ls_t.line
+        );
+    }
+}
+
+#[test]
+fn test_image_geometry_transforms() {
+    for model in test_models() {
+        assert_transform(
+            |s, l| (s - 120.0, l - 64.5),
+            &model,
+            &model.subframe(120.0, 64.5).unwrap(),
+        );
+        assert_transform(
+            |s, l| (s * 0.5, l * 0.25),
+            &model,
+            &model.scaled(0.5, 0.25).unwrap(),
+        );
+        assert_transform(
+            |s, l| (s * 0.5 - 0.25, l * 0.5 - 0.25),
+            &model,
+            &model.resized(1024, 1024, 512, 512).unwrap(),
+        );
+        assert_transform(
+            |s, l| (1023.0 - s, l),
+            &model,
+            &model.flipped_horizontal(1024).unwrap(),
+        );
+        assert_transform(
+            |s, l| (s, 767.0 - l),
+            &model,
+            &model.flipped_vertical(768).unwrap(),
+        );
+        assert_transform(|s, l| (l, s), &model, &model.transposed().unwrap());
+
+        let transformed = model
+            .subframe(10.0, 20.0)
+            .and_then(|m| m.scaled(2.0, 2.0))
+            .unwrap();
+        assert!(transformed.model_type() == model.model_type());
+        assert_vec_eq(&transformed.c(), &model.c());
+        assert_vec_eq(&transformed.r(), &model.r());
+    }
+}
+
+#[test]
+fn test_crop_and_resize_adjust_image_model() {
+    let model = test_models().remove(1);
+    let mut img = Image::new_with_bands(64, 48, 1, ImageMode::U16BIT).unwrap();
+    img.get_metadata_mut().camera_model = Some(model.clone());
+
+    img.crop(8, 4, 32, 32);
+    img.resize_to(16, 16);
+
+    let expected = model
+        .subframe(8.0, 4.0)
+        .and_then(|m| m.resized(32, 32, 16, 16))
+        .unwrap();
+    assert_eq!(
+        img.get_metadata()
+            .camera_model
+            .as_ref()
+            .unwrap()
+            .serialize(),
+        expected.serialize()
+    );
+}
+
+#[test]
+fn test_transform_invalid_model() {
+    let model = CameraModel::default();
+    assert!(model.subframe(8.0, 4.0).is_err());
+    assert!(model.resized(32, 32, 16, 16).is_err());
+
+    let mut img = Image::new_with_bands(64, 48, 1, ImageMode::U16BIT).unwrap();
+    img.get_metadata_mut().camera_model = Some(model);
+
+    img.crop(8, 4, 32, 32);
+    img.resize_to(16, 16);
+    assert!(!img.get_metadata().camera_model.as_ref().unwrap().is_valid());
+}
+
+#[test]
+fn test_rigid_transform() {
+    let translation = Vector::new(12.5, -3.25, 0.75);
+    let q = Quaternion::from_pitch_roll_yaw(0.1, -0.25, 1.2);
+    let m = Matrix::rotate(0.4, Axis::ZAxis).multiply(&Matrix::rotate(-0.2, Axis::XAxis));
+
+    for model in test_models() {
+        let by_quaternion = model.transform(&q, &translation).unwrap();
+        let by_matrix = model.transform(&m, &translation).unwrap();
+
+        assert!(by_quaternion.model_type() == model.model_type());
+        assert_vec_eq(
+            &by_quaternion.c(),
+            &q.rotate_vector(&model.c()).add(&translation),
+        );
+        assert_vec_eq(&by_quaternion.r(), &model.r());
+        assert_vec_eq(&by_quaternion.e(), &model.e());
+
+        for p in scene_points(&model) {
+            let ls = model.xyz_to_ls(&p, false);
+            for (transformed, p_t) in [
+                (&by_quaternion, q.rotate_vector(&p).add(&translation)),
+                (&by_matrix, m.multiply_vector(&p).add(&translation)),
+            ] {
+                let ls_t = transformed.xyz_to_ls(&p_t, false);
+                assert!((ls_t.sample - ls.sample).abs() < 1e-6);
+                assert!((ls_t.line - ls.line).abs() < 1e-6);
+            }
+        }
+
+        // Rotating back into the original frame restores the model
+        let restored = by_quaternion
+            .transform(&q.invert(), &Vector::default())
+            .unwrap();
+        let restored = restored.transform(
+            &Quaternion::default(),
+            &q.invert().rotate_vector(&translation).scale(-1.0),
+        );
+        let restored = restored.unwrap();
+        assert_vec_eq(&restored.c(), &model.c());
+        assert_vec_eq(&restored.a(), &model.a());
+        assert_vec_eq(&restored.h(), &model.h());
+    }
+}
+
+#[test]
+fn test_rigid_transform_invalid_model() {
+    let model = CameraModel::default();
+    assert!(model
+        .transform(&Quaternion::default(), &Vector::default())
+        .is_err());
+}
+
+#[test]
+fn test_ray_intersections() {
+    let ray = LookVector {
+        origin: Vector::new(0.0, 0.0, 10.0),
+        look_direction: Vector::new(1.0, 0.0, -1.0),
+    };
+
+    // Tilted plane through (4, 0, 0) with normal along (1, 0, 2): x + 2z = 4
+    let hit = ray
+        .intersect_plane(&Vector::new(4.0, 0.0, 0.0), &Vector::new(1.0, 0.0, 2.0))
+        .unwrap();
+    assert!(hit.distance_to(&Vector::new(16.0, 0.0, -6.0)) < 1.0e-9);
+
+    // Parallel to the ray, and behind it
+    assert!(ray
+        .intersect_plane(&Vector::new(4.0, 0.0, 0.0), &Vector::new(1.0, 0.0, 1.0))
+        .is_none());
+    assert!(ray
+        .intersect_plane(&Vector::new(0.0, 0.0, 20.0), &Vector::new(0.0, 0.0, 1.0))
+        .is_none());
+    assert!(ray
+        .intersect_plane(&Vector::new(0.0, 5.0, 0.0), &Vector::new(0.0, 1.0, 0.0))
+        .is_none());
+
+    // Sphere around (5, 0, 5), entered from outside and exited from inside
+    let center = Vector::new(5.0, 0.0, 5.0);
+    let hit = ray.intersect_sphere(&center, 1.0).unwrap();
+    let s = 1.0 / 2.0_f64.sqrt();
+    assert!(hit.distance_to(&Vector::new(5.0 - s, 0.0, 5.0 + s)) < 1.0e-9);
+    let inside = LookVector {
+        origin: center,
+        look_direction: Vector::new(0.0, 0.0, 1.0),
+    };
+    assert!(
+        inside
+            .intersect_sphere(&center, 2.0)
+            .unwrap()
+            .distance_to(&Vector::new(5.0, 0.0, 7.0))
+            < 1.0e-9
+    );
+    assert!(ray
+        .intersect_sphere(&Vector::new(0.0, 5.0, 0.0), 1.0)
+        .is_none());
+    assert!(ray
+        .intersect_sphere(&Vector::new(-5.0, 0.0, 15.0), 1.0)
+        .is_none());
+
+    // Mars-sized ellipsoid seen from orbit, looking straight down from above the north pole
+    // and across towards the equator
+    let radii = Vector::new(3396.19, 3396.19, 3376.20);
+    let nadir = LookVector {
+        origin: Vector::new(0.0, 0.0, 3776.2),
+        look_direction: Vector::new(0.0, 0.0, -1.0),
+    };
+    let pole = nadir
+        .intersect_ellipsoid(&Vector::default(), &radii)
+        .unwrap();
+    assert!(pole.distance_to(&Vector::new(0.0, 0.0, 3376.2)) < 1.0e-6);
+
+    let oblique = LookVector {
+        origin: Vector::new(-5000.0, 0.0, 0.0),
+        look_direction: Vector::new(1.0, 0.0, 0.0),
+    };
+    let equator = oblique
+        .intersect_ellipsoid(&Vector::default(), &radii)
+        .unwrap();
+    assert!(equator.distance_to(&Vector::new(-3396.19, 0.0, 0.0)) < 1.0e-6);
+    let p = equator.divide(&radii);
+    assert!((p.dot_product(&p) - 1.0).abs() < 1.0e-9);
+    assert!(oblique
+        .intersect_ellipsoid(&Vector::new(0.0, 4000.0, 0.0), &radii)
+        .is_none());
+
+    // Jupiter in meters, where the scaled direction is smaller than EPSILON
+    let jupiter = Vector::new(71_492_000.0, 71_492_000.0, 66_854_000.0);
+    let approach = LookVector {
+        origin: Vector::new(0.0, 0.0, 1.0e8),
+        look_direction: Vector::new(0.0, 0.0, -1.0),
+    };
+    let pole = approach
+        .intersect_ellipsoid(&Vector::default(), &jupiter)
+        .unwrap();
+    assert!((pole.z - 66_854_000.0).abs() < 1.0e-3);
+
+    let stationary = LookVector {
+        origin: approach.origin,
+        look_direction: Vector::default(),
+    };
+    assert!(stationary
+        .intersect_ellipsoid(&Vector::default(), &jupiter)
+        .is_none());
+}
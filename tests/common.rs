@@ -1,4 +1,6 @@
 use sciimg::*;
+use std::fs;
+use std::path::PathBuf;
 
 // https://stackoverflow.com/questions/30856285/assert-eq-with-floating-point-numbers-and-delta
 #[macro_export]
@@ -12,3 +14,11 @@ macro_rules! assert_delta {
 
 #[allow(dead_code)]
 pub const DEFAULT_DELTA: Dn = 0.0001;
+
+// Path to a file within a scratch directory for a test suite, under the system temp directory
+#[allow(dead_code)]
+pub fn test_output_path(dir: &str, file_name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(file_name)
+}
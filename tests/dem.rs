@@ -0,0 +1,120 @@
+use sciimg::{camera::model::LookVector, dem::Dem, imagebuffer::ImageBuffer, vector::Vector};
+
+// 1 meter DEM of a plane rising along X, z = 0.1x, with X and Y running from -20 to 20
+fn sloped_dem() -> Dem {
+    let mut elevation = ImageBuffer::new(41, 41).unwrap();
+    for line in 0..41 {
+        for sample in 0..41 {
+            elevation.put(sample, line, (sample as f32 - 20.0) * 0.1);
+        }
+    }
+    Dem::new(elevation, -20.0, 20.0, 1.0, -1.0).unwrap()
+}
+
+#[test]
+fn test_dem_georeferencing() {
+    let dem = sloped_dem();
+    assert_eq!(dem.to_pixel(-20.0, 20.0), (0.0, 0.0));
+    assert_eq!(dem.to_pixel(0.5, -3.0), (20.5, 23.0));
+    assert_eq!(dem.to_world(40.0, 40.0), (20.0, -20.0));
+
+    assert!((dem.elevation_at(5.5, 3.25).unwrap() - 0.55).abs() < 1.0e-6);
+    assert!((dem.elevation_at(20.0, -20.0).unwrap() - 2.0).abs() < 1.0e-6);
+    assert!(dem.elevation_at(20.5, 0.0).is_none());
+
+    assert!(Dem::new(ImageBuffer::new(4, 4).unwrap(), 0.0, 0.0, 0.0, 1.0).is_err());
+}
+
+#[test]
+fn test_intersect_dem() {
+    let dem = sloped_dem();
+
+    // Looking down and along +X from 5 meters up, meeting the plane z = 0.1x where
+    // 5 - t / sqrt(2) = 0.1 (-10 + t / sqrt(2))
+    let ray = LookVector {
+        origin: Vector::new(-10.0, 2.0, 5.0),
+        look_direction: Vector::new(1.0, 0.0, -1.0),
+    };
+    let hit = ray.intersect_dem(&dem, 100.0).unwrap();
+    let d = 6.0 / 1.1;
+    assert!(hit.distance_to(&Vector::new(-10.0 + d, 2.0, 5.0 - d)) < 1.0e-6);
+    assert!((hit.z - dem.elevation_at(hit.x, hit.y).unwrap()).abs() < 1.0e-6);
+
+    // Out of range, looking up, and off the edge of the DEM
+    assert!(ray.intersect_dem(&dem, 5.0).is_none());
+    let up = LookVector {
+        origin: ray.origin,
+        look_direction: Vector::new(1.0, 0.0, 0.5),
+    };
+    assert!(up.intersect_dem(&dem, 100.0).is_none());
+    let away = LookVector {
+        origin: ray.origin,
+        look_direction: Vector::new(-1.0, 0.0, -0.01),
+    };
+    assert!(away.intersect_dem(&dem, 100.0).is_none());
+}
+
+#[test]
+fn test_intersect_dem_with_hole() {
+    let mut dem = sloped_dem();
+    let ray = LookVector {
+        origin: Vector::new(0.0, 0.0, 10.0),
+        look_direction: Vector::new(0.0, 0.0, -1.0),
+    };
+    assert!((ray.intersect_dem(&dem, 20.0).unwrap().z).abs() < 1.0e-6);
+
+    // A hole under the ray
+    dem.elevation.put_mask(20, 20, false);
+    assert!(ray.intersect_dem(&dem, 20.0).is_none());
+
+    // A ray passing over the hole still hits the terrain beyond it
+    let oblique = LookVector {
+        origin: Vector::new(-2.0, 0.0, 2.0),
+        look_direction: Vector::new(1.0, 0.0, -0.5),
+    };
+    let hit = oblique.intersect_dem(&dem, 50.0).unwrap();
+    assert!(hit.x > 1.0);
+    assert!((hit.z - 0.1 * hit.x).abs() < 1.0e-6);
+}
+
+#[test]
+fn test_intersect_dem_range() {
+    let dem = sloped_dem();
+    assert_eq!(dem.elevation_range(), Some((-2.0, 2.0)));
+
+    // An orbital-scale range only marches the part of the ray over the DEM
+    let ray = LookVector {
+        origin: Vector::new(-1000.0, 2.0, 505.0),
+        look_direction: Vector::new(1.0, 0.0, -0.5),
+    };
+    let hit = ray.intersect_dem(&dem, 1.0e12).unwrap();
+    assert!((hit.z - 0.1 * hit.x).abs() < 1.0e-6);
+    assert!(hit.distance_to(&ray.intersect_dem(&dem, 2000.0).unwrap()) < 1.0e-9);
+
+    for max_range in [f64::INFINITY, f64::NAN, -1.0, 0.0] {
+        assert!(ray.intersect_dem(&dem, max_range).is_none());
+    }
+
+    // A ray that never crosses the DEM's bounds
+    let above = LookVector {
+        origin: Vector::new(0.0, 0.0, 10.0),
+        look_direction: Vector::new(1.0, 0.0, 0.0),
+    };
+    assert!(above.intersect_dem(&dem, 1.0e12).is_none());
+}
+
+#[test]
+fn test_intersect_flat_dem() {
+    let mut elevation = ImageBuffer::new(11, 11).unwrap();
+    elevation.put(0, 0, 1.0);
+    elevation.put_mask(0, 0, false);
+    let dem = Dem::new(elevation, 0.0, 0.0, 1.0, 1.0).unwrap();
+    assert_eq!(dem.elevation_range(), Some((0.0, 0.0)));
+
+    let ray = LookVector {
+        origin: Vector::new(2.0, 5.0, 3.0),
+        look_direction: Vector::new(1.0, 0.0, -1.0),
+    };
+    let hit = ray.intersect_dem(&dem, 1.0e12).unwrap();
+    assert!(hit.distance_to(&Vector::new(5.0, 5.0, 0.0)) < 1.0e-6);
+}
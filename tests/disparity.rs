@@ -0,0 +1,134 @@
+use sciimg::{
+    disparity::{self, DisparityOptions},
+    imagebuffer::ImageBuffer,
+};
+
+const WIDTH: usize = 96;
+const HEIGHT: usize = 32;
+
+// Smooth, non-repeating texture sampled at fractional horizontal positions
+fn texture(u: f64, y: usize) -> f32 {
+    let y = y as f64;
+    (1000.0
+        + 300.0 * (u * 0.61 + y * 0.37).sin()
+        + 200.0 * (u * 1.37 - y * 0.83).cos()
+        + 150.0 * (u * 0.23 * (1.0 + y * 0.05)).sin()
+        + 100.0 * (u * 2.11 + y * 1.9).sin()) as f32
+}
+
+// Pair with the right image shifted such that left(x) matches right(x - disparity)
+fn stereo_pair(disparity: f64) -> (ImageBuffer, ImageBuffer) {
+    let mut left = ImageBuffer::new(WIDTH, HEIGHT).unwrap();
+    let mut right = ImageBuffer::new(WIDTH, HEIGHT).unwrap();
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            left.put(x, y, texture(x as f64, y));
+            right.put(x, y, texture(x as f64 + disparity, y));
+        }
+    }
+    (left, right)
+}
+
+#[test]
+fn test_integer_disparity() {
+    let (left, right) = stereo_pair(6.0);
+    let options = DisparityOptions {
+        max_disparity: 16,
+        subpixel: false,
+        ..Default::default()
+    };
+
+    let disparity = disparity::compute_disparity(&left, &right, &options).unwrap();
+    assert_eq!(disparity.width, WIDTH);
+
+    let mut valid = 0;
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            // Pixels whose true match lies within the border of the right image can only find
+            // a neighboring match
+            if x >= 9 && disparity.get_mask_at_point(x, y) {
+                valid += 1;
+                assert_eq!(disparity.get(x, y), 6.0);
+            }
+        }
+    }
+    assert!(valid > (WIDTH - 16) * (HEIGHT - 6) * 9 / 10);
+
+    // Window incomplete along the borders, and no match visible in the right image
+    assert!(!disparity.get_mask_at_point(20, 2));
+    assert!(!disparity.get_mask_at_point(5, 16));
+    assert!(disparity.get_mask_at_point(20, 16));
+    assert_eq!(disparity.get(5, 16), 0.0);
+}
+
+#[test]
+fn test_subpixel_disparity() {
+    let (left, right) = stereo_pair(4.4);
+    let options = DisparityOptions {
+        window_size: 9,
+        max_disparity: 12,
+        ..Default::default()
+    };
+
+    let disparity = disparity::compute_disparity(&left, &right, &options).unwrap();
+    let mut errors = vec![];
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            if disparity.get_mask_at_point(x, y) {
+                errors.push((disparity.get(x, y) - 4.4).abs());
+            }
+        }
+    }
+    assert!(!errors.is_empty());
+    let mean_error = errors.iter().sum::<f32>() / errors.len() as f32;
+    assert!(mean_error < 0.2, "Mean sub-pixel error: {}", mean_error);
+}
+
+#[test]
+fn test_invalid_matches_masked() {
+    let (left, mut right) = stereo_pair(3.0);
+
+    // Featureless block in the right image, and a masked out pixel in the left
+    for y in 10..20 {
+        for x in 40..60 {
+            right.put(x, y, 500.0);
+        }
+    }
+    let mut left_masked = left.clone();
+    left_masked.put_mask(70, 25, false);
+
+    let options = DisparityOptions {
+        max_disparity: 8,
+        ..Default::default()
+    };
+    let disparity = disparity::compute_disparity(&left_masked, &right, &options).unwrap();
+    assert!(!disparity.get_mask_at_point(50, 15));
+    assert!(!disparity.get_mask_at_point(70, 25));
+    assert!(disparity.get_mask_at_point(30, 5));
+    assert!((disparity.get(30, 5) - 3.0).abs() < 0.25);
+
+    // Without the consistency check, every complete window finds some best match
+    let unchecked = disparity::compute_disparity(
+        &left,
+        &right,
+        &DisparityOptions {
+            max_lr_difference: None,
+            min_correlation: -1.0,
+            ..options
+        },
+    )
+    .unwrap();
+    assert!(unchecked.get_mask_at_point(80, 15));
+
+    let bad_window = DisparityOptions {
+        window_size: 4,
+        ..Default::default()
+    };
+    assert!(disparity::compute_disparity(&left, &right, &bad_window).is_err());
+    assert!(disparity::compute_disparity(
+        &left,
+        &ImageBuffer::new(WIDTH, HEIGHT + 1).unwrap(),
+        &Default::default()
+    )
+    .is_err());
+}
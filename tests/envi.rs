@@ -0,0 +1,137 @@
+mod common;
+
+use sciimg::{
+    binfilereader::{BinFileReader, Endian},
+    enums::ImageMode,
+    envi,
+    error::SciImgError,
+    image::Image,
+    imagebuffer::ImageBuffer,
+    raster::{Interleave, SampleType},
+};
+use std::fs;
+
+#[test]
+fn test_write_read_cube() {
+    let out_path = common::test_output_path("sciimg_envi_tests", "test_cube.img");
+    let out_path = out_path.to_str().unwrap();
+
+    let mut img = Image::new_with_bands(5, 4, 6, ImageMode::U16BIT).unwrap();
+    for b in 0..6 {
+        for y in 0..4 {
+            for x in 0..5 {
+                img.put(
+                    x,
+                    y,
+                    b as f32 * 1000.0 + y as f32 * 10.0 + x as f32 + 0.5,
+                    b,
+                );
+            }
+        }
+    }
+
+    let mut header = envi::Header::for_image(&img, SampleType::Float32);
+    header.interleave = Interleave::SampleInterleaved;
+    header.wavelength = vec![442.0, 528.0, 605.0, 677.0, 754.0, 866.0];
+    header.wavelength_units = Some(String::from("Nanometers"));
+    header.set("sensor type", "Mastcam-Z");
+    envi::write_image_with_header(&img, out_path, &header).unwrap();
+
+    let header_path = common::test_output_path("sciimg_envi_tests", "test_cube.hdr");
+    let header_path = header_path.to_str().unwrap();
+    assert!(envi::is_envi_file(out_path));
+    assert!(envi::is_envi_file(header_path));
+
+    for path in [out_path, header_path] {
+        let (read, header) = envi::read_image_with_header(path).unwrap();
+        assert_eq!(read.num_bands(), 6);
+        assert_eq!(read.get_band(5).get(4, 3), 5034.5);
+        assert_eq!(header.interleave, Interleave::SampleInterleaved);
+        assert_eq!(header.band_wavelength(2), Some(605.0));
+        assert_eq!(header.wavelength_units.as_deref(), Some("Nanometers"));
+        assert_eq!(header.get("Sensor Type"), Some("Mastcam-Z"));
+    }
+
+    let read = Image::open(header_path).unwrap();
+    assert_eq!(read.get_band(3).get(0, 0), 3000.5);
+    assert_eq!(read.get_metadata().band_wavelength(5), Some(866.0));
+}
+
+#[test]
+fn test_read_bil_big_endian() {
+    let header_path = common::test_output_path("sciimg_envi_tests", "test_bil.hdr");
+    fs::write(
+        &header_path,
+        "ENVI\n\
+         description = {\n  Synthetic cube}\n\
+         samples = 3\n\
+         lines    = 2\n\
+         bands = 2\n\
+         header offset = 4\n\
+         file type = ENVI Standard\n\
+         data type = 2\n\
+         interleave = bil\n\
+         byte order = 1\n\
+         band names = {\n L0,\n R0}\n\
+         wavelength = {\n 440.5, 530.0 }\n",
+    )
+    .unwrap();
+
+    let mut data: Vec<u8> = vec![0; 4];
+    for v in [1_i16, 2, 3, 100, 200, 300, -4, 5, 6, 400, 500, -600] {
+        data.extend_from_slice(&v.to_be_bytes());
+    }
+    let data_path = common::test_output_path("sciimg_envi_tests", "test_bil.dat");
+    fs::write(&data_path, data).unwrap();
+
+    let header = envi::Header::from_file(header_path.to_str().unwrap()).unwrap();
+    assert_eq!(header.description.as_deref(), Some("Synthetic cube"));
+    assert_eq!(header.byte_order, Endian::BigEndian);
+    assert_eq!(header.band_names, vec!["L0", "R0"]);
+    assert_eq!(header.wavelength, vec![440.5, 530.0]);
+
+    let img = envi::read_image(data_path.to_str().unwrap()).unwrap();
+    assert_eq!(img.get_band(0).get(2, 0), 3.0);
+    assert_eq!(img.get_band(1).get(0, 0), 100.0);
+    assert_eq!(img.get_band(0).get(0, 1), -4.0);
+    assert_eq!(img.get_band(1).get(2, 1), -600.0);
+
+    let buffer = ImageBuffer::from_file(header_path.to_str().unwrap()).unwrap();
+    assert_eq!(buffer.get(1, 1), 5.0);
+}
+
+#[test]
+fn test_write_default_8bit() {
+    let out_path = common::test_output_path("sciimg_envi_tests", "test_8bit");
+    let out_path = out_path.to_str().unwrap();
+
+    let mut img = Image::new_with_bands(2, 2, 4, ImageMode::U8BIT).unwrap();
+    img.put(1, 1, 254.6, 3);
+    envi::write_image(&img, out_path).unwrap();
+    assert_eq!(fs::metadata(out_path).unwrap().len(), 16);
+
+    let read = Image::open(out_path).unwrap();
+    assert_eq!(read.get_mode(), ImageMode::U8BIT);
+    assert_eq!(read.get_band(3).get(1, 1), 255.0);
+}
+
+#[test]
+fn test_read_empty_data_file() {
+    let out_path = common::test_output_path("sciimg_envi_tests", "test_empty.img");
+    let out_path = out_path.to_str().unwrap();
+
+    let img = Image::new_with_bands(2, 2, 1, ImageMode::U8BIT).unwrap();
+    envi::write_image(&img, out_path).unwrap();
+    fs::write(out_path, []).unwrap();
+
+    let header_path = common::test_output_path("sciimg_envi_tests", "test_empty.hdr");
+    assert!(matches!(
+        Image::open(header_path.to_str().unwrap()),
+        Err(SciImgError::InvalidFormat(_))
+    ));
+    assert!(envi::read_image(out_path).is_err());
+    assert!(matches!(
+        BinFileReader::open(out_path),
+        Err(SciImgError::InvalidFormat(_))
+    ));
+}
@@ -0,0 +1,200 @@
+mod common;
+
+use sciimg::{enums::ImageMode, fits, image::Image, imagebuffer::ImageBuffer, pds3::Value};
+use std::fs;
+
+fn header_block(cards: &[&str]) -> Vec<u8> {
+    let mut out: Vec<u8> = vec![];
+    for c in cards.iter().chain(["END"].iter()) {
+        out.extend(format!("{:<80}", c).bytes());
+    }
+    out.resize(fits::padded_len(out.len()), b' ');
+    out
+}
+
+#[test]
+fn test_write_read_float_cube() {
+    let out_path = common::test_output_path("sciimg_fits_tests", "test_cube.fits");
+    let out_path = out_path.to_str().unwrap();
+
+    let mut img = Image::new_with_bands(5, 4, 3, ImageMode::U16BIT).unwrap();
+    for b in 0..3 {
+        for y in 0..4 {
+            for x in 0..5 {
+                img.put(
+                    x,
+                    y,
+                    b as f32 * 1000.0 + y as f32 * 10.0 + x as f32 + 0.25,
+                    b,
+                );
+            }
+        }
+    }
+
+    fits::write_image_with_cards(
+        &img,
+        out_path,
+        &[
+            fits::Card::new("EXPTIME", Value::Real(1.5), Some("seconds")),
+            fits::Card::new("OBJECT", Value::Text(String::from("Jupiter's moons")), None),
+            fits::Card::commentary("HISTORY", "Calibrated"),
+        ],
+    )
+    .unwrap();
+    assert!(fits::is_fits_file(out_path));
+    assert_eq!(fs::metadata(out_path).unwrap().len() % 2880, 0);
+
+    let hdus = fits::read_hdus(out_path).unwrap();
+    assert_eq!(hdus.len(), 1);
+    let header = &hdus[0].header;
+    assert_eq!(header.get_i64("BITPIX"), Some(-32));
+    assert_eq!(header.get_i64("NAXIS"), Some(3));
+    assert_eq!(header.get_f64("EXPTIME"), Some(1.5));
+    assert_eq!(header.get_str("OBJECT"), Some("Jupiter's moons"));
+    assert!(header
+        .cards
+        .iter()
+        .any(|c| c.keyword == "HISTORY" && c.comment.as_deref() == Some("Calibrated")));
+
+    let read = Image::open(out_path).unwrap();
+    assert_eq!(read.width, 5);
+    assert_eq!(read.height, 4);
+    assert_eq!(read.num_bands(), 3);
+    assert_eq!(read.get_band(0).get(0, 0), 0.25);
+    assert_eq!(read.get_band(2).get(4, 3), 2034.25);
+}
+
+#[test]
+fn test_read_scaled_16bit_extension() {
+    let mut data = header_block(&[
+        "SIMPLE  =                    T",
+        "BITPIX  =                    8",
+        "NAXIS   =                    0",
+        "EXTEND  =                    T",
+    ]);
+    data.extend(header_block(&[
+        "XTENSION= 'IMAGE   '           / image extension",
+        "BITPIX  =                   16",
+        "NAXIS   =                    2",
+        "NAXIS1  =                    3",
+        "NAXIS2  =                    2",
+        "PCOUNT  =                    0",
+        "GCOUNT  =                    1",
+        "BZERO   =                32768",
+        "BSCALE  =                  1.0",
+    ]));
+    for v in [-32768_i16, -32767, 0, 1, 100, 32767] {
+        data.extend_from_slice(&v.to_be_bytes());
+    }
+    data.resize(fits::padded_len(data.len()), 0);
+
+    let img_path = common::test_output_path("sciimg_fits_tests", "test_extension.fits");
+    let img_path = img_path.to_str().unwrap();
+    fs::write(img_path, data).unwrap();
+
+    let hdus = fits::read_hdus(img_path).unwrap();
+    assert_eq!(hdus.len(), 2);
+    assert!(!hdus[0].is_image());
+    assert!(hdus[1].is_image());
+
+    let img = fits::read_image(img_path).unwrap();
+    assert_eq!(img.width, 3);
+    assert_eq!(img.height, 2);
+    assert_eq!(img.num_bands(), 1);
+    assert_eq!(img.get_band(0).get(0, 0), 0.0);
+    assert_eq!(img.get_band(0).get(2, 0), 32768.0);
+    assert_eq!(img.get_band(0).get(2, 1), 65535.0);
+
+    let buffer = ImageBuffer::from_file(img_path).unwrap();
+    assert_eq!(buffer.get(1, 1), 32868.0);
+}
+
+#[test]
+fn test_read_8bit_primary() {
+    let mut data = header_block(&[
+        "SIMPLE  =                    T",
+        "BITPIX  =                    8",
+        "NAXIS   =                    2",
+        "NAXIS1  =                    2",
+        "NAXIS2  =                    2",
+    ]);
+    data.extend([10_u8, 20, 30, 40]);
+    data.resize(fits::padded_len(data.len()), 0);
+
+    let img_path = common::test_output_path("sciimg_fits_tests", "test_8bit.fits");
+    fs::write(&img_path, data).unwrap();
+
+    let img = fits::read_image(img_path.to_str().unwrap()).unwrap();
+    assert_eq!(img.get_mode(), ImageMode::U8BIT);
+    assert_eq!(img.get_band(0).get(1, 1), 40.0);
+}
+
+#[test]
+fn test_write_processing_history() {
+    let out_path = common::test_output_path("sciimg_fits_tests", "test_history.fits");
+    let out_path = out_path.to_str().unwrap();
+
+    let mut img = Image::new_with_bands(4, 4, 1, ImageMode::U16BIT).unwrap();
+    img.normalize_to_12bit_with_max(4095.0, 1000.0);
+    img.normalize_between(0.0, 1.0);
+    fits::write_image(&img, out_path).unwrap();
+
+    let hdus = fits::read_hdus(out_path).unwrap();
+    let history: Vec<&str> = hdus[0]
+        .header
+        .cards
+        .iter()
+        .filter(|c| c.keyword == "HISTORY")
+        .filter_map(|c| c.comment.as_deref())
+        .collect();
+    assert_eq!(
+        history,
+        vec![
+            "normalize_to_12bit_with_max(max=1000, max12bit=4095)",
+            "normalize_between(max=1, min=0)"
+        ]
+    );
+}
+
+#[test]
+fn test_write_rejects_invalid_cards() {
+    let out_path = common::test_output_path("sciimg_fits_tests", "test_invalid_cards.fits");
+    let out_path = out_path.to_str().unwrap();
+    let img = Image::new_with_bands(4, 4, 1, ImageMode::U16BIT).unwrap();
+
+    for card in [
+        fits::Card::new("OBJECT", Value::Text("é".repeat(40)), None),
+        fits::Card::new("OBJECT", Value::Text("a".repeat(75)), None),
+        fits::Card::new("OBJECT", Value::Text("it's".repeat(14)), None),
+        fits::Card::new("OBJECT", Value::Text(String::from("Mars")), Some("été")),
+        fits::Card::new("OBJÉCT", Value::Text(String::from("Mars")), None),
+        fits::Card::new("LONGKEYWORD", Value::Integer(1), None),
+    ] {
+        assert!(fits::write_image_with_cards(&img, out_path, &[card]).is_err());
+    }
+}
+
+#[test]
+fn test_write_long_card_values() {
+    let out_path = common::test_output_path("sciimg_fits_tests", "test_long_cards.fits");
+    let out_path = out_path.to_str().unwrap();
+    let img = Image::new_with_bands(4, 4, 1, ImageMode::U16BIT).unwrap();
+
+    // The longest value which fits is kept whole, with the comment cut to the card width
+    let text = "a".repeat(68);
+    let comment = "c".repeat(40);
+    let cards = [
+        fits::Card::new("OBJECT", Value::Text(text.clone()), Some(&comment)),
+        fits::Card::new("TARGET", Value::Text(String::from("Mars")), Some(&comment)),
+    ];
+    fits::write_image_with_cards(&img, out_path, &cards).unwrap();
+
+    let hdus = fits::read_hdus(out_path).unwrap();
+    let header = &hdus[0].header;
+    assert_eq!(header.get_str("OBJECT"), Some(text.as_str()));
+    assert_eq!(header.get_str("TARGET"), Some("Mars"));
+    let target = header.cards.iter().find(|c| c.keyword == "TARGET").unwrap();
+    assert_eq!(target.comment.as_deref(), Some(comment.as_str()));
+    let object = header.cards.iter().find(|c| c.keyword == "OBJECT").unwrap();
+    assert!(object.comment.is_none());
+}
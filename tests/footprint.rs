@@ -0,0 +1,105 @@
+use sciimg::{
+    camera::{cahv::Cahv, model::CameraModel},
+    footprint::{self, Surface},
+    vector::Vector,
+};
+
+// Camera 10 meters up looking straight down, with H along +X and V along +Y
+fn nadir_camera(c: Vector) -> CameraModel {
+    let a = Vector::new(0.0, 0.0, -1.0);
+    CameraModel::new(Box::new(Cahv {
+        c,
+        a,
+        h: Vector::new(100.0, 0.0, 0.0).add(&a.scale(50.0)),
+        v: Vector::new(0.0, 100.0, 0.0).add(&a.scale(40.0)),
+    }))
+}
+
+fn ground() -> Surface {
+    Surface::Plane {
+        point: Vector::default(),
+        normal: Vector::new(0.0, 0.0, 1.0),
+    }
+}
+
+#[test]
+fn test_footprint_on_plane() {
+    let model = nadir_camera(Vector::new(0.0, 0.0, 10.0));
+    let footprint = footprint::compute_footprint(&model, 101, 81, &ground(), 3).unwrap();
+
+    assert!(footprint.complete);
+    assert_eq!(footprint.polygon.len(), 8);
+    let expected = [
+        (-5.0, -4.0),
+        (0.0, -4.0),
+        (5.0, -4.0),
+        (5.0, 0.0),
+        (5.0, 4.0),
+        (0.0, 4.0),
+        (-5.0, 4.0),
+        (-5.0, 0.0),
+    ];
+    for (p, (x, y)) in footprint.polygon.iter().zip(expected.iter()) {
+        assert!(p.distance_to(&Vector::new(*x, *y, 0.0)) < 1.0e-9);
+    }
+
+    let corners = footprint.frustum.corners_at(141.0_f64.sqrt());
+    assert!(corners[0].distance_to(&footprint.polygon[0]) < 1.0e-9);
+    assert!(corners[2].distance_to(&footprint.polygon[4]) < 1.0e-9);
+    assert!((footprint.frustum.corners[1].len() - 1.0).abs() < 1.0e-12);
+
+    let pixel_angle = (1.0_f64 / 100.0).atan();
+    assert!((footprint.fov_horizontal - pixel_angle * 101.0).abs() < 1.0e-12);
+    assert!((footprint.fov_vertical - pixel_angle * 81.0).abs() < 1.0e-12);
+}
+
+#[test]
+fn test_footprint_past_horizon() {
+    // Looking out towards the horizon, with the top of the image above it
+    let a = Vector::new(1.0, 0.0, -0.1).normalized();
+    let v_dir = Vector::new(0.0, 0.0, -1.0)
+        .subtract(&a.scale(a.dot_product(&Vector::new(0.0, 0.0, -1.0))))
+        .normalized();
+    let model = CameraModel::new(Box::new(Cahv {
+        c: Vector::new(0.0, 0.0, 2.0),
+        a,
+        h: Vector::new(0.0, 100.0, 0.0).add(&a.scale(50.0)),
+        v: v_dir.scale(100.0).add(&a.scale(40.0)),
+    }));
+
+    let footprint = footprint::compute_footprint(&model, 101, 81, &ground(), 5).unwrap();
+    assert!(!footprint.complete);
+    assert!(!footprint.polygon.is_empty());
+    assert!(footprint.polygon.len() < 16);
+    for p in footprint.polygon.iter() {
+        assert!(p.z.abs() < 1.0e-9 && p.x > 0.0);
+    }
+}
+
+#[test]
+fn test_footprint_on_sphere() {
+    // Orbital camera 400 km above a Mars-sized sphere
+    let model = nadir_camera(Vector::new(0.0, 0.0, 3796.19));
+    let surface = Surface::Sphere {
+        center: Vector::default(),
+        radius: 3396.19,
+    };
+    let footprint = footprint::compute_footprint(&model, 101, 81, &surface, 2).unwrap();
+
+    assert!(footprint.complete);
+    assert_eq!(footprint.polygon.len(), 4);
+    for p in footprint.polygon.iter() {
+        assert!((p.len() - 3396.19).abs() < 1.0e-6);
+    }
+
+    // The footprint of a curved surface is larger than that of the tangent plane
+    let tangent = Surface::Plane {
+        point: Vector::new(0.0, 0.0, 3396.19),
+        normal: Vector::new(0.0, 0.0, 1.0),
+    };
+    let flat = footprint::compute_footprint(&model, 101, 81, &tangent, 2).unwrap();
+    assert!(footprint.polygon[0].x.abs() > flat.polygon[0].x.abs());
+
+    assert!(footprint::compute_footprint(&model, 101, 81, &surface, 1).is_err());
+    assert!(footprint::compute_footprint(&CameraModel::default(), 101, 81, &surface, 2).is_err());
+}
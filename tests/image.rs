@@ -1,4 +1,17 @@
-use sciimg::{decompanding, enums, enums::ImageMode, image::Image, imagebuffer::ImageBuffer};
+use sciimg::{
+    camera::{
+        cahv::Cahv,
+        model::{CameraModel, ModelType},
+    },
+    decompanding, enums,
+    enums::ImageMode,
+    error::SciImgError,
+    image::Image,
+    imagebuffer::ImageBuffer,
+    metadata::Metadata,
+    pngio,
+    vector::Vector,
+};
 
 const M20_ZCAM_ECM_GRAY: &str =
     "tests/testdata/ZL0_0038_0670307360_057ECM_N0031392ZCAM08007_1100LUJ.png";
@@ -117,3 +130,217 @@ fn test_rgbimage_math_1band() {
     img.divide_from_each(&b0);
     assert_eq!(img.get_band(0).get(100, 100), 0.5);
 }
+
+#[test]
+fn test_open_errors() {
+    assert!(matches!(
+        Image::open("tests/testdata/does_not_exist.png"),
+        Err(SciImgError::FileNotFound(_))
+    ));
+    assert!(matches!(
+        ImageBuffer::from_file("tests/testdata/does_not_exist.png"),
+        Err(SciImgError::FileNotFound(_))
+    ));
+
+    let dir = std::env::temp_dir().join("sciimg_image_tests");
+    std::fs::create_dir_all(&dir).unwrap();
+    let garbage = dir.join("garbage.png");
+    std::fs::write(&garbage, b"This is not an image").unwrap();
+    assert!(Image::open(garbage.to_str().unwrap()).is_err());
+    assert!(ImageBuffer::from_file(garbage.to_str().unwrap()).is_err());
+}
+
+#[test]
+fn test_save_errors() {
+    let img = Image::new_with_bands(10, 10, 2, ImageMode::U8BIT).unwrap();
+    let dir = std::env::temp_dir().join("sciimg_image_tests");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    assert!(matches!(
+        img.save(dir.join("two_band.png").to_str().unwrap()),
+        Err(SciImgError::UnsupportedBandCount(2))
+    ));
+    assert!(matches!(
+        img.save_mono(dir.join("band.png").to_str().unwrap(), 5),
+        Err(SciImgError::BandOutOfBounds(5))
+    ));
+    assert!(matches!(
+        img.save_rgb(dir.join("rgb.png").to_str().unwrap()),
+        Err(SciImgError::UnsupportedBandCount(2))
+    ));
+    assert!(matches!(
+        img.save_mono("/does/not/exist/band.png", 0),
+        Err(SciImgError::UnwritablePath(_))
+    ));
+    assert!(matches!(
+        img.get_band(0)
+            .save("/does/not/exist/band.png", ImageMode::U8BIT),
+        Err(SciImgError::UnwritablePath(_))
+    ));
+
+    assert!(img
+        .save_mono(dir.join("band.png").to_str().unwrap(), 1)
+        .is_ok());
+}
+
+#[test]
+fn test_metadata_propagation() {
+    let model = CameraModel::new(Box::new(Cahv {
+        c: Vector::new(0.9, 0.1, -1.9),
+        a: Vector::new(0.99, 0.05, 0.1),
+        h: Vector::new(100.0, 800.0, 50.0),
+        v: Vector::new(-20.0, 50.0, 810.0),
+    }));
+
+    let mut metadata = Metadata::with_camera_model(&model);
+    metadata.instrument = Some(String::from("MCZ_LEFT"));
+    metadata.filter = Some(String::from("L0"));
+    metadata.exposure_time = Some(12.5);
+    metadata.sol = Some(53);
+
+    let mut img = Image::new_with_bands(64, 64, 1, ImageMode::U16BIT).unwrap();
+    img.set_metadata(&metadata);
+
+    img.debayer();
+    img.crop(8, 8, 48, 48);
+    img.resize_to(24, 24);
+    img.rotate(0.1);
+
+    let m = img.get_metadata();
+    assert_eq!(m.instrument.as_deref(), Some("MCZ_LEFT"));
+    assert_eq!(m.filter.as_deref(), Some("L0"));
+    assert_eq!(m.sol, Some(53));
+
+    // A point projects to where the crop, resize and rotation moved its pixel
+    let p = Vector::new(4.0, 0.3, 0.2);
+    let ls = model.xyz_to_ls(&p, false);
+    let (s, l) = ((ls.sample - 8.0) * 0.5 - 0.25, (ls.line - 8.0) * 0.5 - 0.25);
+    let (sin, cos) = (0.1_f32 as f64).sin_cos();
+    let expected_s = cos * (s - 12.0) - sin * (l - 12.0) + 12.0;
+    let expected_l = sin * (s - 12.0) + cos * (l - 12.0) + 12.0;
+
+    let ls_t = m.camera_model.as_ref().unwrap().xyz_to_ls(&p, false);
+    assert!((ls_t.sample - expected_s).abs() < 1e-6);
+    assert!((ls_t.line - expected_l).abs() < 1e-6);
+}
+
+#[test]
+fn test_metadata_serde() {
+    let mut metadata = Metadata::with_camera_model(&CameraModel::new(Box::new(Cahv {
+        c: Vector::new(1.0, 2.0, 3.0),
+        a: Vector::new(0.0, 1.0, 0.0),
+        h: Vector::new(1000.0, 0.0, 500.0),
+        v: Vector::new(0.0, 500.0, 1000.0),
+    })));
+    metadata.sclk = Some(671642352.5);
+    metadata.wavelengths = vec![630.0, 544.0, 480.0];
+
+    let json = serde_json::to_string(&metadata).unwrap();
+    assert!(!json.contains("instrument"));
+
+    let read: Metadata = serde_json::from_str(&json).unwrap();
+    assert_eq!(read.sclk, Some(671642352.5));
+    assert_eq!(read.band_wavelength(1), Some(544.0));
+    let model = read.camera_model.unwrap();
+    assert!(model.model_type() == ModelType::CAHV);
+    assert_eq!(model.c(), Vector::new(1.0, 2.0, 3.0));
+
+    let empty: Metadata = serde_json::from_str("{}").unwrap();
+    assert!(empty.camera_model.is_none());
+
+    // An invalid model doesn't prevent the rest of the metadata from being written
+    metadata.camera_model = Some(CameraModel::default());
+    let json = serde_json::to_string(&metadata).unwrap();
+    assert!(!json.contains("camera_model"));
+    assert_eq!(
+        serde_json::to_string(&CameraModel::default()).unwrap(),
+        "null"
+    );
+    let model: CameraModel = serde_json::from_str("null").unwrap();
+    assert!(!model.is_valid());
+
+    let out_path = std::env::temp_dir().join("sciimg_image_invalid_model.png");
+    let out_path = out_path.to_str().unwrap();
+    let mut img = Image::new_with_bands(4, 4, 1, ImageMode::U8BIT).unwrap();
+    img.set_metadata(&metadata);
+    img.save_with_sidecar(out_path).unwrap();
+    let read = Image::open_with_sidecar(out_path).unwrap();
+    assert!(read.get_metadata().camera_model.is_none());
+    assert_eq!(read.get_metadata().sclk, Some(671642352.5));
+}
+
+fn history_operations(img: &Image) -> Vec<String> {
+    img.get_metadata()
+        .history
+        .iter()
+        .map(|s| s.operation.clone())
+        .collect()
+}
+
+#[test]
+fn test_processing_history() {
+    let mut img = Image::new_with_bands(64, 64, 1, ImageMode::U8BIT).unwrap();
+    for y in 0..64 {
+        for x in 0..64 {
+            img.put(x, y, ((x * 7 + y * 3) % 200) as f32, 0);
+        }
+    }
+    let mut flat = Image::new_with_bands(64, 64, 1, ImageMode::U16BIT).unwrap();
+    flat.set_band(&ImageBuffer::new_with_fill(64, 64, 1.0).unwrap(), 0);
+
+    img.decompand(&decompanding::NSYT_ILT);
+    img.flatfield(&flat);
+    img.hot_pixel_correction(5, 2.5);
+    img.debayer();
+    img.normalize_to_8bit();
+
+    assert_eq!(
+        history_operations(&img),
+        vec![
+            "decompand",
+            "flat_field",
+            "hot_pixel_correction",
+            "debayer",
+            "normalize_to_8bit"
+        ]
+    );
+    let steps = &img.get_metadata().history;
+    assert_eq!(steps[0].to_string(), "decompand(ilt=NSYT_ILT)");
+    assert_eq!(
+        steps[2].to_string(),
+        "hot_pixel_correction(threshold=2.5, window_size=5)"
+    );
+    assert_eq!(steps[3].parameters.get("method").unwrap(), "AMaZE");
+
+    let json = img.get_metadata().history_to_json().unwrap();
+    assert_eq!(Metadata::history_from_json(&json).unwrap(), *steps);
+
+    let out_path = std::env::temp_dir().join("sciimg_image_history.png");
+    let out_path = out_path.to_str().unwrap();
+    img.save(out_path).unwrap();
+    let read = Image::open(out_path).unwrap();
+    assert_eq!(read.get_metadata().history, *steps);
+    assert_eq!(
+        read.get_band(1).get(5, 5),
+        img.get_band(1).get(5, 5).round()
+    );
+}
+
+#[test]
+fn test_open_png_with_foreign_history() {
+    // Pixels remain readable when the history chunk isn't one written by sciimg
+    let out_path = std::env::temp_dir().join("sciimg_image_foreign_history.png");
+    let out_path = out_path.to_str().unwrap();
+    let mut buffer = image::GrayImage::new(3, 2);
+    buffer.put_pixel(1, 1, image::Luma([42]));
+    pngio::write_image(
+        &image::DynamicImage::ImageLuma8(buffer),
+        out_path,
+        &[(pngio::HISTORY_KEYWORD, String::from("{not a history"))],
+    )
+    .unwrap();
+
+    let read = Image::open(out_path).unwrap();
+    assert!(read.get_metadata().history.is_empty());
+    assert_eq!(read.get_band(0).get(1, 1), 42.0);
+}
@@ -0,0 +1,255 @@
+use sciimg::{
+    camera::{
+        cahv::Cahv,
+        model::{CameraModel, ImageCoordinate},
+    },
+    enums::ImageMode,
+    image::Image,
+    matrix::Matrix,
+    mosaic::{self, BlendMode, MosaicOptions, Projection},
+    reproject::Interpolation,
+    vector::Vector,
+};
+use std::f64::consts::FRAC_PI_2;
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 48;
+
+// Pinhole camera at the origin with a 100 pixel focal length, pointed along an azimuth and
+// elevation with the image upright for +Z up
+fn pointed_model(azimuth: f64, elevation: f64) -> CameraModel {
+    let a = mosaic::az_el_to_vector(azimuth, elevation);
+    let h_dir = Vector::new(azimuth.sin(), -azimuth.cos(), 0.0);
+    let v_dir = a.cross_product(&h_dir);
+    CameraModel::new(Box::new(Cahv {
+        c: Vector::default(),
+        a,
+        h: h_dir.scale(100.0).add(&a.scale((WIDTH - 1) as f64 / 2.0)),
+        v: v_dir.scale(100.0).add(&a.scale((HEIGHT - 1) as f64 / 2.0)),
+    }))
+}
+
+fn filled_image(value: f32) -> Image {
+    let mut img = Image::new_with_bands(WIDTH, HEIGHT, 1, ImageMode::U12BIT).unwrap();
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            img.put(x, y, value, 0);
+        }
+    }
+    img
+}
+
+fn ramp_image() -> Image {
+    let mut img = Image::new_with_bands(WIDTH, HEIGHT, 1, ImageMode::U12BIT).unwrap();
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            img.put(x, y, (x * 10 + y) as f32, 0);
+        }
+    }
+    img
+}
+
+fn cylindrical_options(blend: BlendMode) -> MosaicOptions {
+    MosaicOptions {
+        projection: Projection::Cylindrical {
+            min_azimuth: -0.4,
+            max_azimuth: 0.7,
+            min_elevation: -0.3,
+            max_elevation: 0.3,
+        },
+        resolution: 0.01,
+        blend,
+        interpolation: Interpolation::Bilinear,
+        projection_range: None,
+    }
+}
+
+#[test]
+fn test_az_el_round_trip() {
+    for (az, el) in [(0.0, 0.0), (1.2, -0.4), (-2.5, 1.1), (3.0, -1.5)] {
+        let v = mosaic::az_el_to_vector(az, el);
+        assert!((v.len() - 1.0).abs() < 1.0e-12);
+        assert!((v.get_az() - az).abs() < 1.0e-12);
+        assert!((v.get_el() - el).abs() < 1.0e-12);
+    }
+}
+
+#[test]
+fn test_cylindrical_blending() {
+    let frames = vec![
+        (filled_image(100.0), pointed_model(0.0, 0.0)),
+        (filled_image(200.0), pointed_model(0.3, 0.0)),
+    ];
+    let origin = Vector::default();
+
+    // Columns run from azimuth 0.7 down to -0.4, rows from elevation 0.3 down to -0.3
+    let m = mosaic::build_mosaic(&frames, &origin, &cylindrical_options(BlendMode::First)).unwrap();
+    assert_eq!((m.width, m.height), (111, 61));
+    assert_eq!(m.get_mode(), ImageMode::U12BIT);
+    assert!(m.get_alpha_at(70, 30));
+    assert_eq!(m.get_band(0).get(70, 30), 100.0);
+    assert_eq!(m.get_band(0).get(35, 30), 200.0);
+    assert_eq!(m.get_band(0).get(55, 30), 100.0);
+    assert!(!m.get_alpha_at(0, 30));
+    assert!(!m.get_alpha_at(110, 30));
+    assert!(!m.get_alpha_at(70, 0));
+
+    let step = m.get_metadata().history.last().unwrap();
+    assert_eq!(step.operation, "mosaic");
+    assert_eq!(step.parameters["projection"], "cylindrical");
+    assert_eq!(step.parameters["blend"], "first");
+    assert!(m.get_metadata().camera_model.is_none());
+
+    let m = mosaic::build_mosaic(&frames, &origin, &cylindrical_options(BlendMode::Last)).unwrap();
+    assert_eq!(m.get_band(0).get(55, 30), 200.0);
+    assert_eq!(m.get_band(0).get(75, 30), 100.0);
+
+    let m =
+        mosaic::build_mosaic(&frames, &origin, &cylindrical_options(BlendMode::Average)).unwrap();
+    assert!((m.get_band(0).get(55, 30) - 150.0).abs() < 1.0e-3);
+    assert!((m.get_band(0).get(65, 30) - 150.0).abs() < 1.0e-3);
+
+    // Feathering favors the frame whose center is nearer, meeting halfway between the centers
+    let m =
+        mosaic::build_mosaic(&frames, &origin, &cylindrical_options(BlendMode::Feather)).unwrap();
+    assert!((m.get_band(0).get(55, 30) - 150.0).abs() < 1.0e-2);
+    let v = m.get_band(0).get(65, 30);
+    assert!(v > 100.0 && v < 150.0);
+    let v = m.get_band(0).get(45, 30);
+    assert!(v > 150.0 && v < 200.0);
+}
+
+#[test]
+fn test_cylindrical_orientation() {
+    let frames = vec![(ramp_image(), pointed_model(0.0, 0.0))];
+    let m = mosaic::build_mosaic(
+        &frames,
+        &Vector::default(),
+        &cylindrical_options(BlendMode::Feather),
+    )
+    .unwrap();
+
+    // Left to right and top to bottom in the frame stays so in the mosaic
+    let band = m.get_band(0);
+    assert!(band.get(60, 30) < band.get(70, 30) && band.get(70, 30) < band.get(80, 30));
+    assert!(band.get(70, 20) < band.get(70, 30) && band.get(70, 30) < band.get(70, 40));
+}
+
+#[test]
+fn test_perspective_mosaic() {
+    let model = pointed_model(0.0, 0.0);
+    let frames = vec![(ramp_image(), model.clone())];
+
+    // A perspective view matching the frame reproduces it
+    let options = MosaicOptions {
+        projection: Projection::Perspective {
+            azimuth: 0.0,
+            elevation: 0.0,
+            fov_horizontal: 2.0 * (31.5_f64 / 100.0).atan(),
+            fov_vertical: 2.0 * (23.5_f64 / 100.0).atan(),
+        },
+        resolution: (1.0_f64 / 100.0).atan(),
+        blend: BlendMode::Average,
+        interpolation: Interpolation::Bicubic,
+        projection_range: None,
+    };
+    let m = mosaic::build_mosaic(&frames, &Vector::default(), &options).unwrap();
+    assert_eq!((m.width, m.height), (WIDTH, HEIGHT));
+    for (x, y) in [(0, 0), (31, 23), (63, 47), (10, 40)] {
+        assert!(m.get_alpha_at(x, y));
+        assert!((m.get_band(0).get(x, y) - frames[0].0.get_band(0).get(x, y)).abs() < 0.05);
+    }
+
+    let view = m.get_metadata().camera_model.as_ref().unwrap();
+    let p = Vector::new(10.0, 1.0, -0.5);
+    let expected = model.xyz_to_ls(&p, false);
+    let found = view.xyz_to_ls(&p, false);
+    assert!((found.sample - expected.sample).abs() < 1.0e-6);
+    assert!((found.line - expected.line).abs() < 1.0e-6);
+}
+
+#[test]
+fn test_polar_mosaic() {
+    let model = pointed_model(0.0, -FRAC_PI_2 + 1.0e-9);
+    let frames = vec![(ramp_image(), model.clone())];
+    let options = MosaicOptions {
+        projection: Projection::Polar {
+            max_elevation: -FRAC_PI_2 + 0.2,
+        },
+        resolution: 0.01,
+        ..MosaicOptions::default()
+    };
+    let m = mosaic::build_mosaic(&frames, &Vector::default(), &options).unwrap();
+    assert_eq!((m.width, m.height), (41, 41));
+    assert!(m.get_alpha_at(20, 20));
+    assert!(!m.get_alpha_at(0, 0));
+
+    // Ten pixels right of the nadir looks out along +X, ten pixels up along +Y
+    for (x, y, az) in [(30, 20, 0.0_f64), (20, 10, FRAC_PI_2)] {
+        let direction = mosaic::az_el_to_vector(az, -FRAC_PI_2 + 0.1);
+        let ls = model.xyz_to_ls(&direction, true);
+        let expected = 10.0 * ls.sample + ls.line;
+        assert!((m.get_band(0).get(x, y) as f64 - expected).abs() < 0.05);
+    }
+}
+
+#[test]
+fn test_projection_range_parallax() {
+    // A camera half a meter to the side of the origin sees a nearby point off center
+    let model = pointed_model(0.0, 0.0)
+        .transform(&Matrix::identity(), &Vector::new(0.0, 0.5, 0.0))
+        .unwrap();
+    let frames = vec![(ramp_image(), model.clone())];
+
+    let options = MosaicOptions {
+        projection_range: Some(5.0),
+        ..cylindrical_options(BlendMode::Average)
+    };
+    let m = mosaic::build_mosaic(&frames, &Vector::default(), &options).unwrap();
+    let ls: ImageCoordinate = model.xyz_to_ls(&Vector::new(5.0, 0.0, 0.0), false);
+    let expected = 10.0 * ls.sample + ls.line;
+    assert!((m.get_band(0).get(70, 30) as f64 - expected).abs() < 0.05);
+}
+
+#[test]
+fn test_mosaic_errors() {
+    let origin = Vector::default();
+    let options = cylindrical_options(BlendMode::Feather);
+    assert!(mosaic::build_mosaic(&[], &origin, &options).is_err());
+
+    let two_band = Image::new_with_bands(WIDTH, HEIGHT, 2, ImageMode::U12BIT).unwrap();
+    let frames = vec![
+        (filled_image(1.0), pointed_model(0.0, 0.0)),
+        (two_band, pointed_model(0.3, 0.0)),
+    ];
+    assert!(mosaic::build_mosaic(&frames, &origin, &options).is_err());
+
+    let frames = vec![(filled_image(1.0), pointed_model(0.0, 0.0))];
+    let bad_resolution = MosaicOptions {
+        resolution: 0.0,
+        ..options
+    };
+    assert!(mosaic::build_mosaic(&frames, &origin, &bad_resolution).is_err());
+
+    let bad_bounds = MosaicOptions {
+        projection: Projection::Cylindrical {
+            min_azimuth: 1.0,
+            max_azimuth: 0.0,
+            min_elevation: -0.1,
+            max_elevation: 0.1,
+        },
+        ..options
+    };
+    assert!(mosaic::build_mosaic(&frames, &origin, &bad_bounds).is_err());
+
+    let bad_fov = MosaicOptions {
+        projection: Projection::Perspective {
+            azimuth: 0.0,
+            elevation: 0.0,
+            fov_horizontal: 0.0,
+            fov_vertical: 0.5,
+        },
+        ..options
+    };
+    assert!(mosaic::build_mosaic(&frames, &origin, &bad_fov).is_err());
+}
@@ -0,0 +1,173 @@
+mod common;
+
+use sciimg::{enums::ImageMode, error::SciImgError, image::Image, pds3};
+use std::fs;
+
+const TEST_LABEL: &str = r#"PDS_VERSION_ID = PDS3
+/* File characteristics */
+RECORD_TYPE = FIXED_LENGTH
+RECORD_BYTES = 8
+FILE_RECORDS = 6
+^IMAGE = ("TEST_DETACHED.IMG", 2)
+INSTRUMENT_ID = "MAST_LEFT"
+START_TIME = 2021-03-05T12:21:10.552Z
+GROUP = GEOMETRIC_CAMERA_MODEL_PARMS
+  MODEL_TYPE = CAHV
+  MODEL_COMPONENT_1 = (1.0, 2.5, -3.0E-1)
+  FILTER_CENTER_WAVELENGTH = 630.0 <NM>
+END_GROUP = GEOMETRIC_CAMERA_MODEL_PARMS
+OBJECT = IMAGE
+  LINES = 2
+  LINE_SAMPLES = 2
+  SAMPLE_TYPE = MSB_UNSIGNED_INTEGER
+  SAMPLE_BITS = 16
+  SAMPLE_BIT_MASK = 2#0000111111111111#
+  BANDS = 2
+  BAND_STORAGE_TYPE = LINE_INTERLEAVED
+  LINE_PREFIX_BYTES = 4
+END_OBJECT = IMAGE
+END
+"#;
+
+#[test]
+fn test_parse_label() {
+    let label = pds3::Label::parse_str(TEST_LABEL).unwrap();
+
+    assert_eq!(label.get_str("PDS_VERSION_ID"), Some("PDS3"));
+    assert_eq!(label.get_i64("RECORD_BYTES"), Some(8));
+    assert_eq!(label.get_str("INSTRUMENT_ID"), Some("MAST_LEFT"));
+    assert_eq!(
+        label.get_str("START_TIME"),
+        Some("2021-03-05T12:21:10.552Z")
+    );
+
+    let group = label.find_block("GEOMETRIC_CAMERA_MODEL_PARMS").unwrap();
+    assert_eq!(group.block_type, pds3::BlockType::Group);
+    assert_eq!(group.get_str("MODEL_TYPE"), Some("CAHV"));
+    assert_eq!(
+        group.get("MODEL_COMPONENT_1").unwrap().as_f64_vec(),
+        Some(vec![1.0, 2.5, -0.3])
+    );
+    let wavelength = group.get("FILTER_CENTER_WAVELENGTH").unwrap();
+    assert_eq!(wavelength.as_f64(), Some(630.0));
+    assert_eq!(wavelength.units(), Some("NM"));
+
+    let image = label.find_object("IMAGE").unwrap();
+    assert_eq!(image.get_i64("SAMPLE_BIT_MASK"), Some(4095));
+    assert_eq!(image.get_i64("LINE_PREFIX_BYTES"), Some(4));
+}
+
+#[test]
+fn test_parse_label_unbalanced() {
+    assert!(pds3::Label::parse_str("OBJECT = IMAGE\nLINES = 2\nEND\n").is_err());
+    assert!(pds3::Label::parse_str("END_OBJECT = IMAGE\nEND\n").is_err());
+}
+
+#[test]
+fn test_read_detached_line_interleaved() {
+    let label_path = common::test_output_path("sciimg_pds3_tests", "TEST_DETACHED.LBL");
+    fs::write(&label_path, TEST_LABEL).unwrap();
+
+    // One 8-byte record to skip, then (prefix + 2 samples) per band per line
+    let mut data: Vec<u8> = vec![0xFF; 8];
+    for line in 0..2_u16 {
+        for band in 0..2_u16 {
+            data.extend_from_slice(&[0xAA; 4]);
+            for sample in 0..2_u16 {
+                let value = band * 1000 + line * 10 + sample;
+                data.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+    }
+    fs::write(
+        common::test_output_path("sciimg_pds3_tests", "TEST_DETACHED.IMG"),
+        data,
+    )
+    .unwrap();
+
+    let img = Image::open(label_path.to_str().unwrap()).unwrap();
+    assert_eq!(img.width, 2);
+    assert_eq!(img.height, 2);
+    assert_eq!(img.num_bands(), 2);
+    assert_eq!(img.get_mode(), ImageMode::U12BIT);
+    assert_eq!(img.get_band(0).get(1, 0), 1.0);
+    assert_eq!(img.get_band(0).get(0, 1), 10.0);
+    assert_eq!(img.get_band(1).get(1, 1), 1011.0);
+}
+
+#[test]
+fn test_read_attached_8bit() {
+    let label_text = "PDS_VERSION_ID = PDS3\r\n\
+        RECORD_TYPE = FIXED_LENGTH\r\n\
+        RECORD_BYTES = 256\r\n\
+        ^IMAGE = 2\r\n\
+        OBJECT = IMAGE\r\n\
+        LINES = 3\r\n\
+        LINE_SAMPLES = 4\r\n\
+        SAMPLE_TYPE = UNSIGNED_INTEGER\r\n\
+        SAMPLE_BITS = 8\r\n\
+        END_OBJECT = IMAGE\r\n\
+        END\r\n";
+
+    let mut data = label_text.as_bytes().to_vec();
+    data.resize(256, b' ');
+    data.extend((0..12).map(|v| v as u8 * 2));
+
+    let img_path = common::test_output_path("sciimg_pds3_tests", "TEST_ATTACHED.IMG");
+    fs::write(&img_path, data).unwrap();
+
+    let img = pds3::read_image(img_path.to_str().unwrap()).unwrap();
+    assert_eq!(img.width, 4);
+    assert_eq!(img.height, 3);
+    assert_eq!(img.num_bands(), 1);
+    assert_eq!(img.get_mode(), ImageMode::U8BIT);
+    assert_eq!(img.get_band(0).get(3, 2), 22.0);
+}
+
+#[test]
+fn test_read_invalid_dimensions() {
+    let img_path = common::test_output_path("sciimg_pds3_tests", "TEST_INVALID.IMG");
+    for (lines, line_samples, prefix) in [
+        ("-3", "4", "0"),
+        ("3", "-4", "0"),
+        ("3", "4", "-1"),
+        ("3", "9223372036854775807", "0"),
+    ] {
+        let label_text = format!(
+            "PDS_VERSION_ID = PDS3\r\n\
+            RECORD_TYPE = FIXED_LENGTH\r\n\
+            RECORD_BYTES = 256\r\n\
+            ^IMAGE = 2\r\n\
+            OBJECT = IMAGE\r\n\
+            LINES = {}\r\n\
+            LINE_SAMPLES = {}\r\n\
+            LINE_PREFIX_BYTES = {}\r\n\
+            SAMPLE_TYPE = MSB_UNSIGNED_INTEGER\r\n\
+            SAMPLE_BITS = 16\r\n\
+            END_OBJECT = IMAGE\r\n\
+            END\r\n",
+            lines, line_samples, prefix
+        );
+        let mut data = label_text.as_bytes().to_vec();
+        data.resize(256 + 24, 0);
+        fs::write(&img_path, data).unwrap();
+
+        assert!(matches!(
+            Image::open(img_path.to_str().unwrap()),
+            Err(SciImgError::InvalidFormat(_))
+        ));
+    }
+}
+
+#[test]
+fn test_label_read_stops_at_end() {
+    // Data following the END statement is never parsed as part of the label
+    let mut data = b"PDS_VERSION_ID = PDS3\nRECORD_BYTES = 8\nEND\n".to_vec();
+    data.extend_from_slice(b"OBJECT = IMAGE\n\xFF\xFE\x00");
+    let path = common::test_output_path("sciimg_pds3_tests", "TEST_LABEL_END.IMG");
+    fs::write(&path, data).unwrap();
+
+    let label = pds3::Label::from_file(path.to_str().unwrap()).unwrap();
+    assert_eq!(label.get_i64("RECORD_BYTES"), Some(8));
+    assert!(label.find_object("IMAGE").is_none());
+}
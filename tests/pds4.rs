@@ -0,0 +1,114 @@
+mod common;
+
+use sciimg::{enums::ImageMode, image::Image, pds4, raster::Interleave};
+use std::fs;
+
+const TEST_LABEL: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Product_Observational xmlns="http://pds.nasa.gov/pds4/pds/v1"
+    xmlns:img="http://pds.nasa.gov/pds4/img/v1">
+  <Identification_Area>
+    <logical_identifier>urn:nasa:pds:mars2020_test:data:test_product</logical_identifier>
+    <version_id>1.0</version_id>
+  </Identification_Area>
+  <Observation_Area>
+    <Time_Coordinates>
+      <start_date_time>2021-03-05T12:21:10.552Z</start_date_time>
+    </Time_Coordinates>
+    <Discipline_Area>
+      <img:Exposure>
+        <img:exposure_duration unit="ms">12.5</img:exposure_duration>
+      </img:Exposure>
+    </Discipline_Area>
+  </Observation_Area>
+  <File_Area_Observational>
+    <File>
+      <file_name>test_product.dat</file_name>
+    </File>
+    <Array_3D_Image>
+      <local_identifier>IMAGE_DATA</local_identifier>
+      <offset unit="byte">16</offset>
+      <axes>3</axes>
+      <axis_index_order>Last Index Fastest</axis_index_order>
+      <Element_Array>
+        <data_type>SignedLSB2</data_type>
+        <scaling_factor>0.5</scaling_factor>
+        <value_offset>10</value_offset>
+      </Element_Array>
+      <Axis_Array>
+        <axis_name>Sample</axis_name>
+        <elements>3</elements>
+        <sequence_number>2</sequence_number>
+      </Axis_Array>
+      <Axis_Array>
+        <axis_name>Line</axis_name>
+        <elements>2</elements>
+        <sequence_number>1</sequence_number>
+      </Axis_Array>
+      <Axis_Array>
+        <axis_name>Band</axis_name>
+        <elements>2</elements>
+        <sequence_number>3</sequence_number>
+      </Axis_Array>
+    </Array_3D_Image>
+  </File_Area_Observational>
+</Product_Observational>
+"#;
+
+#[test]
+fn test_parse_label() {
+    let label = pds4::Label::parse_str(TEST_LABEL).unwrap();
+
+    assert_eq!(
+        label.logical_identifier(),
+        Some("urn:nasa:pds:mars2020_test:data:test_product")
+    );
+    assert_eq!(
+        label.text_at("Observation_Area/Time_Coordinates/start_date_time"),
+        Some("2021-03-05T12:21:10.552Z")
+    );
+
+    let exposure = label.find("exposure_duration").unwrap();
+    assert_eq!(exposure.text_as_f64(), Some(12.5));
+    assert_eq!(exposure.attribute("unit"), Some("ms"));
+
+    assert_eq!(label.arrays.len(), 1);
+    let array = &label.arrays[0];
+    assert_eq!(array.file_name, "test_product.dat");
+    assert_eq!(array.offset, 16);
+    assert_eq!(array.width(), 3);
+    assert_eq!(array.height(), 2);
+    assert_eq!(array.bands(), 2);
+    assert_eq!(array.interleave().unwrap(), Interleave::SampleInterleaved);
+}
+
+#[test]
+fn test_read_sample_interleaved() {
+    let label_path = common::test_output_path("sciimg_pds4_tests", "test_product.xml");
+    fs::write(&label_path, TEST_LABEL).unwrap();
+
+    let mut data: Vec<u8> = vec![0; 16];
+    for line in 0..2_i16 {
+        for sample in 0..3_i16 {
+            for band in 0..2_i16 {
+                let value = -(band * 100 + line * 10 + sample);
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+    fs::write(
+        common::test_output_path("sciimg_pds4_tests", "test_product.dat"),
+        data,
+    )
+    .unwrap();
+
+    let img = Image::open(label_path.to_str().unwrap()).unwrap();
+    assert_eq!(img.width, 3);
+    assert_eq!(img.height, 2);
+    assert_eq!(img.num_bands(), 2);
+    assert_eq!(img.get_mode(), ImageMode::U16BIT);
+
+    // Raw values are scaled by 0.5 and offset by 10
+    assert_eq!(img.get_band(0).get(0, 0), 10.0);
+    assert_eq!(img.get_band(0).get(2, 1), 4.0);
+    assert_eq!(img.get_band(1).get(1, 0), -40.5);
+}
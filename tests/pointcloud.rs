@@ -0,0 +1,238 @@
+mod common;
+
+use sciimg::{
+    camera::{
+        cahv::Cahv,
+        model::{CameraModel, ImageCoordinate},
+    },
+    enums::ImageMode,
+    image::Image,
+    imagebuffer::ImageBuffer,
+    pointcloud::{self, Mesh},
+    vector::Vector,
+};
+use std::fs;
+
+// Camera looking down +X, centered on a 32x24 image
+fn test_model(c: Vector) -> CameraModel {
+    let a = Vector::new(1.0, 0.0, 0.0);
+    CameraModel::new(Box::new(Cahv {
+        c,
+        a,
+        h: Vector::new(0.0, 40.0, 0.0).add(&a.scale(16.0)),
+        v: Vector::new(0.0, 0.0, 40.0).add(&a.scale(12.0)),
+    }))
+}
+
+fn look(model: &CameraModel, x: usize, y: usize) -> Vector {
+    model
+        .ls_to_look_vector(&ImageCoordinate {
+            line: y as f64,
+            sample: x as f64,
+        })
+        .unwrap()
+        .intersect_to_sphere(1.0)
+        .subtract(&model.c())
+}
+
+#[test]
+fn test_xyz_from_range() {
+    let model = test_model(Vector::new(1.0, 2.0, 3.0));
+    let mut range = ImageBuffer::new_with_fill(32, 24, 10.0).unwrap();
+    range.put(3, 4, 0.0);
+    range.put_mask(5, 6, false);
+
+    let xyz = pointcloud::xyz_from_range(&range, &model).unwrap();
+    assert_eq!(xyz.num_bands(), 3);
+    assert!(!xyz.get_alpha_at(3, 4));
+    assert!(!xyz.get_alpha_at(5, 6));
+    assert_eq!(xyz.get_band(0).get(5, 6), 0.0);
+    assert_eq!(
+        xyz.get_metadata()
+            .camera_model
+            .as_ref()
+            .unwrap()
+            .serialize(),
+        model.serialize()
+    );
+
+    for (x, y) in [(16, 12), (0, 0), (31, 23)] {
+        assert!(xyz.get_alpha_at(x, y));
+        let p = Vector::new(
+            xyz.get_band(0).get(x, y) as f64,
+            xyz.get_band(1).get(x, y) as f64,
+            xyz.get_band(2).get(x, y) as f64,
+        );
+        assert!((p.distance_to(&model.c()) - 10.0).abs() < 1.0e-4);
+        let ls = model.xyz_to_ls(&p, false);
+        assert!((ls.sample - x as f64).abs() < 1.0e-3 && (ls.line - y as f64).abs() < 1.0e-3);
+    }
+}
+
+#[test]
+fn test_xyz_from_disparity() {
+    let left = test_model(Vector::new(0.0, 0.0, 0.0));
+    let right = test_model(Vector::new(0.0, 0.3, 0.0));
+
+    let mut disparity = ImageBuffer::new_with_mask_as(32, 24, false).unwrap();
+    let mut expected = vec![];
+    for (x, y, r) in [(16, 12, 4.0), (2, 20, 7.5), (30, 3, 2.0)] {
+        let p = look(&left, x, y).scale(r);
+        let ls = right.xyz_to_ls(&p, false);
+        disparity.put_mask(x, y, true);
+        disparity.put(x, y, (x as f64 - ls.sample) as f32);
+        expected.push((x, y, p));
+    }
+
+    let xyz = pointcloud::xyz_from_disparity(&disparity, &left, &right).unwrap();
+    assert!(!xyz.get_alpha_at(0, 0));
+    for (x, y, p) in expected {
+        assert!(xyz.get_alpha_at(x, y));
+        let found = Vector::new(
+            xyz.get_band(0).get(x, y) as f64,
+            xyz.get_band(1).get(x, y) as f64,
+            xyz.get_band(2).get(x, y) as f64,
+        );
+        assert!(found.distance_to(&p) < 1.0e-3);
+    }
+}
+
+fn grid_xyz() -> Image {
+    let mut xyz = Image::new_with_bands_masked(3, 3, 3, ImageMode::U16BIT, true).unwrap();
+    for y in 0..3 {
+        for x in 0..3 {
+            xyz.put(x, y, 5.0, 0);
+            xyz.put(x, y, x as f32 * 0.1, 1);
+            xyz.put(x, y, y as f32 * 0.1, 2);
+        }
+    }
+    xyz
+}
+
+#[test]
+fn test_mesh_from_xyz() {
+    let mut xyz = grid_xyz();
+    let mesh = Mesh::from_xyz(&xyz, None, None).unwrap();
+    assert_eq!(mesh.vertices.len(), 9);
+    assert_eq!(mesh.faces.len(), 8);
+    assert!(!mesh.has_colors());
+
+    // Dropping a corner removes the triangle which used it, and a far point removes those
+    // which span the discontinuity
+    xyz.put_alpha(2, 2, false);
+    xyz.put(0, 0, 50.0, 0);
+    let mesh = Mesh::from_xyz(&xyz, None, Some(1.0)).unwrap();
+    assert_eq!(mesh.vertices.len(), 8);
+    assert_eq!(mesh.faces.len(), 6);
+
+    let bad = Image::new_with_bands(3, 3, 2, ImageMode::U16BIT).unwrap();
+    assert!(Mesh::from_xyz(&bad, None, None).is_err());
+}
+
+#[test]
+fn test_mesh_export() {
+    let xyz = grid_xyz();
+    let mut colors = Image::new_with_bands(3, 3, 1, ImageMode::U12BIT).unwrap();
+    colors.put(1, 0, 2033.0, 0);
+    let mesh = Mesh::from_xyz(&xyz, Some(&colors), None).unwrap();
+    assert_eq!(mesh.colors[1], [255, 255, 255]);
+
+    let ply_path = common::test_output_path("sciimg_pointcloud_tests", "test_mesh.ply");
+    mesh.save_ply(ply_path.to_str().unwrap()).unwrap();
+    let ply = fs::read_to_string(&ply_path).unwrap();
+    let lines: Vec<&str> = ply.lines().collect();
+    assert_eq!(lines[0], "ply");
+    assert!(lines.contains(&"element vertex 9"));
+    assert!(lines.contains(&"element face 8"));
+    assert!(lines.contains(&"property uchar red"));
+    let header_end = lines.iter().position(|l| *l == "end_header").unwrap();
+    assert_eq!(lines[header_end + 2], "5 0.1 0 255 255 255");
+    assert_eq!(lines.len(), header_end + 1 + 9 + 8);
+    assert!(lines[header_end + 10].starts_with("3 "));
+
+    let obj_path = common::test_output_path("sciimg_pointcloud_tests", "test_mesh.obj");
+    mesh.save_obj(obj_path.to_str().unwrap()).unwrap();
+    let obj = fs::read_to_string(&obj_path).unwrap();
+    assert!(obj.contains("\nv 5 0.1 0 1 1 1\n"));
+    assert!(obj.contains("\nf 1 4 2\n"));
+    assert_eq!(obj.lines().filter(|l| l.starts_with("f ")).count(), 8);
+}
+
+// Plane rising along X, z = 0.2x, seen from a camera above it
+fn tilted_plane() -> (Image, CameraModel) {
+    let mut xyz = Image::new_with_bands_masked(8, 6, 3, ImageMode::U16BIT, true).unwrap();
+    for y in 0..6 {
+        for x in 0..8 {
+            xyz.put(x, y, x as f32 * 0.1, 0);
+            xyz.put(x, y, y as f32 * 0.1, 1);
+            xyz.put(x, y, x as f32 * 0.02, 2);
+        }
+    }
+    xyz.put_alpha(3, 3, false);
+
+    let a = Vector::new(0.0, 0.0, -1.0);
+    let model = CameraModel::new(Box::new(Cahv {
+        c: Vector::new(0.4, 0.3, 10.0),
+        a,
+        h: Vector::new(40.0, 0.0, 0.0).add(&a.scale(4.0)),
+        v: Vector::new(0.0, 40.0, 0.0).add(&a.scale(3.0)),
+    }));
+    (xyz, model)
+}
+
+#[test]
+fn test_range_and_normals() {
+    let (xyz, model) = tilted_plane();
+
+    let range = pointcloud::range_map(&xyz, &model).unwrap();
+    assert!(!range.get_mask_at_point(3, 3));
+    let expected = Vector::new(0.5, 0.2, 0.1).distance_to(&model.c());
+    assert!((range.get(5, 2) as f64 - expected).abs() < 1.0e-5);
+
+    let normals = pointcloud::surface_normals(&xyz, &model).unwrap();
+    let expected = Vector::new(-0.2, 0.0, 1.0).normalized();
+    for (x, y) in [(0, 0), (7, 5), (2, 3), (3, 2), (5, 1)] {
+        assert!(normals.get_alpha_at(x, y));
+        for (b, e) in [expected.x, expected.y, expected.z].iter().enumerate() {
+            assert!((normals.get_band(b).get(x, y) as f64 - e).abs() < 1.0e-5);
+        }
+    }
+    assert!(!normals.get_alpha_at(3, 3));
+    assert!(!normals.get_band(2).get_mask_at_point(3, 3));
+
+    let gravity = Vector::new(0.0, 0.0, -3.71);
+    let slope = pointcloud::slope_map(&normals, &gravity).unwrap();
+    assert!((slope.get(1, 1) as f64 - 0.2_f64.atan().to_degrees()).abs() < 1.0e-3);
+    assert!(!slope.get_mask_at_point(3, 3));
+
+    // Relative to a gravity vector along the plane's normal, the plane is level
+    let slope = pointcloud::slope_map(&normals, &expected.inversed()).unwrap();
+    assert!(slope.get(6, 4).abs() < 1.0e-2);
+}
+
+#[test]
+fn test_roughness() {
+    let (mut xyz, _) = tilted_plane();
+    let gravity = Vector::new(0.0, 0.0, -1.0);
+
+    let roughness = pointcloud::roughness_map(&xyz, &gravity, 3).unwrap();
+    assert!(roughness.get_mask_at_point(0, 0));
+    assert!(!roughness.get_mask_at_point(3, 3));
+    for y in 0..6 {
+        for x in 0..8 {
+            if roughness.get_mask_at_point(x, y) {
+                assert!(roughness.get(x, y) < 1.0e-5);
+            }
+        }
+    }
+
+    // A bump only affects windows containing it
+    xyz.put(6, 1, 0.12 + 0.05, 2);
+    let roughness = pointcloud::roughness_map(&xyz, &gravity, 3).unwrap();
+    assert!(roughness.get(6, 1) > 0.01);
+    assert!(roughness.get(5, 2) > 0.005);
+    assert!(roughness.get(2, 4) < 1.0e-5);
+
+    assert!(pointcloud::roughness_map(&xyz, &gravity, 4).is_err());
+    assert!(pointcloud::roughness_map(&xyz, &Vector::default(), 3).is_err());
+}
@@ -0,0 +1,216 @@
+use sciimg::{
+    camera::{
+        cahv::Cahv,
+        cahvor::Cahvor,
+        cahvore::{Cahvore, PupilType},
+        model::{CameraModel, ImageCoordinate, ModelType},
+    },
+    enums::ImageMode,
+    image::Image,
+    reproject::{self, Interpolation},
+    vector::Vector,
+};
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 48;
+
+// Pinhole camera looking down +X with a 100 pixel focal length, centered in the image
+fn test_cahv() -> Cahv {
+    let a = Vector::new(1.0, 0.0, 0.0);
+    Cahv {
+        c: Vector::new(0.0, 0.0, 0.0),
+        a,
+        h: Vector::new(0.0, 100.0, 0.0).add(&a.scale(WIDTH as f64 / 2.0)),
+        v: Vector::new(0.0, 0.0, 100.0).add(&a.scale(HEIGHT as f64 / 2.0)),
+    }
+}
+
+fn ramp_image() -> Image {
+    let mut img = Image::new_with_bands(WIDTH, HEIGHT, 2, ImageMode::U16BIT).unwrap();
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            img.put(x, y, (x * 10 + y * 3) as f32, 0);
+            img.put(x, y, (x * x + y) as f32, 1);
+        }
+    }
+    img
+}
+
+#[test]
+fn test_reproject_identity() {
+    let img = ramp_image();
+    let model = CameraModel::new(Box::new(test_cahv()));
+
+    for interpolation in [Interpolation::Bilinear, Interpolation::Bicubic] {
+        let out =
+            reproject::reproject(&img, &model, &model, WIDTH, HEIGHT, interpolation, None).unwrap();
+        assert!(out.is_using_alpha());
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                assert!(out.get_alpha_at(x, y));
+                for b in 0..2 {
+                    let expected = img.get_band(b).get(x, y);
+                    assert!((out.get_band(b).get(x, y) - expected).abs() < 0.01);
+                }
+            }
+        }
+        assert_eq!(
+            out.get_metadata().history.last().unwrap().parameters["interpolation"],
+            interpolation.name()
+        );
+    }
+}
+
+#[test]
+fn test_reproject_subframe() {
+    let img = ramp_image();
+    let model = CameraModel::new(Box::new(test_cahv()));
+    let target = model.subframe(20.5, 10.0).unwrap();
+
+    let out = reproject::reproject(
+        &img,
+        &model,
+        &target,
+        50,
+        40,
+        Interpolation::Bicubic,
+        Some(10.0),
+    )
+    .unwrap();
+    assert_eq!(out.width, 50);
+    assert_eq!(out.get_mode(), ImageMode::U16BIT);
+    assert_eq!(
+        out.get_metadata()
+            .camera_model
+            .as_ref()
+            .unwrap()
+            .serialize(),
+        target.serialize()
+    );
+
+    // Inside the source, a half pixel shift of a quadratic ramp is reproduced exactly
+    for (x, y) in [(0, 0), (10, 5), (30, 20), (41, 37)] {
+        let sx = x as f32 + 20.5;
+        let sy = (y + 10) as f32;
+        assert!(out.get_alpha_at(x, y));
+        assert!((out.get_band(0).get(x, y) - (sx * 10.0 + sy * 3.0)).abs() < 0.01);
+        assert!((out.get_band(1).get(x, y) - (sx * sx + sy)).abs() < 0.01);
+    }
+
+    // Past the right and bottom edges of the source
+    assert!(!out.get_alpha_at(43, 0));
+    assert!(!out.get_alpha_at(0, 38));
+    assert_eq!(out.get_band(0).get(49, 39), 0.0);
+}
+
+#[test]
+fn test_reproject_cahvore_to_linear() {
+    let img = ramp_image();
+    let cahv = test_cahv();
+    let cahvore = CameraModel::new(Box::new(Cahvore {
+        c: cahv.c,
+        a: cahv.a,
+        h: cahv.h,
+        v: cahv.v,
+        o: cahv.a,
+        r: Vector::new(0.0, -0.05, 0.01),
+        e: Vector::new(0.0, 0.0, 0.0),
+        pupil_type: PupilType::Perspective,
+        linearity: 1.0,
+    }));
+    let linear = cahvore.linearize(WIDTH, HEIGHT, WIDTH, HEIGHT).unwrap();
+
+    let out = reproject::reproject(
+        &img,
+        &cahvore,
+        &linear,
+        WIDTH,
+        HEIGHT,
+        Interpolation::Bilinear,
+        None,
+    )
+    .unwrap();
+
+    // Each valid pixel carries the source value at the location it projects to
+    let mut valid = 0;
+    for (x, y) in [(32, 24), (5, 5), (60, 40), (10, 44)] {
+        if !out.get_alpha_at(x, y) {
+            continue;
+        }
+        valid += 1;
+        let lv = linear
+            .ls_to_look_vector(&ImageCoordinate {
+                line: y as f64,
+                sample: x as f64,
+            })
+            .unwrap();
+        let ls = cahvore.xyz_to_ls(&lv.look_direction.scale(1.0e8), false);
+        let expected = reproject::sample_bilinear(img.get_band(0), ls.sample, ls.line).unwrap();
+        assert!((out.get_band(0).get(x, y) - expected).abs() < 0.05);
+    }
+    assert!(out.get_alpha_at(32, 24));
+    assert!(valid > 1);
+}
+
+#[test]
+fn test_linearize_with_model() {
+    let cahv = test_cahv();
+    let cahvor = CameraModel::new(Box::new(Cahvor {
+        c: cahv.c,
+        a: cahv.a,
+        h: cahv.h,
+        v: cahv.v,
+        o: cahv.a,
+        r: Vector::new(0.0, -0.08, 0.02),
+    }));
+
+    // Render a scene whose brightness varies with the direction of view, so the linearized
+    // image can be checked against the same scene seen through the linear model
+    let scene = |d: &Vector| (1000.0 + 800.0 * d.y / d.x + 300.0 * d.z / d.x) as f32;
+    let look = |model: &CameraModel, x: usize, y: usize| {
+        model
+            .ls_to_look_vector(&ImageCoordinate {
+                line: y as f64,
+                sample: x as f64,
+            })
+            .unwrap()
+            .look_direction
+    };
+
+    let mut img = Image::new_with_bands(WIDTH, HEIGHT, 1, ImageMode::U16BIT).unwrap();
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            img.put(x, y, scene(&look(&cahvor, x, y)), 0);
+        }
+    }
+    img.get_metadata_mut().camera_model = Some(cahvor.clone());
+
+    let (linearized, linear_model) = img.linearize_with_model(&cahvor, WIDTH, HEIGHT).unwrap();
+    assert!(linear_model.model_type() == ModelType::CAHV);
+    assert_eq!(linearized.width, WIDTH);
+    assert_eq!(
+        linearized.get_metadata().history.last().unwrap().operation,
+        "linearize"
+    );
+    assert_eq!(
+        linearized
+            .get_metadata()
+            .camera_model
+            .as_ref()
+            .unwrap()
+            .serialize(),
+        linear_model.serialize()
+    );
+
+    let mut valid = 0;
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            if linearized.get_alpha_at(x, y) {
+                valid += 1;
+                let expected = scene(&look(&linear_model, x, y));
+                assert!((linearized.get_band(0).get(x, y) - expected).abs() < 1.0);
+            }
+        }
+    }
+    assert!(valid > WIDTH * HEIGHT / 2);
+}
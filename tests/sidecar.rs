@@ -0,0 +1,95 @@
+mod common;
+
+use sciimg::{
+    camera::{
+        cahvor::Cahvor,
+        model::{CameraModel, ModelType},
+    },
+    decompanding,
+    enums::ImageMode,
+    error::SciImgError,
+    image::Image,
+    metadata::Metadata,
+    sidecar::{self, Sidecar},
+    vector::Vector,
+};
+use std::fs;
+
+fn test_model() -> CameraModel {
+    CameraModel::new(Box::new(Cahvor {
+        c: Vector::new(0.876, 0.121, -1.924),
+        a: Vector::new(0.962, 0.058, 0.267),
+        h: Vector::new(-1140.2, 3425.9, 412.3),
+        v: Vector::new(-105.8, 421.4, 3581.2),
+        o: Vector::new(0.962, 0.058, 0.267),
+        r: Vector::new(1.0e-6, -0.023, 0.012),
+    }))
+}
+
+#[test]
+fn test_sidecar_round_trip() {
+    let out_path = common::test_output_path("sciimg_sidecar_tests", "test_sidecar.png");
+    let out_path = out_path.to_str().unwrap();
+
+    let mut img = Image::new_with_bands(16, 12, 1, ImageMode::U8BIT).unwrap();
+    for y in 0..12 {
+        for x in 0..16 {
+            img.put(x, y, (y * 16 + x) as f32, 0);
+        }
+    }
+    img.get_metadata_mut().camera_model = Some(test_model());
+    img.get_metadata_mut().instrument = Some(String::from("NAVCAM_LEFT"));
+    img.decompand(&decompanding::ILT);
+    img.save_with_sidecar(out_path).unwrap();
+
+    let json = fs::read_to_string(sidecar::sidecar_path(out_path)).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["mode"], "U12BIT");
+    assert_eq!(value["metadata"]["camera_model"]["model_type"], "CAHVOR");
+
+    let read = Image::open_with_sidecar(out_path).unwrap();
+    assert_eq!(read.num_bands(), 1);
+    assert_eq!(read.get_mode(), ImageMode::U12BIT);
+    assert_eq!(read.get_band(0).get(15, 11), img.get_band(0).get(15, 11));
+
+    let metadata = read.get_metadata();
+    assert_eq!(metadata.instrument.as_deref(), Some("NAVCAM_LEFT"));
+    assert_eq!(metadata.history, img.get_metadata().history);
+
+    let model = metadata.camera_model.as_ref().unwrap();
+    assert!(model.model_type() == ModelType::CAHVOR);
+    assert_eq!(model.serialize(), test_model().serialize());
+}
+
+#[test]
+fn test_sidecar_serialized_model_string() {
+    let sidecar: Sidecar = serde_json::from_str(&format!(
+        r#"{{"width": 4, "height": 2, "bands": 3, "mode": "U16BIT",
+            "metadata": {{"camera_model": "{}"}}}}"#,
+        test_model().serialize()
+    ))
+    .unwrap();
+    assert_eq!(sidecar.bands, 3);
+
+    let model = sidecar.metadata.camera_model.unwrap();
+    assert!(model.model_type() == ModelType::CAHVOR);
+    assert_eq!(model.r(), Vector::new(1.0e-6, -0.023, 0.012));
+
+    let metadata: Metadata = serde_json::from_str("{}").unwrap();
+    assert!(metadata.camera_model.is_none());
+}
+
+#[test]
+fn test_open_without_sidecar() {
+    let out_path = common::test_output_path("sciimg_sidecar_tests", "test_no_sidecar.png");
+    let out_path = out_path.to_str().unwrap();
+
+    let img = Image::new_with_bands(4, 4, 3, ImageMode::U8BIT).unwrap();
+    img.save(out_path).unwrap();
+    let _ = fs::remove_file(sidecar::sidecar_path(out_path));
+
+    assert!(matches!(
+        Image::open_with_sidecar(out_path),
+        Err(SciImgError::FileNotFound(_))
+    ));
+}
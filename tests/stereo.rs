@@ -0,0 +1,176 @@
+use sciimg::{
+    camera::{
+        cahv::Cahv,
+        cahvor::Cahvor,
+        model::{CameraModel, ImageCoordinate, ModelType},
+    },
+    enums::ImageMode,
+    image::Image,
+    quaternion::Quaternion,
+    stereo,
+    vector::Vector,
+};
+
+// Stereo pair looking down +X with a 0.4 meter baseline along +Y
+fn stereo_pair() -> (CameraModel, CameraModel) {
+    let a = Vector::new(1.0, 0.0, 0.0);
+    let h = Vector::new(0.0, 500.0, 0.0).add(&a.scale(320.0));
+    let v = Vector::new(0.0, 0.0, 500.0).add(&a.scale(240.0));
+    let left = Cahv {
+        c: Vector::new(0.0, 0.0, 0.0),
+        a,
+        h,
+        v,
+    };
+    let right = Cahvor {
+        c: Vector::new(0.0, 0.4, 0.0),
+        a,
+        h,
+        v,
+        o: a,
+        r: Vector::new(0.0, -0.02, 0.005),
+    };
+    (
+        CameraModel::new(Box::new(left)),
+        CameraModel::new(Box::new(right)),
+    )
+}
+
+#[test]
+fn test_triangulate() {
+    let (left, right) = stereo_pair();
+
+    for p in [
+        Vector::new(5.0, 0.3, -0.2),
+        Vector::new(2.0, -0.5, 0.4),
+        Vector::new(12.0, 1.0, 0.8),
+    ] {
+        let l = left.xyz_to_ls(&p, false);
+        let r = right.xyz_to_ls(&p, false);
+
+        let t = stereo::triangulate(&left, &l, &right, &r).unwrap();
+        assert!(t.xyz.distance_to(&p) < 1.0e-6);
+        assert!(t.miss_distance < 1.0e-6);
+        assert!((t.range - p.len()).abs() < 1.0e-6);
+
+        // A one pixel disparity error moves the point by about the estimated range error
+        let shifted = ImageCoordinate {
+            sample: r.sample + 1.0,
+            line: r.line,
+        };
+        let t_shifted = stereo::triangulate(&left, &l, &right, &shifted).unwrap();
+        let range_change = (t_shifted.range - t.range).abs();
+        assert!(range_change > t.range_error * 0.5 && range_change < t.range_error * 2.0);
+
+        // A vertical error leaves the rays skew
+        let skewed = ImageCoordinate {
+            sample: r.sample,
+            line: r.line + 2.0,
+        };
+        let t_skewed = stereo::triangulate(&left, &l, &right, &skewed).unwrap();
+        assert!(t_skewed.miss_distance > 1.0e-4);
+    }
+}
+
+#[test]
+fn test_triangulate_rejects_parallel_and_diverging() {
+    let (left, right) = stereo_pair();
+    let center = ImageCoordinate {
+        sample: 320.0,
+        line: 240.0,
+    };
+    let diverging = ImageCoordinate {
+        sample: 330.0,
+        line: 240.0,
+    };
+
+    let cahv_right = CameraModel::new(Box::new(Cahv {
+        c: right.c(),
+        a: right.a(),
+        h: right.h(),
+        v: right.v(),
+    }));
+    assert!(stereo::triangulate(&left, &center, &cahv_right, &center).is_err());
+    assert!(stereo::triangulate(&left, &center, &right, &diverging).is_err());
+
+    let results = stereo::triangulate_matches(
+        &left,
+        &right,
+        &[
+            (center, diverging),
+            (
+                left.xyz_to_ls(&Vector::new(3.0, 0.1, 0.1), false),
+                right.xyz_to_ls(&Vector::new(3.0, 0.1, 0.1), false),
+            ),
+        ],
+    );
+    assert!(results[0].is_none());
+    assert!(
+        results[1]
+            .unwrap()
+            .xyz
+            .distance_to(&Vector::new(3.0, 0.1, 0.1))
+            < 1.0e-6
+    );
+}
+
+#[test]
+fn test_rectify() {
+    let (left, right) = stereo_pair();
+
+    // Toe the right camera in and roll it slightly about its own center
+    let right_center = right.c();
+    let right = right
+        .transform(&Quaternion::default(), &right_center.inversed())
+        .and_then(|m| {
+            m.transform(
+                &Quaternion::from_pitch_roll_yaw(0.02, 0.03, -0.05),
+                &right_center,
+            )
+        })
+        .unwrap();
+
+    let (left_rect, right_rect) =
+        stereo::rectify_models(&left, &right, 640, 480, 640, 480).unwrap();
+    assert!(left_rect.model_type() == ModelType::CAHV);
+    assert_eq!(left_rect.c(), left.c());
+    assert_eq!(right_rect.c(), right.c());
+    assert_eq!(left_rect.a(), right_rect.a());
+    assert_eq!(left_rect.h(), right_rect.h());
+    assert_eq!(left_rect.v(), right_rect.v());
+    assert!(left_rect.a().dot_product(&Vector::new(0.0, 1.0, 0.0)).abs() < 1.0e-12);
+
+    // Points fall on the same row in both rectified images, with the left sample greater
+    for p in [
+        Vector::new(5.0, 0.3, -0.2),
+        Vector::new(2.0, -0.5, 0.4),
+        Vector::new(12.0, 1.0, 0.8),
+    ] {
+        let l = left_rect.xyz_to_ls(&p, false);
+        let r = right_rect.xyz_to_ls(&p, false);
+        assert!((l.line - r.line).abs() < 1.0e-6);
+        assert!(l.sample > r.sample);
+
+        let t = stereo::triangulate(&left_rect, &l, &right_rect, &r).unwrap();
+        assert!(t.xyz.distance_to(&p) < 1.0e-6);
+    }
+
+    let mut left_img = Image::new_with_bands(640, 480, 1, ImageMode::U8BIT).unwrap();
+    let mut right_img = Image::new_with_bands(640, 480, 1, ImageMode::U8BIT).unwrap();
+    left_img.put(320, 240, 200.0, 0);
+    right_img.put(320, 240, 100.0, 0);
+
+    let pair = stereo::rectify(&left_img, &left, &right_img, &right, 320, 240).unwrap();
+    assert_eq!(pair.left.width, 320);
+    assert_eq!(pair.right.height, 240);
+    assert_eq!(pair.left_model.h(), pair.right_model.h());
+    assert!(pair.left.get_alpha_at(160, 120));
+    assert!(pair.right.get_alpha_at(160, 120));
+    assert_eq!(
+        pair.right.get_metadata().history.last().unwrap().operation,
+        "rectify"
+    );
+
+    let small = Image::new_with_bands(64, 48, 1, ImageMode::U8BIT).unwrap();
+    assert!(stereo::rectify(&left_img, &left, &small, &right, 320, 240).is_err());
+}
@@ -0,0 +1,233 @@
+mod common;
+
+use sciimg::{
+    enums::ImageMode, image::Image, imagebuffer::ImageBuffer, raster::SampleType, tiffio,
+};
+
+// Values well outside of the u16 range and with fractional parts that would be lost by rounding
+fn test_value(x: usize, y: usize, b: usize) -> f32 {
+    (x as f32 - 3.3) * 1234.567 + (y as f32) * 0.001 - b as f32 * 70000.125
+}
+
+fn test_image(bands: usize) -> Image {
+    let mut img = Image::new_with_bands(7, 5, bands, ImageMode::U16BIT).unwrap();
+    for b in 0..bands {
+        for y in 0..5 {
+            for x in 0..7 {
+                img.put(x, y, test_value(x, y, b), b);
+            }
+        }
+    }
+    img
+}
+
+fn assert_bit_exact(a: &Image, b: &Image) {
+    assert_eq!(a.width, b.width);
+    assert_eq!(a.height, b.height);
+    assert_eq!(a.num_bands(), b.num_bands());
+    for band in 0..a.num_bands() {
+        for y in 0..a.height {
+            for x in 0..a.width {
+                assert_eq!(
+                    a.get_band(band).get(x, y).to_bits(),
+                    b.get_band(band).get(x, y).to_bits()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_float_tiff_round_trip_mono() {
+    let out_path = common::test_output_path("sciimg_tiffio_tests", "test_mono.tif");
+    let out_path = out_path.to_str().unwrap();
+
+    let img = test_image(1);
+    img.save_float(out_path).unwrap();
+    assert!(tiffio::is_float_tiff(out_path));
+
+    let read = Image::open(out_path).unwrap();
+    assert_bit_exact(&img, &read);
+
+    let buffer = ImageBuffer::from_file(out_path).unwrap();
+    assert_eq!(buffer.get(6, 4), test_value(6, 4, 0));
+}
+
+#[test]
+fn test_float_tiff_round_trip_rgba() {
+    let out_path = common::test_output_path("sciimg_tiffio_tests", "test_rgba.tiff");
+    let out_path = out_path.to_str().unwrap();
+
+    let mut img = test_image(3);
+    img.init_alpha();
+    img.put_alpha(2, 3, false);
+    img.save_float(out_path).unwrap();
+
+    let read = Image::open(out_path).unwrap();
+    assert_bit_exact(&img, &read);
+    assert!(read.is_using_alpha());
+    assert!(!read.get_alpha_at(2, 3));
+    assert!(read.get_alpha_at(3, 2));
+}
+
+#[test]
+fn test_exr_round_trip_rgb() {
+    let out_path = common::test_output_path("sciimg_tiffio_tests", "test_rgb.exr");
+    let out_path = out_path.to_str().unwrap();
+
+    let img = test_image(3);
+    img.save_float(out_path).unwrap();
+
+    let read = Image::open(out_path).unwrap();
+    assert_bit_exact(&img, &read);
+}
+
+#[test]
+fn test_buffer_save_float() {
+    let out_path = common::test_output_path("sciimg_tiffio_tests", "test_buffer.tif");
+    let out_path = out_path.to_str().unwrap();
+
+    let mut buffer = ImageBuffer::new(4, 4).unwrap();
+    buffer.put(1, 2, -0.123_456_79);
+    buffer.put(3, 3, 1.0e9);
+    buffer.save_float(out_path).unwrap();
+
+    let read = ImageBuffer::from_file(out_path).unwrap();
+    assert_eq!(read.get(1, 2), -0.123_456_79);
+    assert_eq!(read.get(3, 3), 1.0e9);
+}
+
+#[test]
+fn test_save_float_unsupported() {
+    let img = test_image(2);
+    assert!(img
+        .save_float(
+            common::test_output_path("sciimg_tiffio_tests", "test_two_band.tif")
+                .to_str()
+                .unwrap()
+        )
+        .is_err());
+    assert!(test_image(1)
+        .save_float(
+            common::test_output_path("sciimg_tiffio_tests", "test_mono.png")
+                .to_str()
+                .unwrap()
+        )
+        .is_err());
+}
+
+fn test_multispectral_image(bands: usize, mode: ImageMode) -> Image {
+    let mut img = Image::new(7, 5, mode).unwrap();
+    for b in 0..bands {
+        let mut buffer = ImageBuffer::new_as_mode(7, 5, mode).unwrap();
+        for y in 0..5 {
+            for x in 0..7 {
+                buffer.put(x, y, (b * 35 + y * 7 + x) as f32);
+            }
+        }
+        img.push_band(&buffer);
+    }
+    img
+}
+
+#[test]
+fn test_multi_band_round_trip() {
+    for (mode, file_name) in [
+        (ImageMode::U8BIT, "test_6band_8bit.tif"),
+        (ImageMode::U16BIT, "test_6band_16bit.tif"),
+    ] {
+        let out_path = common::test_output_path("sciimg_tiffio_tests", file_name);
+        let out_path = out_path.to_str().unwrap();
+
+        let img = test_multispectral_image(6, mode);
+        img.save(out_path).unwrap();
+        assert!(tiffio::is_multipage_tiff(out_path));
+
+        let read = Image::open(out_path).unwrap();
+        assert_eq!(read.get_mode(), mode);
+        assert_bit_exact(&img, &read);
+    }
+}
+
+#[test]
+fn test_multi_band_float_round_trip() {
+    let out_path = common::test_output_path("sciimg_tiffio_tests", "test_6band_float.tif");
+    let out_path = out_path.to_str().unwrap();
+
+    let img = test_image(6);
+    tiffio::write_bands(&img, out_path, SampleType::Float32).unwrap();
+
+    let read = tiffio::read_bands(out_path).unwrap();
+    assert_bit_exact(&img, &read);
+}
+
+#[test]
+fn test_save_two_bands() {
+    let out_path = common::test_output_path("sciimg_tiffio_tests", "test_2band.tiff");
+    let out_path = out_path.to_str().unwrap();
+
+    let img = test_multispectral_image(2, ImageMode::U16BIT);
+    img.save(out_path).unwrap();
+    assert_bit_exact(&img, &Image::open(out_path).unwrap());
+
+    assert!(img
+        .save_bands(
+            common::test_output_path("sciimg_tiffio_tests", "test_2band.png")
+                .to_str()
+                .unwrap()
+        )
+        .is_err());
+}
+
+#[test]
+fn test_float_resave_round_trip() {
+    // Reloaded float images keep their values when saved again through `save`
+    for (first, second) in [
+        ("test_resave_a.tif", "test_resave_b.tif"),
+        ("test_resave_a.exr", "test_resave_b.exr"),
+    ] {
+        let first = common::test_output_path("sciimg_tiffio_tests", first);
+        let second = common::test_output_path("sciimg_tiffio_tests", second);
+        let (first, second) = (first.to_str().unwrap(), second.to_str().unwrap());
+
+        let img = test_image(3);
+        img.save_float(first).unwrap();
+
+        let read = Image::open(first).unwrap();
+        assert_eq!(read.get_mode(), ImageMode::U16BIT);
+        assert!(read.get_metadata().float_samples);
+        read.save(second).unwrap();
+
+        let resaved = Image::open(second).unwrap();
+        assert_bit_exact(&img, &resaved);
+        assert!(resaved.get_metadata().float_samples);
+    }
+
+    // Integer sources are unaffected
+    let path = common::test_output_path("sciimg_tiffio_tests", "test_resave_16bit.tif");
+    let path = path.to_str().unwrap();
+    let mut img = Image::new_with_bands(4, 4, 1, ImageMode::U16BIT).unwrap();
+    img.put(1, 1, 1234.0, 0);
+    img.save(path).unwrap();
+    let read = Image::open(path).unwrap();
+    assert!(!read.get_metadata().float_samples);
+    assert!(!tiffio::is_float_tiff(path));
+}
+
+#[test]
+fn test_multi_band_float_resave() {
+    let first = common::test_output_path("sciimg_tiffio_tests", "test_resave_6band_a.tif");
+    let second = common::test_output_path("sciimg_tiffio_tests", "test_resave_6band_b.tif");
+    let (first, second) = (first.to_str().unwrap(), second.to_str().unwrap());
+
+    let img = test_image(6);
+    tiffio::write_bands(&img, first, SampleType::Float32).unwrap();
+    let read = Image::open(first).unwrap();
+    assert!(read.get_metadata().float_samples);
+    read.save(second).unwrap();
+
+    let resaved = Image::open(second).unwrap();
+    for b in 0..6 {
+        assert_eq!(resaved.get_band(b).get(5, 3), test_value(5, 3, b));
+    }
+}
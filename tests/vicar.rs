@@ -0,0 +1,138 @@
+mod common;
+
+use sciimg::{
+    enums::ImageMode, image::Image, imagebuffer::ImageBuffer, pds3::Value, raster::SampleType,
+    vicar,
+};
+use std::fs;
+
+fn test_image(mode: ImageMode) -> Image {
+    let mut img = Image::new_with_bands(4, 3, 2, mode).unwrap();
+    for b in 0..2 {
+        for y in 0..3 {
+            for x in 0..4 {
+                img.put(x, y, (b * 100 + y * 10 + x) as f32, b);
+            }
+        }
+    }
+    img
+}
+
+#[test]
+fn test_parse_label() {
+    let label = vicar::Label::parse_str(
+        "LBLSIZE=200  FORMAT='HALF'  TYPE='IMAGE'  ORG='BIL'  NL=2  NS=3  NB=2  \
+         RECSIZE=6  PROPERTY='CAMERA'  NAME='It''s a camera'  OFFSET=(1.5,-2,3)  \
+         TASK='LABEL'  USER='tester'",
+    )
+    .unwrap();
+
+    assert_eq!(label.get_i64("LBLSIZE"), Some(200));
+    assert_eq!(label.get_str("FORMAT"), Some("HALF"));
+    assert_eq!(label.properties.len(), 1);
+    assert_eq!(label.history.len(), 1);
+
+    let camera = label.property("CAMERA").unwrap();
+    assert_eq!(camera.get_str("NAME"), Some("It's a camera"));
+    assert_eq!(
+        camera.get("OFFSET").unwrap().as_f64_vec(),
+        Some(vec![1.5, -2.0, 3.0])
+    );
+    assert_eq!(label.history[0].name, "LABEL");
+    assert_eq!(label.history[0].get_str("USER"), Some("tester"));
+
+    let layout = label.raster_layout().unwrap();
+    assert_eq!(layout.sample_type, SampleType::SignedInt16);
+    assert_eq!(layout.offset, 200);
+}
+
+#[test]
+fn test_write_read_byte() {
+    let out_path = common::test_output_path("sciimg_vicar_tests", "test_byte.vic");
+    let out_path = out_path.to_str().unwrap();
+
+    let img = test_image(ImageMode::U8BIT);
+    vicar::write_image(&img, out_path).unwrap();
+    assert!(vicar::is_vicar_file(out_path));
+
+    let label = vicar::read_label(out_path).unwrap();
+    assert_eq!(label.get_str("FORMAT"), Some("BYTE"));
+    assert_eq!(label.get_i64("LBLSIZE").unwrap() % 4, 0);
+    assert_eq!(label.history[0].name, "SCIIMG");
+
+    let read = Image::open(out_path).unwrap();
+    assert_eq!(read.width, 4);
+    assert_eq!(read.height, 3);
+    assert_eq!(read.num_bands(), 2);
+    assert_eq!(read.get_mode(), ImageMode::U8BIT);
+    assert_eq!(read.get_band(0).get(3, 2), 23.0);
+    assert_eq!(read.get_band(1).get(1, 1), 111.0);
+
+    let buffer = ImageBuffer::from_file(out_path).unwrap();
+    assert_eq!(buffer.get(2, 1), 12.0);
+}
+
+#[test]
+fn test_write_read_real_with_properties() {
+    let out_path = common::test_output_path("sciimg_vicar_tests", "test_real.vic");
+    let out_path = out_path.to_str().unwrap();
+
+    let mut img = test_image(ImageMode::U16BIT);
+    img.put(0, 0, 1234.5, 0);
+
+    let mut label = vicar::Label::default();
+    label
+        .property_mut("INSTRUMENT")
+        .set("NAME", Value::Text(String::from("MASTCAM")));
+    vicar::write_image_with_label(&img, out_path, SampleType::Float32, &label).unwrap();
+
+    let label = vicar::read_label(out_path).unwrap();
+    assert_eq!(label.get_str("FORMAT"), Some("REAL"));
+    assert_eq!(
+        label.property("INSTRUMENT").unwrap().get_str("NAME"),
+        Some("MASTCAM")
+    );
+
+    let read = vicar::read_image(out_path).unwrap();
+    assert_eq!(read.get_band(0).get(0, 0), 1234.5);
+    assert_eq!(read.get_band(1).get(3, 2), 123.0);
+}
+
+#[test]
+fn test_read_sample_interleaved_big_endian() {
+    let mut data = b"LBLSIZE=100  FORMAT='HALF'  ORG='BIP'  NL=2  NS=2  NB=2  RECSIZE=8  \
+                     INTFMT='HIGH'"
+        .to_vec();
+    data.resize(100, 0);
+    for line in 0..2_i16 {
+        for sample in 0..2_i16 {
+            for band in 0..2_i16 {
+                data.extend_from_slice(&(-(band * 100 + line * 10 + sample)).to_be_bytes());
+            }
+        }
+    }
+
+    let img_path = common::test_output_path("sciimg_vicar_tests", "test_bip.vic");
+    fs::write(&img_path, data).unwrap();
+
+    let img = vicar::read_image(img_path.to_str().unwrap()).unwrap();
+    assert_eq!(img.num_bands(), 2);
+    assert_eq!(img.get_band(0).get(1, 1), -11.0);
+    assert_eq!(img.get_band(1).get(0, 1), -110.0);
+}
+
+#[test]
+fn test_write_processing_history() {
+    let out_path = common::test_output_path("sciimg_vicar_tests", "test_history.vic");
+    let out_path = out_path.to_str().unwrap();
+
+    let mut img = test_image(ImageMode::U16BIT);
+    img.hot_pixel_correction(5, 2.5);
+    vicar::write_image(&img, out_path).unwrap();
+
+    let label = vicar::read_label(out_path).unwrap();
+    let names: Vec<&str> = label.history.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, vec!["HOT_PIXEL_CORRECTION", "SCIIMG"]);
+    assert_eq!(label.history[0].get_str("WINDOW_SIZE"), Some("5"));
+    assert_eq!(label.history[0].get_str("THRESHOLD"), Some("2.5"));
+}